@@ -0,0 +1,139 @@
+//! The vertex and face decoding used before the reader switched to buffered
+//! decoding: every value is a separate `read_exact` and indices go through a
+//! heap buffer sized at runtime. Kept only as a point of comparison.
+
+use pmx_rs::{Bdef1, Bdef2, Bdef4, Header, Sdef, Vertex, Weight};
+use std::io::{Error, ErrorKind, Read};
+
+pub struct Baseline<R> {
+    reader: R,
+    extended_uv: u8,
+    vertex_index: Vec<u8>,
+    bone_index: Vec<u8>,
+}
+
+fn invalid(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, what)
+}
+
+impl<R: Read> Baseline<R> {
+    pub fn new(reader: R, header: &Header) -> Self {
+        Self {
+            reader,
+            extended_uv: header.extended_uv,
+            vertex_index: vec![0; header.vertex_index_size as usize],
+            bone_index: vec![0; header.bone_index_size as usize],
+        }
+    }
+
+    fn read_bin<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut buffer = [0; N];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bin::<1>()?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.read_bin()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.read_bin()?))
+    }
+
+    fn read_vec<const N: usize>(&mut self) -> Result<[f32; N], Error> {
+        let mut buffer = [0.0f32; N];
+        for v in buffer.iter_mut() {
+            *v = self.read_f32()?;
+        }
+        Ok(buffer)
+    }
+
+    fn read_bone_index(&mut self) -> Result<Option<usize>, Error> {
+        let buffer = &mut self.bone_index;
+        self.reader.read_exact(buffer)?;
+        let v = match buffer.len() {
+            1 => i8::from_le_bytes([buffer[0]]) as i32,
+            2 => i16::from_le_bytes([buffer[0], buffer[1]]) as i32,
+            4 => i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]),
+            _ => unreachable!(),
+        };
+        Ok((v >= 0).then_some(v as usize))
+    }
+
+    fn read_vertex_index(&mut self) -> Result<Option<usize>, Error> {
+        let buffer = &mut self.vertex_index;
+        self.reader.read_exact(buffer)?;
+        match buffer.len() {
+            1 => Ok(Some(buffer[0] as usize)),
+            2 => Ok(Some(u16::from_le_bytes([buffer[0], buffer[1]]) as usize)),
+            4 => {
+                let v = i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+                Ok((v >= 0).then_some(v as usize))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn vertex(&mut self) -> Result<Vertex, Error> {
+        let position = self.read_vec()?;
+        let normal = self.read_vec()?;
+        let uv = self.read_vec()?;
+        let extended_uv = (0..self.extended_uv)
+            .map(|_| self.read_vec())
+            .collect::<Result<Vec<_>, Error>>()?;
+        let weight = match self.read_u8()? {
+            0 => Weight::Bdef1(Bdef1 {
+                bone: self.read_bone_index()?,
+            }),
+            1 => Weight::Bdef2(Bdef2 {
+                bones: [self.read_bone_index()?, self.read_bone_index()?],
+                weight: self.read_f32()?,
+            }),
+            2 => Weight::Bdef4(Bdef4 {
+                bones: [
+                    self.read_bone_index()?,
+                    self.read_bone_index()?,
+                    self.read_bone_index()?,
+                    self.read_bone_index()?,
+                ],
+                weights: self.read_vec()?,
+            }),
+            3 => Weight::Sdef(Sdef {
+                bones: [self.read_bone_index()?, self.read_bone_index()?],
+                weight: self.read_f32()?,
+                c: self.read_vec()?,
+                r0: self.read_vec()?,
+                r1: self.read_vec()?,
+            }),
+            _ => return Err(invalid("vertex::weight")),
+        };
+        let edge_ratio = self.read_f32()?;
+        Ok(Vertex {
+            position,
+            normal,
+            uv,
+            extended_uv,
+            weight,
+            edge_ratio,
+        })
+    }
+
+    pub fn vertices(&mut self) -> Result<Vec<Vertex>, Error> {
+        let len = self.read_u32()?;
+        (0..len).map(|_| self.vertex()).collect()
+    }
+
+    pub fn faces(&mut self) -> Result<Vec<u32>, Error> {
+        let len = self.read_u32()?;
+        let mut faces = vec![];
+        for _ in 0..len {
+            let index = self.read_vertex_index()?.ok_or_else(|| invalid("faces"))?;
+            faces.push(index as u32);
+        }
+        Ok(faces)
+    }
+}
@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use pmx_rs::lazy::{LazyReader, SectionKind};
+use std::io::Cursor;
+
+mod baseline;
+
+use baseline::Baseline;
+
+const ALICIA: &str = "resource/Alicia/Alicia_solid.pmx";
+
+fn read(c: &mut Criterion) {
+    let data = std::fs::read(ALICIA).unwrap();
+    c.bench_function("read/slice", |b| {
+        b.iter(|| pmx_rs::read(data.as_slice()).unwrap())
+    });
+    c.bench_function("read/file", |b| {
+        b.iter(|| {
+            let file = std::fs::File::open(ALICIA).unwrap();
+            pmx_rs::read(std::io::BufReader::new(file)).unwrap()
+        })
+    });
+}
+
+fn sections(c: &mut Criterion) {
+    let data = std::fs::read(ALICIA).unwrap();
+    let mut reader = LazyReader::new(Cursor::new(data.as_slice())).unwrap();
+    let header = *reader.header();
+    // the baseline reads from the same kind of source, positioned at the section
+    let section = |kind| {
+        let mut cursor = Cursor::new(data.as_slice());
+        cursor.set_position(reader.section(kind).offset);
+        cursor
+    };
+    let vertices = section(SectionKind::Vertices);
+    let faces = section(SectionKind::Faces);
+
+    let mut group = c.benchmark_group("vertices");
+    group.bench_function("baseline", |b| {
+        b.iter(|| Baseline::new(vertices.clone(), &header).vertices().unwrap())
+    });
+    group.bench_function("reader", |b| b.iter(|| reader.vertices().unwrap()));
+    group.finish();
+
+    let mut group = c.benchmark_group("faces");
+    group.bench_function("baseline", |b| {
+        b.iter(|| Baseline::new(faces.clone(), &header).faces().unwrap())
+    });
+    group.bench_function("reader", |b| b.iter(|| reader.faces().unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, read, sections);
+criterion_main!(benches);
@@ -0,0 +1,332 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use pmx_rs::*;
+use serde_json::Value;
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Inspects, validates and converts PMX models.
+#[derive(Parser)]
+#[command(name = "pmx", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the version, encoding, index sizes and element counts.
+    Info { path: PathBuf },
+    /// Prints the contents of a model.
+    Dump {
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Sections to print, all of them by default.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        sections: Vec<Section>,
+    },
+    /// Reports problems that make a model an invalid PMX file.
+    Validate { paths: Vec<PathBuf> },
+    /// Reports authoring mistakes such as unused textures or degenerate triangles.
+    ///
+    /// Fails if any lint has the `error` severity.
+    Lint {
+        paths: Vec<PathBuf>,
+        /// Overrides the severity of a rule, e.g. `--set unused-bone=allow`.
+        #[arg(long = "set", value_parser = parse_severity)]
+        severities: Vec<(lint::Rule, lint::Severity)>,
+    },
+    /// Converts between formats chosen by the file extensions.
+    ///
+    /// Reads PMX, PMD, glTF and GLB, and writes PMX, glTF, GLB and OBJ. Images
+    /// embedded in glTF input are written next to the output.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        /// Scale applied when importing or exporting glTF.
+        #[arg(long)]
+        scale: Option<f32>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Section {
+    Header,
+    ModelInfo,
+    Vertices,
+    Faces,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    DisplayGroups,
+    Rigids,
+    Joints,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Info { path } => info(&path),
+        Command::Dump {
+            path,
+            format,
+            sections,
+        } => dump(&path, format, &sections),
+        Command::Validate { paths } => validate(&paths),
+        Command::Lint { paths, severities } => run_lint(&paths, &severities),
+        Command::Convert {
+            input,
+            output,
+            scale,
+        } => convert(&input, &output, scale),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn load(path: &Path) -> Result<Pmx> {
+    load_with(
+        path,
+        gltf::ImportOptions {
+            embedded_images: gltf::EmbeddedImages::Ignore,
+            ..Default::default()
+        },
+    )
+}
+
+fn load_with(path: &Path, options: gltf::ImportOptions) -> Result<Pmx> {
+    match extension(path).as_str() {
+        "pmx" => {
+            let file = std::io::BufReader::new(std::fs::File::open(path)?);
+            Ok(read(file)?)
+        }
+        "pmd" => {
+            let file = std::io::BufReader::new(std::fs::File::open(path)?);
+            Ok(pmd::read(file)?)
+        }
+        "gltf" | "glb" => Ok(gltf::import(path, &options)?),
+        e => Err(format!("unknown input format: {:?}", e).into()),
+    }
+}
+
+fn info(path: &Path) -> Result<bool> {
+    let pmx = load(path)?;
+    let header = &pmx.header;
+    let sizes = header.index_sizes();
+    println!("name:        {}", pmx.model_info.name);
+    println!("name_en:     {}", pmx.model_info.name_en);
+    println!("version:     {:.1}", header.version);
+    println!("encoding:    {:?}", header.encoding);
+    println!("extended_uv: {}", header.extended_uv);
+    println!(
+        "index sizes: vertex {}, texture {}, material {}, bone {}, morph {}, rigid {}",
+        sizes.vertex, sizes.texture, sizes.material, sizes.bone, sizes.morph, sizes.rigid
+    );
+    println!("vertices:       {}", pmx.vertices.len());
+    println!("faces:          {}", pmx.faces.len() / 3);
+    println!("textures:       {}", pmx.textures.len());
+    println!("materials:      {}", pmx.materials.len());
+    println!("bones:          {}", pmx.bones.len());
+    println!("morphs:         {}", pmx.morphs.len());
+    println!("display groups: {}", pmx.display_groups.len());
+    println!("rigids:         {}", pmx.rigids.len());
+    println!("joints:         {}", pmx.joints.len());
+    Ok(true)
+}
+
+fn validate(paths: &[PathBuf]) -> Result<bool> {
+    let mut valid = true;
+    for path in paths {
+        let issues = load(path)?.validate();
+        if issues.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for issue in issues.iter() {
+            println!("{}: {}", path.display(), issue);
+        }
+        valid &= issues.is_empty();
+    }
+    Ok(valid)
+}
+
+fn parse_severity(s: &str) -> std::result::Result<(lint::Rule, lint::Severity), String> {
+    let (rule, severity) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected RULE=SEVERITY: {}", s))?;
+    let rule = lint::Rule::from_name(rule).ok_or_else(|| format!("unknown rule: {}", rule))?;
+    let severity = lint::Severity::from_name(severity)
+        .ok_or_else(|| format!("unknown severity: {}", severity))?;
+    Ok((rule, severity))
+}
+
+fn run_lint(paths: &[PathBuf], severities: &[(lint::Rule, lint::Severity)]) -> Result<bool> {
+    let mut options = lint::LintOptions::default();
+    for &(rule, severity) in severities {
+        options.set(rule, severity);
+    }
+    let mut passed = true;
+    for path in paths {
+        for lint in load(path)?.lint(&options) {
+            println!("{}: {}", path.display(), lint);
+            passed &= lint.severity < lint::Severity::Error;
+        }
+    }
+    Ok(passed)
+}
+
+fn convert(input: &Path, output: &Path, scale: Option<f32>) -> Result<bool> {
+    let output_dir = output.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut import = gltf::ImportOptions {
+        embedded_images: gltf::EmbeddedImages::Write(output_dir),
+        ..Default::default()
+    };
+    import.scale = scale.unwrap_or(import.scale);
+    let mut pmx = load_with(input, import)?;
+    // texture paths of glTF input are kept, embedded images having been written next to the output
+    let model_dir = match extension(input).as_str() {
+        "gltf" | "glb" => None,
+        _ => input.parent(),
+    };
+    match extension(output).as_str() {
+        "pmx" => {
+            pmx.update_index_sizes();
+            write(
+                std::io::BufWriter::new(std::fs::File::create(output)?),
+                &pmx,
+            )?;
+        }
+        "gltf" | "glb" => {
+            let mut options = gltf::ExportOptions {
+                model_dir,
+                ..Default::default()
+            };
+            options.scale = scale.unwrap_or(options.scale);
+            gltf::export(&pmx, output, &options)?;
+        }
+        "obj" => {
+            let options = obj::Options {
+                model_dir,
+                ..Default::default()
+            };
+            obj::export(&pmx, output, &options)?;
+        }
+        e => return Err(format!("unknown output format: {:?}", e).into()),
+    }
+    Ok(true)
+}
+
+fn dump(path: &Path, format: Format, sections: &[Section]) -> Result<bool> {
+    let pmx = load(path)?;
+    let all = [
+        Section::Header,
+        Section::ModelInfo,
+        Section::Vertices,
+        Section::Faces,
+        Section::Textures,
+        Section::Materials,
+        Section::Bones,
+        Section::Morphs,
+        Section::DisplayGroups,
+        Section::Rigids,
+        Section::Joints,
+    ];
+    let sections = if sections.is_empty() { &all } else { sections };
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let result = match format {
+        Format::Text => sections
+            .iter()
+            .try_for_each(|&section| dump_text(&mut out, &pmx, section)),
+        Format::Json => {
+            let object = sections
+                .iter()
+                .map(|&section| Ok((section_name(section).into(), section_json(&pmx, section)?)))
+                .collect::<serde_json::Result<serde_json::Map<_, _>>>()?;
+            serde_json::to_writer_pretty(&mut out, &object)
+                .map_err(std::io::Error::from)
+                .and_then(|_| writeln!(out))
+        }
+    };
+    match result.and_then(|_| out.flush()) {
+        // the reader went away, e.g. `pmx dump model.pmx | head`
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(true),
+        r => r.map(|_| true).map_err(Into::into),
+    }
+}
+
+fn section_name(section: Section) -> &'static str {
+    match section {
+        Section::Header => "header",
+        Section::ModelInfo => "model_info",
+        Section::Vertices => "vertices",
+        Section::Faces => "faces",
+        Section::Textures => "textures",
+        Section::Materials => "materials",
+        Section::Bones => "bones",
+        Section::Morphs => "morphs",
+        Section::DisplayGroups => "display_groups",
+        Section::Rigids => "rigids",
+        Section::Joints => "joints",
+    }
+}
+
+fn dump_text(out: &mut impl Write, pmx: &Pmx, section: Section) -> std::io::Result<()> {
+    fn list<T: std::fmt::Debug>(out: &mut impl Write, items: &[T]) -> std::io::Result<()> {
+        for (i, item) in items.iter().enumerate() {
+            writeln!(out, "[{}] {:?}", i, item)?;
+        }
+        Ok(())
+    }
+    writeln!(out, "# {}", section_name(section))?;
+    match section {
+        Section::Header => writeln!(out, "{:#?}", pmx.header),
+        Section::ModelInfo => writeln!(out, "{:#?}", pmx.model_info),
+        Section::Vertices => list(out, &pmx.vertices),
+        Section::Faces => list(out, &pmx.faces.chunks(3).collect::<Vec<_>>()),
+        Section::Textures => list(out, &pmx.textures),
+        Section::Materials => list(out, &pmx.materials),
+        Section::Bones => list(out, &pmx.bones),
+        Section::Morphs => list(out, &pmx.morphs),
+        Section::DisplayGroups => list(out, &pmx.display_groups),
+        Section::Rigids => list(out, &pmx.rigids),
+        Section::Joints => list(out, &pmx.joints),
+    }
+}
+
+fn section_json(pmx: &Pmx, section: Section) -> serde_json::Result<Value> {
+    match section {
+        Section::Header => serde_json::to_value(pmx.header),
+        Section::ModelInfo => serde_json::to_value(&pmx.model_info),
+        Section::Vertices => serde_json::to_value(&pmx.vertices),
+        Section::Faces => serde_json::to_value(pmx.faces.chunks(3).collect::<Vec<_>>()),
+        Section::Textures => serde_json::to_value(&pmx.textures),
+        Section::Materials => serde_json::to_value(&pmx.materials),
+        Section::Bones => serde_json::to_value(&pmx.bones),
+        Section::Morphs => serde_json::to_value(&pmx.morphs),
+        Section::DisplayGroups => serde_json::to_value(&pmx.display_groups),
+        Section::Rigids => serde_json::to_value(&pmx.rigids),
+        Section::Joints => serde_json::to_value(&pmx.joints),
+    }
+}
@@ -0,0 +1,343 @@
+//! Parsing from a byte slice without decoding the whole model.
+//!
+//! [`parse`] walks the file once to find where each section starts, keeping
+//! names as raw bytes and elements undecoded. Sections are decoded on demand,
+//! one element at a time, so scanning the names of many files allocates
+//! next to nothing.
+
+use crate::reader::{Error, Reader};
+use crate::*;
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+/// A string as stored in the file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Text<'a> {
+    bytes: &'a [u8],
+    encoding: Encoding,
+}
+
+impl<'a> Text<'a> {
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Decodes the string, borrowing it when it is valid UTF-8.
+    pub fn to_str(&self) -> Cow<'a, str> {
+        match self.encoding {
+            Encoding::Utf8 => String::from_utf8_lossy(self.bytes),
+            Encoding::Utf16 => {
+                let units = self
+                    .bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                Cow::Owned(String::from_utf16_lossy(&units))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Text<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.to_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ModelInfoRef<'a> {
+    pub name: Text<'a>,
+    pub name_en: Text<'a>,
+    pub comment: Text<'a>,
+    pub comment_en: Text<'a>,
+}
+
+/// An undecoded section of `len` elements of type `T`.
+pub struct Section<'a, T> {
+    data: &'a [u8],
+    len: usize,
+    header: Header,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Section<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Section<'_, T> {}
+
+impl<T> std::fmt::Debug for Section<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Section")
+            .field("len", &self.len)
+            .field("bytes", &self.data.len())
+            .finish()
+    }
+}
+
+impl<'a, T> Section<'a, T> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bytes of the section, without the leading element count.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn reader(&self) -> Reader<&'a [u8]> {
+        let mut reader = Reader::new(self.data);
+        reader.set_header(&self.header);
+        reader
+    }
+
+    fn decode(
+        &self,
+        f: fn(&mut Reader<&'a [u8]>) -> Result<T, Error>,
+    ) -> impl Iterator<Item = Result<T, Error>> + 'a
+    where
+        T: 'a,
+    {
+        let mut reader = self.reader();
+        (0..self.len).map(move |_| f(&mut reader))
+    }
+
+    /// Returns the names of named elements, skipping everything else.
+    fn raw_names(
+        &self,
+        skip: fn(&mut Reader<&'a [u8]>) -> Result<(), Error>,
+    ) -> impl Iterator<Item = (Text<'a>, Text<'a>)> + 'a {
+        let mut reader = self.reader();
+        let encoding = self.header.encoding;
+        // the section was walked by `parse`, so skipping cannot fail here
+        (0..self.len).map_while(move |_| {
+            let mut names = Reader::new(reader.remaining());
+            let name = names.read_raw_string().ok()?;
+            let name_en = names.read_raw_string().ok()?;
+            skip(&mut reader).ok()?;
+            Some((
+                Text {
+                    bytes: name,
+                    encoding,
+                },
+                Text {
+                    bytes: name_en,
+                    encoding,
+                },
+            ))
+        })
+    }
+}
+
+impl<'a> Section<'a, Vertex> {
+    pub fn iter(&self) -> impl Iterator<Item = Result<Vertex, Error>> + 'a {
+        self.decode(Reader::vertex)
+    }
+}
+
+impl<'a> Section<'a, u32> {
+    /// Returns the `i`-th vertex index of the faces.
+    pub fn get(&self, i: usize) -> Option<u32> {
+        let size = self.header.vertex_index_size as usize;
+        let bytes = self.data.get(i * size..(i + 1) * size)?;
+        Some(decode_vertex_index(bytes))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + 'a {
+        let size = self.header.vertex_index_size as usize;
+        self.data.chunks_exact(size).map(decode_vertex_index)
+    }
+}
+
+fn decode_vertex_index(bytes: &[u8]) -> u32 {
+    match *bytes {
+        [a] => a as u32,
+        [a, b] => u16::from_le_bytes([a, b]) as u32,
+        [a, b, c, d] => u32::from_le_bytes([a, b, c, d]),
+        _ => unreachable!(),
+    }
+}
+
+impl<'a> Section<'a, PathBuf> {
+    pub fn iter(&self) -> impl Iterator<Item = Text<'a>> + 'a {
+        let mut reader = self.reader();
+        let encoding = self.header.encoding;
+        (0..self.len).map_while(move |_| {
+            let bytes = reader.read_raw_string().ok()?;
+            Some(Text { bytes, encoding })
+        })
+    }
+}
+
+macro_rules! named_sections {
+    ($($t:ty => $decode:ident, $skip:ident;)*) => {
+        $(impl<'a> Section<'a, $t> {
+            pub fn iter(&self) -> impl Iterator<Item = Result<$t, Error>> + 'a {
+                self.decode(Reader::$decode)
+            }
+
+            /// Returns `name` and `name_en` of each element without decoding the rest.
+            pub fn names(&self) -> impl Iterator<Item = (Text<'a>, Text<'a>)> + 'a {
+                self.raw_names(Reader::$skip)
+            }
+        })*
+    };
+}
+
+named_sections! {
+    Material => material, skip_material;
+    Bone => bone, skip_bone;
+    Morph => morph, skip_morph;
+    DisplayGroup => display_group, skip_display_group;
+    Rigid => rigid, skip_rigid;
+    Joint => joint, skip_joint;
+}
+
+#[derive(Clone, Debug)]
+pub struct PmxRef<'a> {
+    pub header: Header,
+    pub model_info: ModelInfoRef<'a>,
+    pub vertices: Section<'a, Vertex>,
+    pub faces: Section<'a, u32>,
+    pub textures: Section<'a, PathBuf>,
+    pub materials: Section<'a, Material>,
+    pub bones: Section<'a, Bone>,
+    pub morphs: Section<'a, Morph>,
+    pub display_groups: Section<'a, DisplayGroup>,
+    pub rigids: Section<'a, Rigid>,
+    pub joints: Section<'a, Joint>,
+}
+
+/// Finds the sections of a PMX file held in memory.
+pub fn parse<'a>(data: &'a [u8]) -> Result<PmxRef<'a>, Error> {
+    let mut reader = Reader::new(data);
+    let header = reader.header()?;
+    reader.set_header(&header);
+    let encoding = header.encoding;
+    let text = |reader: &mut Reader<&'a [u8]>| {
+        reader
+            .read_raw_string()
+            .map(|bytes| Text { bytes, encoding })
+    };
+    let model_info = ModelInfoRef {
+        name: text(&mut reader)?,
+        name_en: text(&mut reader)?,
+        comment: text(&mut reader)?,
+        comment_en: text(&mut reader)?,
+    };
+    fn section<'a, T>(
+        reader: &mut Reader<&'a [u8]>,
+        header: Header,
+        skip: impl FnOnce(&mut Reader<&'a [u8]>, u32) -> Result<(), Error>,
+    ) -> Result<Section<'a, T>, Error> {
+        let len = reader.read_u32()?;
+        let start = reader.remaining();
+        skip(reader, len)?;
+        let data = &start[..start.len() - reader.remaining().len()];
+        Ok(Section {
+            data,
+            len: len as usize,
+            header,
+            _marker: PhantomData,
+        })
+    }
+    let r = &mut reader;
+    Ok(PmxRef {
+        header,
+        model_info,
+        vertices: section(r, header, |r, len| {
+            r.skip_elements(len, Reader::skip_vertex)
+        })?,
+        faces: section(r, header, Reader::skip_faces)?,
+        textures: section(r, header, |r, len| {
+            r.skip_elements(len, Reader::skip_texture)
+        })?,
+        materials: section(r, header, |r, len| {
+            r.skip_elements(len, Reader::skip_material)
+        })?,
+        bones: section(r, header, |r, len| r.skip_elements(len, Reader::skip_bone))?,
+        morphs: section(r, header, |r, len| r.skip_elements(len, Reader::skip_morph))?,
+        display_groups: section(r, header, |r, len| {
+            r.skip_elements(len, Reader::skip_display_group)
+        })?,
+        rigids: section(r, header, |r, len| r.skip_elements(len, Reader::skip_rigid))?,
+        joints: section(r, header, |r, len| r.skip_elements(len, Reader::skip_joint))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alicia() {
+        let data = std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap();
+        let pmx = crate::read(data.as_slice()).unwrap();
+        let view = parse(&data).unwrap();
+
+        assert!(view.model_info.name.to_str() == pmx.model_info.name);
+        assert!(view.vertices.len() == pmx.vertices.len());
+        assert!(view.faces.len() == pmx.faces.len());
+        assert!(view.faces.iter().eq(pmx.faces.iter().copied()));
+        assert!(view.faces.get(pmx.faces.len() - 1) == pmx.faces.last().copied());
+        assert!(view.faces.get(pmx.faces.len()).is_none());
+        assert!(view
+            .textures
+            .iter()
+            .map(|t| PathBuf::from(t.to_string()))
+            .eq(pmx.textures.iter().cloned()));
+        assert!(view
+            .bones
+            .names()
+            .map(|(name, _)| name.to_string())
+            .eq(pmx.bones.iter().map(|b| b.name.clone())));
+        assert!(view.morphs.names().count() == pmx.morphs.len());
+        assert!(view.joints.names().last().unwrap().0.to_str() == pmx.joints.last().unwrap().name);
+
+        let decoded = Pmx {
+            header: view.header,
+            model_info: pmx.model_info.clone(),
+            vertices: view.vertices.iter().collect::<Result<_, _>>().unwrap(),
+            faces: view.faces.iter().collect(),
+            textures: pmx.textures.clone(),
+            materials: view.materials.iter().collect::<Result<_, _>>().unwrap(),
+            bones: view.bones.iter().collect::<Result<_, _>>().unwrap(),
+            morphs: view.morphs.iter().collect::<Result<_, _>>().unwrap(),
+            display_groups: view
+                .display_groups
+                .iter()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            rigids: view.rigids.iter().collect::<Result<_, _>>().unwrap(),
+            joints: view.joints.iter().collect::<Result<_, _>>().unwrap(),
+        };
+        assert!(decoded == pmx);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap();
+        assert!(parse(&data[..data.len() - 1]).is_err());
+        assert!(parse(&data[..4]).is_err());
+    }
+}
@@ -0,0 +1,689 @@
+//! Structural comparison of two models.
+//!
+//! Named elements are matched by name when the name is unique in both models,
+//! and otherwise by index. References to other elements are compared by the name
+//! of the referenced element, so inserting a bone does not change every child.
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    /// Largest difference between two floats that are considered equal.
+    pub tolerance: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { tolerance: 1e-5 }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Changed {
+    /// Index in the old model.
+    pub old: usize,
+    /// Index in the new model.
+    pub new: usize,
+    pub name: String,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ElementDiff {
+    /// Indices in the new model.
+    pub added: Vec<usize>,
+    /// Indices in the old model.
+    pub removed: Vec<usize>,
+    pub changed: Vec<Changed>,
+}
+
+impl ElementDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct VertexStats {
+    pub old_count: usize,
+    pub new_count: usize,
+    /// Vertices of the same index whose attributes differ.
+    pub changed: usize,
+    /// Largest distance a vertex of the same index moved.
+    pub max_displacement: f32,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FaceStats {
+    pub old_count: usize,
+    pub new_count: usize,
+    /// Triangles of the same index that use different vertices.
+    pub changed: usize,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Diff {
+    pub header: Vec<FieldChange>,
+    pub model_info: Vec<FieldChange>,
+    pub vertices: VertexStats,
+    pub faces: FaceStats,
+    pub textures: ElementDiff,
+    pub materials: ElementDiff,
+    pub bones: ElementDiff,
+    pub morphs: ElementDiff,
+    pub display_groups: ElementDiff,
+    pub rigids: ElementDiff,
+    pub joints: ElementDiff,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty()
+            && self.model_info.is_empty()
+            && self.vertices.changed == 0
+            && self.vertices.old_count == self.vertices.new_count
+            && self.faces.changed == 0
+            && self.faces.old_count == self.faces.new_count
+            && self.textures.is_empty()
+            && self.materials.is_empty()
+            && self.bones.is_empty()
+            && self.morphs.is_empty()
+            && self.display_groups.is_empty()
+            && self.rigids.is_empty()
+            && self.joints.is_empty()
+    }
+}
+
+impl Pmx {
+    /// Compares this model, taken as the old one, with `new`.
+    pub fn diff(&self, new: &Pmx, options: &DiffOptions) -> Diff {
+        let cx = Context {
+            old: self,
+            new,
+            tolerance: options.tolerance,
+        };
+        let mut header = Fields::new(options.tolerance);
+        header.float("version", &[self.header.version], &[new.header.version]);
+        header.eq("encoding", &self.header.encoding, &new.header.encoding);
+        header.eq(
+            "extended_uv",
+            &self.header.extended_uv,
+            &new.header.extended_uv,
+        );
+        header.eq(
+            "index_sizes",
+            &self.header.index_sizes(),
+            &new.header.index_sizes(),
+        );
+        let mut model_info = Fields::new(options.tolerance);
+        let (a, b) = (&self.model_info, &new.model_info);
+        model_info.eq("name", &a.name, &b.name);
+        model_info.eq("name_en", &a.name_en, &b.name_en);
+        model_info.eq("comment", &a.comment, &b.comment);
+        model_info.eq("comment_en", &a.comment_en, &b.comment_en);
+
+        let textures = self
+            .textures
+            .iter()
+            .map(|t| t.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let new_textures = new
+            .textures
+            .iter()
+            .map(|t| t.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        Diff {
+            header: header.changes,
+            model_info: model_info.changes,
+            vertices: cx.vertices(),
+            faces: cx.faces(),
+            textures: cx.elements(&textures, &new_textures, |_, _, _, _| {}),
+            materials: cx.elements(&self.materials, &new.materials, Context::material),
+            bones: cx.elements(&self.bones, &new.bones, Context::bone),
+            morphs: cx.elements(&self.morphs, &new.morphs, Context::morph),
+            display_groups: cx.elements(
+                &self.display_groups,
+                &new.display_groups,
+                Context::display_group,
+            ),
+            rigids: cx.elements(&self.rigids, &new.rigids, Context::rigid),
+            joints: cx.elements(&self.joints, &new.joints, Context::joint),
+        }
+    }
+}
+
+trait Named {
+    fn name(&self) -> &str;
+}
+
+impl Named for String {
+    fn name(&self) -> &str {
+        self
+    }
+}
+
+macro_rules! impl_named {
+    ($($t:ty),*) => {
+        $(impl Named for $t {
+            fn name(&self) -> &str {
+                &self.name
+            }
+        })*
+    };
+}
+
+impl_named!(Material, Bone, Morph, DisplayGroup, Rigid, Joint);
+
+struct Fields {
+    tolerance: f32,
+    changes: Vec<FieldChange>,
+}
+
+impl Fields {
+    fn new(tolerance: f32) -> Self {
+        Self {
+            tolerance,
+            changes: vec![],
+        }
+    }
+
+    fn push(&mut self, field: &str, old: impl Debug, new: impl Debug) {
+        self.changes.push(FieldChange {
+            field: field.into(),
+            old: format!("{:?}", old),
+            new: format!("{:?}", new),
+        });
+    }
+
+    fn eq<T: PartialEq + Debug>(&mut self, field: &str, old: &T, new: &T) {
+        if old != new {
+            self.push(field, old, new);
+        }
+    }
+
+    fn float(&mut self, field: &str, old: &[f32], new: &[f32]) {
+        if !floats_eq(old, new, self.tolerance) {
+            self.push(field, old, new);
+        }
+    }
+}
+
+fn floats_eq(a: &[f32], b: &[f32], tolerance: f32) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+}
+
+struct Context<'a> {
+    old: &'a Pmx,
+    new: &'a Pmx,
+    tolerance: f32,
+}
+
+/// Returns the name of the referenced element, or `None` for no reference.
+fn name_of<T: Named>(items: &[T], index: Option<usize>) -> Option<String> {
+    index.map(|i| {
+        items
+            .get(i)
+            .map_or_else(|| format!("#{}", i), |t| t.name().to_string())
+    })
+}
+
+impl Context<'_> {
+    fn elements<T: Named>(
+        &self,
+        old: &[T],
+        new: &[T],
+        compare: impl Fn(&Self, &T, &T, &mut Fields),
+    ) -> ElementDiff {
+        let mut diff = ElementDiff::default();
+        let pairs = match_elements(old, new);
+        let mut matched_new = vec![false; new.len()];
+        let mut matched_old = vec![false; old.len()];
+        for &(o, n) in pairs.iter() {
+            matched_old[o] = true;
+            matched_new[n] = true;
+            let mut fields = Fields::new(self.tolerance);
+            fields.eq("name", &old[o].name(), &new[n].name());
+            compare(self, &old[o], &new[n], &mut fields);
+            if !fields.changes.is_empty() {
+                diff.changed.push(Changed {
+                    old: o,
+                    new: n,
+                    name: new[n].name().into(),
+                    fields: fields.changes,
+                });
+            }
+        }
+        diff.removed = (0..old.len()).filter(|&i| !matched_old[i]).collect();
+        diff.added = (0..new.len()).filter(|&i| !matched_new[i]).collect();
+        diff
+    }
+
+    fn vertices(&self) -> VertexStats {
+        let mut stats = VertexStats {
+            old_count: self.old.vertices.len(),
+            new_count: self.new.vertices.len(),
+            ..Default::default()
+        };
+        for (a, b) in self.old.vertices.iter().zip(&self.new.vertices) {
+            let d = (0..3)
+                .map(|k| (a.position[k] - b.position[k]).powi(2))
+                .sum::<f32>()
+                .sqrt();
+            stats.max_displacement = stats.max_displacement.max(d);
+            let t = self.tolerance;
+            let same = floats_eq(&a.position, &b.position, t)
+                && floats_eq(&a.normal, &b.normal, t)
+                && floats_eq(&a.uv, &b.uv, t)
+                && floats_eq(
+                    a.extended_uv.as_flattened(),
+                    b.extended_uv.as_flattened(),
+                    t,
+                )
+                && (a.edge_ratio - b.edge_ratio).abs() <= t
+                && self.weights_eq(&a.weight, &b.weight);
+            if !same {
+                stats.changed += 1;
+            }
+        }
+        stats
+    }
+
+    fn weights_eq(&self, a: &Weight, b: &Weight) -> bool {
+        let t = self.tolerance;
+        let bones = |a: &[Option<usize>], b: &[Option<usize>]| {
+            a.iter()
+                .zip(b)
+                .all(|(x, y)| name_of(&self.old.bones, *x) == name_of(&self.new.bones, *y))
+        };
+        match (a, b) {
+            (Weight::Bdef1(a), Weight::Bdef1(b)) => bones(&[a.bone], &[b.bone]),
+            (Weight::Bdef2(a), Weight::Bdef2(b)) => {
+                bones(&a.bones, &b.bones) && (a.weight - b.weight).abs() <= t
+            }
+            (Weight::Bdef4(a), Weight::Bdef4(b)) => {
+                bones(&a.bones, &b.bones) && floats_eq(&a.weights, &b.weights, t)
+            }
+            (Weight::Sdef(a), Weight::Sdef(b)) => {
+                bones(&a.bones, &b.bones)
+                    && (a.weight - b.weight).abs() <= t
+                    && floats_eq(&[a.c, a.r0, a.r1].concat(), &[b.c, b.r0, b.r1].concat(), t)
+            }
+            _ => false,
+        }
+    }
+
+    fn faces(&self) -> FaceStats {
+        FaceStats {
+            old_count: self.old.faces.len() / 3,
+            new_count: self.new.faces.len() / 3,
+            changed: self
+                .old
+                .faces
+                .chunks_exact(3)
+                .zip(self.new.faces.chunks_exact(3))
+                .filter(|(a, b)| a != b)
+                .count(),
+        }
+    }
+
+    fn texture(&self, old: Option<usize>, new: Option<usize>) -> (Option<String>, Option<String>) {
+        let path = |pmx: &Pmx, t: Option<usize>| {
+            t.map(|t| {
+                pmx.textures
+                    .get(t)
+                    .map_or_else(|| format!("#{}", t), |p| p.to_string_lossy().into_owned())
+            })
+        };
+        (path(self.old, old), path(self.new, new))
+    }
+
+    fn bone_names(
+        &self,
+        old: Option<usize>,
+        new: Option<usize>,
+    ) -> (Option<String>, Option<String>) {
+        (name_of(&self.old.bones, old), name_of(&self.new.bones, new))
+    }
+
+    fn material(&self, a: &Material, b: &Material, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        f.float("diffuse", &a.diffuse, &b.diffuse);
+        f.float("specular", &a.specular, &b.specular);
+        f.float("specular_power", &[a.specular_power], &[b.specular_power]);
+        f.float("ambient", &a.ambient, &b.ambient);
+        f.eq("both", &a.both, &b.both);
+        f.eq("ground_shadow", &a.ground_shadow, &b.ground_shadow);
+        f.eq("self_shadow_map", &a.self_shadow_map, &b.self_shadow_map);
+        f.eq("self_shadow", &a.self_shadow, &b.self_shadow);
+        f.eq("edge", &a.edge, &b.edge);
+        f.float("edge_color", &a.edge_color, &b.edge_color);
+        f.float("edge_size", &[a.edge_size], &[b.edge_size]);
+        let (x, y) = self.texture(a.texture, b.texture);
+        f.eq("texture", &x, &y);
+        let (x, y) = self.texture(a.sphere, b.sphere);
+        f.eq("sphere", &x, &y);
+        f.eq("sphere_mode", &a.sphere_mode, &b.sphere_mode);
+        match (&a.toon, &b.toon) {
+            (Toon::Texture(x), Toon::Texture(y)) => {
+                let (x, y) = self.texture(*x, *y);
+                f.eq("toon", &x, &y);
+            }
+            (x, y) => f.eq("toon", x, y),
+        }
+        f.eq("memo", &a.memo, &b.memo);
+        f.eq("index_count", &a.index_count, &b.index_count);
+    }
+
+    fn bone(&self, a: &Bone, b: &Bone, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        f.float("position", &a.position, &b.position);
+        let (x, y) = self.bone_names(a.parent, b.parent);
+        f.eq("parent", &x, &y);
+        f.eq("deform_hierarchy", &a.deform_hierarchy, &b.deform_hierarchy);
+        match (&a.connected_to, &b.connected_to) {
+            (ConnectedTo::Offset(x), ConnectedTo::Offset(y)) => f.float("connected_to", x, y),
+            (ConnectedTo::Bone(x), ConnectedTo::Bone(y)) => {
+                let (x, y) = self.bone_names(*x, *y);
+                f.eq("connected_to", &x, &y);
+            }
+            (x, y) => f.push("connected_to", x, y),
+        }
+        f.eq("rotatable", &a.rotatable, &b.rotatable);
+        f.eq("translatable", &a.translatable, &b.translatable);
+        f.eq("visibility", &a.visibility, &b.visibility);
+        f.eq("operable", &a.operable, &b.operable);
+        match (&a.ik, &b.ik) {
+            (Some(x), Some(y)) => {
+                let (t, u) = self.bone_names(x.bone, y.bone);
+                f.eq("ik.bone", &t, &u);
+                f.eq("ik.loop_count", &x.loop_count, &y.loop_count);
+                f.float("ik.angle", &[x.angle], &[y.angle]);
+                let links = |pmx: &Pmx, ik: &Ik| {
+                    ik.links
+                        .iter()
+                        .map(|l| name_of(&pmx.bones, l.bone))
+                        .collect::<Vec<_>>()
+                };
+                f.eq("ik.links", &links(self.old, x), &links(self.new, y));
+                let limits = |ik: &Ik| {
+                    ik.links
+                        .iter()
+                        .flat_map(|l| match &l.limits {
+                            Some(l) => [l.lower, l.upper].concat(),
+                            None => vec![f32::NAN; 6],
+                        })
+                        .map(|v| if v.is_nan() { f32::MAX } else { v })
+                        .collect::<Vec<_>>()
+                };
+                f.float("ik.limits", &limits(x), &limits(y));
+            }
+            (None, None) => {}
+            (x, y) => f.push("ik", x.is_some(), y.is_some()),
+        }
+        match (&a.addition, &b.addition) {
+            (Some(x), Some(y)) => {
+                f.eq("addition.rotation", &x.rotation, &y.rotation);
+                f.eq("addition.translation", &x.translation, &y.translation);
+                f.eq("addition.local", &x.local, &y.local);
+                let (t, u) = self.bone_names(x.bone, y.bone);
+                f.eq("addition.bone", &t, &u);
+                f.float("addition.ratio", &[x.ratio], &[y.ratio]);
+            }
+            (None, None) => {}
+            (x, y) => f.push("addition", x.is_some(), y.is_some()),
+        }
+        f.eq("after_physics", &a.after_physics, &b.after_physics);
+        let pole = |p: &Option<[f32; 3]>| p.map_or(vec![], |p| p.to_vec());
+        f.float("fixed_pole", &pole(&a.fixed_pole), &pole(&b.fixed_pole));
+        let local = |p: &Option<LocalPole>| p.as_ref().map_or(vec![], |p| [p.x, p.z].concat());
+        f.float("local_pole", &local(&a.local_pole), &local(&b.local_pole));
+        f.eq("external_parent", &a.external_parent, &b.external_parent);
+    }
+
+    fn morph(&self, a: &Morph, b: &Morph, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        f.eq("panel", &a.panel, &b.panel);
+        let (old, new) = (
+            self.offsets(self.old, &a.kind),
+            self.offsets(self.new, &b.kind),
+        );
+        if std::mem::discriminant(&a.kind) != std::mem::discriminant(&b.kind) {
+            f.push("kind", kind_name(&a.kind), kind_name(&b.kind));
+            return;
+        }
+        let changed = new
+            .iter()
+            .filter(|(k, v)| old.get(*k).is_none_or(|o| !floats_eq(o, v, self.tolerance)))
+            .count();
+        let removed = old.keys().filter(|k| !new.contains_key(*k)).count();
+        if changed > 0 || removed > 0 {
+            f.changes.push(FieldChange {
+                field: "offsets".into(),
+                old: format!("{} offsets", old.len()),
+                new: format!(
+                    "{} offsets, {} added or changed, {} removed",
+                    new.len(),
+                    changed,
+                    removed
+                ),
+            });
+        }
+    }
+
+    /// Returns the offsets of a morph keyed by what they apply to.
+    fn offsets(&self, pmx: &Pmx, kind: &morph::Kind) -> HashMap<String, Vec<f32>> {
+        match kind {
+            morph::Kind::Vertex(v) => v
+                .iter()
+                .map(|o| (format!("{:?}", o.vertex), o.offset.to_vec()))
+                .collect(),
+            morph::Kind::Uv(v) | morph::Kind::ExtendedUv(_, v) => v
+                .iter()
+                .map(|o| (format!("{:?}", o.vertex), o.offset.to_vec()))
+                .collect(),
+            morph::Kind::Bone(v) => v
+                .iter()
+                .map(|o| {
+                    let key = format!("{:?}", name_of(&pmx.bones, o.bone));
+                    (key, [o.offset.as_slice(), &o.rotation].concat())
+                })
+                .collect(),
+            morph::Kind::Maerial(v) => v
+                .iter()
+                .map(|o| {
+                    let key = format!("{:?} {:?}", name_of(&pmx.materials, o.material), o.op);
+                    let values = [
+                        o.diffuse.as_slice(),
+                        &o.specular,
+                        &[o.specular_power],
+                        &o.ambient,
+                        &o.edge_color,
+                        &[o.edge_size],
+                        &o.texture,
+                        &o.sphere,
+                        &o.toon,
+                    ]
+                    .concat();
+                    (key, values)
+                })
+                .collect(),
+            morph::Kind::Group(v) => v
+                .iter()
+                .map(|o| {
+                    (
+                        format!("{:?}", name_of(&pmx.morphs, o.morph)),
+                        vec![o.ratio],
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn display_group(&self, a: &DisplayGroup, b: &DisplayGroup, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        f.eq("special", &a.special, &b.special);
+        let elements = |pmx: &Pmx, g: &DisplayGroup| {
+            g.elements
+                .iter()
+                .map(|e| match e {
+                    DisplayElement::Bone(b) => format!("bone {:?}", name_of(&pmx.bones, *b)),
+                    DisplayElement::Morph(m) => format!("morph {:?}", name_of(&pmx.morphs, *m)),
+                })
+                .collect::<Vec<_>>()
+        };
+        f.eq("elements", &elements(self.old, a), &elements(self.new, b));
+    }
+
+    fn rigid(&self, a: &Rigid, b: &Rigid, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        let (x, y) = self.bone_names(a.bone, b.bone);
+        f.eq("bone", &x, &y);
+        f.eq("group", &a.group, &b.group);
+        f.eq(
+            "non_collision_groups",
+            &a.non_collision_groups,
+            &b.non_collision_groups,
+        );
+        f.eq("shape", &a.shape, &b.shape);
+        f.float("size", &a.size, &b.size);
+        f.float("position", &a.position, &b.position);
+        f.float("rotation", &a.rotation, &b.rotation);
+        f.float("mass", &[a.mass], &[b.mass]);
+        f.float(
+            "dump_translation",
+            &[a.dump_translation],
+            &[b.dump_translation],
+        );
+        f.float("dump_rotation", &[a.dump_rotation], &[b.dump_rotation]);
+        f.float("repulsive", &[a.repulsive], &[b.repulsive]);
+        f.float("friction", &[a.friction], &[b.friction]);
+        f.eq("method", &a.method, &b.method);
+    }
+
+    fn joint(&self, a: &Joint, b: &Joint, f: &mut Fields) {
+        f.eq("name_en", &a.name_en, &b.name_en);
+        let rigids = |pmx: &Pmx, j: &Joint| j.rigids.map(|r| name_of(&pmx.rigids, r));
+        f.eq("rigids", &rigids(self.old, a), &rigids(self.new, b));
+        f.float("position", &a.position, &b.position);
+        f.float("rotation", &a.rotation, &b.rotation);
+        let limit = |l: &AngleLimit| [l.lower, l.upper].concat();
+        f.float(
+            "limit_translation",
+            &limit(&a.limit_translation),
+            &limit(&b.limit_translation),
+        );
+        f.float(
+            "limit_rotation",
+            &limit(&a.limit_rotation),
+            &limit(&b.limit_rotation),
+        );
+        f.float(
+            "spring_translation",
+            &a.spring_translation,
+            &b.spring_translation,
+        );
+        f.float("spring_rotation", &a.spring_rotation, &b.spring_rotation);
+    }
+}
+
+fn kind_name(kind: &morph::Kind) -> &'static str {
+    match kind {
+        morph::Kind::Group(_) => "group",
+        morph::Kind::Vertex(_) => "vertex",
+        morph::Kind::Bone(_) => "bone",
+        morph::Kind::Uv(_) => "uv",
+        morph::Kind::ExtendedUv(..) => "extended uv",
+        morph::Kind::Maerial(_) => "material",
+    }
+}
+
+/// Pairs elements by unique names first, then by index among the rest.
+fn match_elements<T: Named>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let unique = |items: &[T]| {
+        let mut seen = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            seen.entry(item.name().to_string())
+                .and_modify(|e: &mut Option<usize>| *e = None)
+                .or_insert(Some(i));
+        }
+        seen.into_iter()
+            .filter_map(|(name, i)| Some((name, i?)))
+            .filter(|(name, _)| !name.is_empty())
+            .collect::<HashMap<_, _>>()
+    };
+    let new_names = unique(new);
+    let mut pairs = vec![];
+    let mut used_old = HashSet::new();
+    let mut used_new = HashSet::new();
+    for (name, o) in unique(old) {
+        if let Some(&n) = new_names.get(&name) {
+            pairs.push((o, n));
+            used_old.insert(o);
+            used_new.insert(n);
+        }
+    }
+    for i in 0..old.len().min(new.len()) {
+        if !used_old.contains(&i) && !used_new.contains(&i) {
+            pairs.push((i, i));
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn identical() {
+        let pmx = read_pmx();
+        assert!(pmx.diff(&pmx.clone(), &DiffOptions::default()).is_empty());
+        assert!(pmx == pmx.clone());
+    }
+
+    #[test]
+    fn changes() {
+        let old = read_pmx();
+        let mut new = old.clone();
+        new.bones[5].position[1] += 1.0;
+        new.bones[6].position[1] += 1e-7;
+        new.materials[0].diffuse[0] = 0.25;
+        new.vertices[10].position[0] += 2.0;
+        new.faces.swap(0, 1);
+        new.insert_bone(0, new.bones[1].clone()).unwrap();
+        new.bones[0].name = "追加".into();
+        new.remove_morphs(&[3]).unwrap();
+        let diff = old.diff(&new, &DiffOptions::default());
+
+        assert!(diff.bones.added == [0]);
+        assert!(diff.bones.removed.is_empty());
+        assert!(diff.bones.changed.len() == 1);
+        let bone = &diff.bones.changed[0];
+        assert!(bone.old == 5 && bone.new == 6);
+        assert!(bone.fields.len() == 1 && bone.fields[0].field == "position");
+        assert!(diff.materials.changed[0].fields[0].field == "diffuse");
+        assert!(diff.morphs.removed == [3]);
+        assert!(diff.vertices.changed == 1);
+        assert!(diff.vertices.max_displacement == 2.0);
+        assert!(diff.faces.changed == 1);
+        assert!(diff.rigids.is_empty());
+        assert!(diff.joints.is_empty());
+    }
+}
@@ -0,0 +1,597 @@
+//! Structural edits that keep every cross-reference consistent.
+//!
+//! Every operation rewrites the indices stored in vertex weights, faces,
+//! materials, bones, morphs, display groups, rigids and joints. References to
+//! removed elements are cleared, or deleted where the containing list has no
+//! use for an empty entry, and each of them is listed in the returned [`Report`].
+
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Kind {
+    Vertex,
+    Texture,
+    Material,
+    Bone,
+    Morph,
+    Rigid,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{:?} index out of range: {}", .0, .1)]
+    OutOfRange(Kind, usize),
+    #[error("invalid {:?} order", .0)]
+    InvalidOrder(Kind),
+}
+
+/// A reference that was cleared or deleted by an edit.
+///
+/// Owners (`vertex`, `material`, `bone`, ...) are indices after the edit.
+/// Deleted entries (`triangle`, `link`, `offset`, `element`) are positions before the edit.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Dropped {
+    Weight { vertex: usize },
+    Face { triangle: usize },
+    MaterialTexture { material: usize },
+    MaterialSphere { material: usize },
+    MaterialToon { material: usize },
+    BoneParent { bone: usize },
+    BoneConnection { bone: usize },
+    IkTarget { bone: usize },
+    IkLink { bone: usize, link: usize },
+    Addition { bone: usize },
+    MorphOffset { morph: usize, offset: usize },
+    DisplayElement { group: usize, element: usize },
+    RigidBone { rigid: usize },
+    JointRigid { joint: usize },
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Report {
+    pub dropped: Vec<Dropped>,
+}
+
+impl Pmx {
+    pub(crate) fn len_of(&self, kind: Kind) -> usize {
+        match kind {
+            Kind::Vertex => self.vertices.len(),
+            Kind::Texture => self.textures.len(),
+            Kind::Material => self.materials.len(),
+            Kind::Bone => self.bones.len(),
+            Kind::Morph => self.morphs.len(),
+            Kind::Rigid => self.rigids.len(),
+        }
+    }
+
+    pub fn remove_vertices(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Vertex, indices)
+    }
+
+    pub fn remove_textures(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Texture, indices)
+    }
+
+    /// Removes materials together with their faces.
+    pub fn remove_materials(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Material, indices)
+    }
+
+    pub fn remove_bones(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Bone, indices)
+    }
+
+    pub fn remove_morphs(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Morph, indices)
+    }
+
+    pub fn remove_rigids(&mut self, indices: &[usize]) -> Result<Report, Error> {
+        self.remove(Kind::Rigid, indices)
+    }
+
+    /// Reorders vertices so that `order[new] == old`.
+    pub fn reorder_vertices(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Vertex, order)
+    }
+
+    /// Reorders textures so that `order[new] == old`.
+    pub fn reorder_textures(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Texture, order)
+    }
+
+    /// Reorders materials so that `order[new] == old`. Faces move with their materials.
+    pub fn reorder_materials(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Material, order)
+    }
+
+    /// Reorders bones so that `order[new] == old`.
+    pub fn reorder_bones(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Bone, order)
+    }
+
+    /// Reorders morphs so that `order[new] == old`.
+    pub fn reorder_morphs(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Morph, order)
+    }
+
+    /// Reorders rigids so that `order[new] == old`.
+    pub fn reorder_rigids(&mut self, order: &[usize]) -> Result<Report, Error> {
+        self.reorder(Kind::Rigid, order)
+    }
+
+    /// Inserts a vertex at `index`. Indices inside `vertex` are taken as they are.
+    pub fn insert_vertex(&mut self, index: usize, vertex: Vertex) -> Result<Report, Error> {
+        let report = self.shift(Kind::Vertex, index)?;
+        self.vertices.insert(index, vertex);
+        Ok(report)
+    }
+
+    pub fn insert_texture(&mut self, index: usize, path: PathBuf) -> Result<Report, Error> {
+        let report = self.shift(Kind::Texture, index)?;
+        self.textures.insert(index, path);
+        Ok(report)
+    }
+
+    /// Inserts a material drawing `faces`. `Material::index_count` is set to `faces.len()`.
+    pub fn insert_material(
+        &mut self,
+        index: usize,
+        mut material: Material,
+        faces: &[u32],
+    ) -> Result<Report, Error> {
+        let report = self.shift(Kind::Material, index)?;
+        let offset = self.materials[..index]
+            .iter()
+            .map(|m| m.index_count as usize)
+            .sum::<usize>();
+        self.faces.splice(offset..offset, faces.iter().copied());
+        material.index_count = faces.len() as u32;
+        self.materials.insert(index, material);
+        Ok(report)
+    }
+
+    /// Inserts a bone at `index`. Indices inside `bone` are taken as they are.
+    pub fn insert_bone(&mut self, index: usize, bone: Bone) -> Result<Report, Error> {
+        let report = self.shift(Kind::Bone, index)?;
+        self.bones.insert(index, bone);
+        Ok(report)
+    }
+
+    /// Inserts a morph at `index`. Indices inside `morph` are taken as they are.
+    pub fn insert_morph(&mut self, index: usize, morph: Morph) -> Result<Report, Error> {
+        let report = self.shift(Kind::Morph, index)?;
+        self.morphs.insert(index, morph);
+        Ok(report)
+    }
+
+    /// Inserts a rigid at `index`. Indices inside `rigid` are taken as they are.
+    pub fn insert_rigid(&mut self, index: usize, rigid: Rigid) -> Result<Report, Error> {
+        let report = self.shift(Kind::Rigid, index)?;
+        self.rigids.insert(index, rigid);
+        Ok(report)
+    }
+
+    fn remove(&mut self, kind: Kind, indices: &[usize]) -> Result<Report, Error> {
+        let len = self.len_of(kind);
+        let mut map = vec![Some(0); len];
+        for &i in indices {
+            *map.get_mut(i).ok_or(Error::OutOfRange(kind, i))? = None;
+        }
+        let mut new_len = 0;
+        for m in map.iter_mut().flatten() {
+            *m = new_len;
+            new_len += 1;
+        }
+        Ok(self.remap(kind, &map, new_len))
+    }
+
+    fn reorder(&mut self, kind: Kind, order: &[usize]) -> Result<Report, Error> {
+        let len = self.len_of(kind);
+        if order.len() != len {
+            return Err(Error::InvalidOrder(kind));
+        }
+        let mut map = vec![None; len];
+        for (new, &old) in order.iter().enumerate() {
+            match map.get_mut(old) {
+                Some(m @ None) => *m = Some(new),
+                _ => return Err(Error::InvalidOrder(kind)),
+            }
+        }
+        Ok(self.remap(kind, &map, len))
+    }
+
+    fn shift(&mut self, kind: Kind, index: usize) -> Result<Report, Error> {
+        let len = self.len_of(kind);
+        if index > len {
+            return Err(Error::OutOfRange(kind, index));
+        }
+        let map = (0..len)
+            .map(|i| Some(if i < index { i } else { i + 1 }))
+            .collect::<Vec<_>>();
+        let mut report = Report::default();
+        self.rewrite_references(kind, &map, &mut report);
+        Ok(report)
+    }
+
+    /// Moves every element of `kind` from `i` to `map[i]`, dropping those mapped to `None`.
+    ///
+    /// Several elements may map to the same index, in which case the first one is kept.
+    pub(crate) fn remap(&mut self, kind: Kind, map: &[Option<usize>], new_len: usize) -> Report {
+        let mut report = Report::default();
+        self.rewrite_references(kind, map, &mut report);
+        match kind {
+            Kind::Vertex => {
+                self.vertices = permute(std::mem::take(&mut self.vertices), map, new_len);
+            }
+            Kind::Texture => {
+                self.textures = permute(std::mem::take(&mut self.textures), map, new_len);
+            }
+            Kind::Material => {
+                let mut faces = self.faces.as_slice();
+                let mut ranges = vec![];
+                let mut triangle = 0;
+                for (i, material) in self.materials.iter().enumerate() {
+                    let count = (material.index_count as usize).min(faces.len());
+                    let (range, rest) = faces.split_at(count);
+                    if map[i].is_none() {
+                        report.dropped.extend(
+                            (triangle..triangle + count / 3).map(|t| Dropped::Face { triangle: t }),
+                        );
+                    }
+                    ranges.push(range.to_vec());
+                    triangle += count / 3;
+                    faces = rest;
+                }
+                let rest = faces.to_vec();
+                let mut faces = permute(ranges, map, new_len).concat();
+                faces.extend(rest);
+                self.faces = faces;
+                self.materials = permute(std::mem::take(&mut self.materials), map, new_len);
+            }
+            Kind::Bone => {
+                self.bones = permute(std::mem::take(&mut self.bones), map, new_len);
+            }
+            Kind::Morph => {
+                self.morphs = permute(std::mem::take(&mut self.morphs), map, new_len);
+            }
+            Kind::Rigid => {
+                self.rigids = permute(std::mem::take(&mut self.rigids), map, new_len);
+            }
+        }
+        report
+    }
+
+    /// Rewrites every reference to an element of `kind` through `map` without moving anything.
+    pub(crate) fn rewrite_references(
+        &mut self,
+        kind: Kind,
+        map: &[Option<usize>],
+        report: &mut Report,
+    ) {
+        let remap = |index: &mut Option<usize>| -> bool {
+            match *index {
+                Some(i) => {
+                    *index = map.get(i).copied().flatten();
+                    index.is_none()
+                }
+                None => false,
+            }
+        };
+        let owner = |owner: Kind, i: usize| {
+            if owner == kind {
+                map.get(i).copied().flatten()
+            } else {
+                Some(i)
+            }
+        };
+        let mut drop = |dropped: Option<Dropped>| report.dropped.extend(dropped);
+        match kind {
+            Kind::Vertex => {
+                let mut triangle = 0;
+                let mut faces = std::mem::take(&mut self.faces).into_iter();
+                let mut kept = vec![];
+                for material in self.materials.iter_mut() {
+                    let mut count = 0;
+                    for _ in 0..material.index_count / 3 {
+                        let face = [faces.next(), faces.next(), faces.next()];
+                        let face = face.map(|i| {
+                            i.and_then(|i| map.get(i as usize).copied().flatten())
+                                .map(|i| i as u32)
+                        });
+                        match face {
+                            [Some(a), Some(b), Some(c)] => {
+                                kept.extend([a, b, c]);
+                                count += 3;
+                            }
+                            _ => drop(Some(Dropped::Face { triangle })),
+                        }
+                        triangle += 1;
+                    }
+                    material.index_count = count;
+                }
+                kept.extend(
+                    faces.filter_map(|i| map.get(i as usize).copied().flatten().map(|i| i as u32)),
+                );
+                self.faces = kept;
+                for (m, morph) in self.morphs.iter_mut().enumerate() {
+                    match &mut morph.kind {
+                        morph::Kind::Vertex(offsets) => {
+                            retain_offsets(offsets, m, &mut drop, |o| !remap(&mut o.vertex))
+                        }
+                        morph::Kind::Uv(offsets) | morph::Kind::ExtendedUv(_, offsets) => {
+                            retain_offsets(offsets, m, &mut drop, |o| !remap(&mut o.vertex))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Kind::Texture => {
+                for (i, material) in self.materials.iter_mut().enumerate() {
+                    if remap(&mut material.texture) {
+                        drop(Some(Dropped::MaterialTexture { material: i }));
+                    }
+                    if remap(&mut material.sphere) {
+                        drop(Some(Dropped::MaterialSphere { material: i }));
+                    }
+                    if let Toon::Texture(toon) = &mut material.toon {
+                        if remap(toon) {
+                            drop(Some(Dropped::MaterialToon { material: i }));
+                        }
+                    }
+                }
+            }
+            Kind::Material => {
+                for (m, morph) in self.morphs.iter_mut().enumerate() {
+                    if let morph::Kind::Maerial(offsets) = &mut morph.kind {
+                        retain_offsets(offsets, m, &mut drop, |o| !remap(&mut o.material));
+                    }
+                }
+            }
+            Kind::Bone => {
+                for (i, vertex) in self.vertices.iter_mut().enumerate() {
+                    let bones: &mut [Option<usize>] = match &mut vertex.weight {
+                        Weight::Bdef1(w) => std::slice::from_mut(&mut w.bone),
+                        Weight::Bdef2(w) => &mut w.bones,
+                        Weight::Bdef4(w) => &mut w.bones,
+                        Weight::Sdef(w) => &mut w.bones,
+                    };
+                    let mut dropped = false;
+                    for bone in bones.iter_mut() {
+                        dropped |= remap(bone);
+                    }
+                    if dropped {
+                        drop(Some(Dropped::Weight { vertex: i }));
+                    }
+                }
+                for (i, bone) in self.bones.iter_mut().enumerate() {
+                    let owner = owner(Kind::Bone, i);
+                    if remap(&mut bone.parent) {
+                        drop(owner.map(|bone| Dropped::BoneParent { bone }));
+                    }
+                    if let ConnectedTo::Bone(target) = &mut bone.connected_to {
+                        if remap(target) {
+                            drop(owner.map(|bone| Dropped::BoneConnection { bone }));
+                        }
+                    }
+                    if let Some(ik) = &mut bone.ik {
+                        if remap(&mut ik.bone) {
+                            drop(owner.map(|bone| Dropped::IkTarget { bone }));
+                        }
+                        let mut link = 0;
+                        ik.links.retain_mut(|l| {
+                            let dropped = remap(&mut l.bone);
+                            if dropped {
+                                drop(owner.map(|bone| Dropped::IkLink { bone, link }));
+                            }
+                            link += 1;
+                            !dropped
+                        });
+                    }
+                    if let Some(addition) = &mut bone.addition {
+                        if remap(&mut addition.bone) {
+                            drop(owner.map(|bone| Dropped::Addition { bone }));
+                        }
+                    }
+                }
+                for (m, morph) in self.morphs.iter_mut().enumerate() {
+                    if let morph::Kind::Bone(offsets) = &mut morph.kind {
+                        retain_offsets(offsets, m, &mut drop, |o| !remap(&mut o.bone));
+                    }
+                }
+                for (i, rigid) in self.rigids.iter_mut().enumerate() {
+                    if remap(&mut rigid.bone) {
+                        drop(Some(Dropped::RigidBone { rigid: i }));
+                    }
+                }
+            }
+            Kind::Morph => {
+                for (m, morph) in self.morphs.iter_mut().enumerate() {
+                    let Some(owner) = owner(Kind::Morph, m) else {
+                        continue;
+                    };
+                    if let morph::Kind::Group(offsets) = &mut morph.kind {
+                        retain_offsets(offsets, owner, &mut drop, |o| !remap(&mut o.morph));
+                    }
+                }
+            }
+            Kind::Rigid => {
+                for (i, joint) in self.joints.iter_mut().enumerate() {
+                    let mut dropped = false;
+                    for rigid in joint.rigids.iter_mut() {
+                        dropped |= remap(rigid);
+                    }
+                    if dropped {
+                        drop(Some(Dropped::JointRigid { joint: i }));
+                    }
+                }
+            }
+        }
+        if matches!(kind, Kind::Bone | Kind::Morph) {
+            for (g, group) in self.display_groups.iter_mut().enumerate() {
+                let mut element = 0;
+                group.elements.retain_mut(|e| {
+                    let dropped = match e {
+                        DisplayElement::Bone(b) if kind == Kind::Bone => remap(b),
+                        DisplayElement::Morph(m) if kind == Kind::Morph => remap(m),
+                        _ => false,
+                    };
+                    if dropped {
+                        drop(Some(Dropped::DisplayElement { group: g, element }));
+                    }
+                    element += 1;
+                    !dropped
+                });
+            }
+        }
+    }
+}
+
+fn retain_offsets<T>(
+    offsets: &mut Vec<T>,
+    morph: usize,
+    drop: &mut impl FnMut(Option<Dropped>),
+    mut keep: impl FnMut(&mut T) -> bool,
+) {
+    let mut offset = 0;
+    offsets.retain_mut(|o| {
+        let kept = keep(o);
+        if !kept {
+            drop(Some(Dropped::MorphOffset { morph, offset }));
+        }
+        offset += 1;
+        kept
+    });
+}
+
+fn permute<T>(items: Vec<T>, map: &[Option<usize>], new_len: usize) -> Vec<T> {
+    let mut slots = (0..new_len).map(|_| None).collect::<Vec<_>>();
+    for (item, new) in items.into_iter().zip(map) {
+        if let Some(slot) = new.and_then(|new| slots.get_mut(new)) {
+            if slot.is_none() {
+                *slot = Some(item);
+            }
+        }
+    }
+    slots
+        .into_iter()
+        .map(|slot| slot.expect("every new index must be mapped"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn bone_references(pmx: &Pmx) -> Vec<usize> {
+        let mut refs = vec![];
+        for v in &pmx.vertices {
+            match &v.weight {
+                Weight::Bdef1(w) => refs.extend(w.bone),
+                Weight::Bdef2(w) => refs.extend(w.bones.iter().flatten()),
+                Weight::Bdef4(w) => refs.extend(w.bones.iter().flatten()),
+                Weight::Sdef(w) => refs.extend(w.bones.iter().flatten()),
+            }
+        }
+        for b in &pmx.bones {
+            refs.extend(b.parent);
+            if let Some(ik) = &b.ik {
+                refs.extend(ik.bone);
+                refs.extend(ik.links.iter().filter_map(|l| l.bone));
+            }
+            refs.extend(b.addition.as_ref().and_then(|a| a.bone));
+        }
+        refs.extend(pmx.rigids.iter().filter_map(|r| r.bone));
+        refs
+    }
+
+    #[test]
+    fn remove_bone() {
+        let mut pmx = read_pmx();
+        let removed = pmx.bones[10].name.clone();
+        let report = pmx.remove_bones(&[10]).unwrap();
+        assert!(pmx.bones.len() == 149);
+        assert!(pmx.bones.iter().all(|b| b.name != removed));
+        assert!(bone_references(&pmx).iter().all(|&b| b < pmx.bones.len()));
+        assert!(!report.dropped.is_empty());
+    }
+
+    #[test]
+    fn reorder_bones() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let order = (0..pmx.bones.len()).rev().collect::<Vec<_>>();
+        let report = pmx.reorder_bones(&order).unwrap();
+        assert!(report.dropped.is_empty());
+        assert!(pmx.bones[0].name == original.bones[149].name);
+        let parent = original.bones[149].parent.map(|p| 149 - p);
+        assert!(pmx.bones[0].parent == parent);
+        pmx.reorder_bones(&order).unwrap();
+        assert!(bone_references(&pmx) == bone_references(&original));
+        assert!(pmx.reorder_bones(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn insert_bone() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let bone = pmx.bones[0].clone();
+        pmx.insert_bone(0, bone).unwrap();
+        assert!(pmx.bones.len() == 151);
+        assert!(bone_references(&pmx)
+            .iter()
+            .zip(bone_references(&original))
+            .all(|(&new, old)| new == old + 1));
+    }
+
+    #[test]
+    fn remove_vertices() {
+        let mut pmx = read_pmx();
+        let report = pmx.remove_vertices(&[0, 1, 2]).unwrap();
+        assert!(pmx.vertices.len() == 22308);
+        let triangles = report
+            .dropped
+            .iter()
+            .filter(|d| matches!(d, Dropped::Face { .. }))
+            .count();
+        assert!(triangles > 0);
+        assert!(pmx.faces.len() == 95598 - triangles * 3);
+        assert!(
+            pmx.materials
+                .iter()
+                .map(|m| m.index_count as usize)
+                .sum::<usize>()
+                == pmx.faces.len()
+        );
+        assert!(pmx.faces.iter().all(|&f| (f as usize) < pmx.vertices.len()));
+    }
+
+    #[test]
+    fn materials() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let order = (0..22).rev().collect::<Vec<_>>();
+        pmx.reorder_materials(&order).unwrap();
+        let last = original.materials[21].index_count as usize;
+        assert!(pmx.faces[..last] == original.faces[original.faces.len() - last..]);
+        let first = original.materials[0].index_count as usize;
+        pmx.remove_materials(&[21]).unwrap();
+        assert!(pmx.faces.len() == original.faces.len() - first);
+        assert!(pmx.remove_materials(&[100]).is_err());
+    }
+
+    #[test]
+    fn remove_texture() {
+        let mut pmx = read_pmx();
+        let report = pmx.remove_textures(&[0]).unwrap();
+        assert!(pmx.materials[0].texture.is_none());
+        assert!(pmx.materials[2].texture == Some(1));
+        assert!(report
+            .dropped
+            .contains(&Dropped::MaterialTexture { material: 0 }));
+    }
+}
@@ -0,0 +1,322 @@
+//! Extraction of a part of a model into a self-consistent model of its own.
+
+use crate::edit::{Error, Kind};
+use crate::weight::influences;
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub enum Selection {
+    /// Keeps the given materials and their faces.
+    Materials(Vec<usize>),
+    /// Keeps the triangles whose vertices are all influenced by the bone or one of its descendants.
+    BoneSubtree(usize),
+}
+
+impl Pmx {
+    /// Returns a model holding only the selected geometry and what it references.
+    ///
+    /// Bones are kept together with their ancestors, IK chains and addition sources,
+    /// rigids are kept with their bones and joints with both of their rigids.
+    /// Morphs and display groups left empty by the extraction are removed.
+    pub fn extract(&self, selection: &Selection) -> Result<Pmx, Error> {
+        let mut pmx = self.clone();
+        let mut keep_bones = vec![false; self.bones.len()];
+        match selection {
+            Selection::Materials(materials) => {
+                let mut keep = vec![false; self.materials.len()];
+                for &m in materials {
+                    *keep
+                        .get_mut(m)
+                        .ok_or(Error::OutOfRange(Kind::Material, m))? = true;
+                }
+                self.filter_faces(&mut pmx, |m, _| keep[m]);
+            }
+            Selection::BoneSubtree(root) => {
+                if *root >= self.bones.len() {
+                    return Err(Error::OutOfRange(Kind::Bone, *root));
+                }
+                for (i, keep) in keep_bones.iter_mut().enumerate() {
+                    *keep = self.is_descendant(i, *root);
+                }
+                let inside = self
+                    .vertices
+                    .iter()
+                    .map(|v| {
+                        influences(&v.weight)
+                            .into_iter()
+                            .try_fold(false, |inside, (b, _)| {
+                                let keep =
+                                    keep_bones.get(b).ok_or(Error::OutOfRange(Kind::Bone, b))?;
+                                Ok(inside || *keep)
+                            })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                // out of range vertices keep their faces so that they are reported below
+                self.filter_faces(&mut pmx, |_, face| {
+                    face.iter().all(|&i| inside.get(i as usize) != Some(&false))
+                });
+            }
+        }
+
+        let mut keep_vertices = vec![false; self.vertices.len()];
+        for &f in pmx.faces.iter() {
+            *keep_vertices
+                .get_mut(f as usize)
+                .ok_or(Error::OutOfRange(Kind::Vertex, f as usize))? = true;
+        }
+        for (vertex, _) in self
+            .vertices
+            .iter()
+            .zip(&keep_vertices)
+            .filter(|(_, k)| **k)
+        {
+            for (b, _) in influences(&vertex.weight) {
+                *keep_bones
+                    .get_mut(b)
+                    .ok_or(Error::OutOfRange(Kind::Bone, b))? = true;
+            }
+        }
+        self.close_bones(&mut keep_bones);
+        let keep_rigids = self
+            .rigids
+            .iter()
+            .map(|r| r.bone.is_some_and(|b| keep_bones.get(b) == Some(&true)))
+            .collect::<Vec<_>>();
+        pmx.joints.retain(|j| {
+            j.rigids
+                .iter()
+                .all(|r| r.is_some_and(|r| keep_rigids.get(r) == Some(&true)))
+        });
+        let mut keep_textures = vec![false; self.textures.len()];
+        for material in pmx.materials.iter().filter(|m| m.index_count > 0) {
+            let toon = match material.toon {
+                Toon::Texture(t) => t,
+                Toon::Shared(_) => None,
+            };
+            for t in [material.texture, material.sphere, toon]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(keep) = keep_textures.get_mut(t) {
+                    *keep = true;
+                }
+            }
+        }
+
+        let unused = |keep: &[bool]| {
+            keep.iter()
+                .enumerate()
+                .filter(|(_, k)| !**k)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+        let empty_materials = pmx
+            .materials
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.index_count == 0)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        pmx.remove_materials(&empty_materials)?;
+        pmx.remove_vertices(&unused(&keep_vertices))?;
+        pmx.remove_bones(&unused(&keep_bones))?;
+        pmx.remove_rigids(&unused(&keep_rigids))?;
+        pmx.remove_textures(&unused(&keep_textures))?;
+        pmx.remove_empty_morphs(self)?;
+        pmx.display_groups
+            .retain(|g| g.special || !g.elements.is_empty());
+        Ok(pmx)
+    }
+
+    /// Rebuilds the faces of `pmx` by walking the material ranges of this model,
+    /// keeping the triangles for which `keep(material, face)` holds.
+    fn filter_faces(&self, pmx: &mut Pmx, keep: impl Fn(usize, &[u32]) -> bool) {
+        let mut faces = vec![];
+        let mut rest = self.faces.as_slice();
+        for (m, (material, original)) in pmx.materials.iter_mut().zip(&self.materials).enumerate() {
+            let count = (original.index_count as usize).min(rest.len());
+            let (range, tail) = rest.split_at(count);
+            material.index_count = 0;
+            for face in range.chunks_exact(3) {
+                if keep(m, face) {
+                    faces.extend_from_slice(face);
+                    material.index_count += 3;
+                }
+            }
+            rest = tail;
+        }
+        pmx.faces = faces;
+    }
+
+    fn is_descendant(&self, mut bone: usize, root: usize) -> bool {
+        for _ in 0..self.bones.len() {
+            if bone == root {
+                return true;
+            }
+            match self.bones[bone].parent {
+                Some(parent) if parent < self.bones.len() => bone = parent,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Adds the ancestors, IK chains and addition sources of every kept bone.
+    fn close_bones(&self, keep: &mut [bool]) {
+        let len = keep.len();
+        let mut stack = (0..keep.len()).filter(|&i| keep[i]).collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            let bone = &self.bones[i];
+            let ik = bone
+                .ik
+                .iter()
+                .flat_map(|ik| std::iter::once(ik.bone).chain(ik.links.iter().map(|l| l.bone)));
+            let addition = bone.addition.as_ref().and_then(|a| a.bone);
+            for b in ik
+                .chain([bone.parent, addition])
+                .flatten()
+                .filter(|&b| b < len)
+            {
+                if !keep[b] {
+                    keep[b] = true;
+                    stack.push(b);
+                }
+            }
+        }
+    }
+
+    /// Removes morphs that lost all of their offsets, repeating for group morphs left empty.
+    fn remove_empty_morphs(&mut self, original: &Pmx) -> Result<(), Error> {
+        let mut populated = original
+            .morphs
+            .iter()
+            .map(|m| morph_len(&m.kind) > 0)
+            .collect::<Vec<_>>();
+        loop {
+            let empty = (0..self.morphs.len())
+                .filter(|&i| populated[i] && morph_len(&self.morphs[i].kind) == 0)
+                .collect::<Vec<_>>();
+            if empty.is_empty() {
+                return Ok(());
+            }
+            self.remove_morphs(&empty)?;
+            let mut i = 0;
+            populated.retain(|_| {
+                i += 1;
+                !empty.contains(&(i - 1))
+            });
+        }
+    }
+}
+
+fn morph_len(kind: &morph::Kind) -> usize {
+    match kind {
+        morph::Kind::Vertex(v) => v.len(),
+        morph::Kind::Uv(v) | morph::Kind::ExtendedUv(_, v) => v.len(),
+        morph::Kind::Bone(v) => v.len(),
+        morph::Kind::Maerial(v) => v.len(),
+        morph::Kind::Group(v) => v.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn assert_consistent(pmx: &Pmx) {
+        let faces = pmx
+            .materials
+            .iter()
+            .map(|m| m.index_count as usize)
+            .sum::<usize>();
+        assert!(faces == pmx.faces.len());
+        assert!(pmx.faces.iter().all(|&f| (f as usize) < pmx.vertices.len()));
+        for v in &pmx.vertices {
+            assert!(influences(&v.weight)
+                .iter()
+                .all(|&(b, _)| b < pmx.bones.len()));
+        }
+        for b in &pmx.bones {
+            assert!(b.parent.is_none_or(|p| p < pmx.bones.len()));
+        }
+        for m in &pmx.materials {
+            assert!(m.texture.is_none_or(|t| t < pmx.textures.len()));
+        }
+        for j in &pmx.joints {
+            assert!(j
+                .rigids
+                .iter()
+                .all(|r| r.is_some_and(|r| r < pmx.rigids.len())));
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let original = read_pmx();
+        let pmx = original.extract(&Selection::Materials(vec![0])).unwrap();
+        assert_consistent(&pmx);
+        assert!(pmx.materials.len() == 1);
+        assert!(pmx.materials[0].name == original.materials[0].name);
+        assert!(pmx.faces.len() == original.materials[0].index_count as usize);
+        assert!(pmx.vertices.len() < original.vertices.len());
+        assert!(pmx.textures.len() == 2);
+        assert!(pmx.bones.len() < original.bones.len());
+        assert!(original.extract(&Selection::Materials(vec![22])).is_err());
+    }
+
+    #[test]
+    fn material_triangles() {
+        let original = read_pmx();
+        let pmx = original.extract(&Selection::Materials(vec![1])).unwrap();
+        assert_consistent(&pmx);
+        let start = original.materials[0].index_count as usize;
+        let end = start + original.materials[1].index_count as usize;
+        let positions = |pmx: &Pmx, faces: &[u32]| {
+            faces
+                .iter()
+                .map(|&f| pmx.vertices[f as usize].position)
+                .collect::<Vec<_>>()
+        };
+        assert!(positions(&pmx, &pmx.faces) == positions(&original, &original.faces[start..end]));
+    }
+
+    #[test]
+    fn bone_subtree() {
+        let original = read_pmx();
+        let arm = original
+            .bones
+            .iter()
+            .position(|b| b.name == "左腕")
+            .unwrap();
+        let pmx = original.extract(&Selection::BoneSubtree(arm)).unwrap();
+        assert_consistent(&pmx);
+        assert!(!pmx.faces.is_empty());
+        assert!(pmx.bones.iter().any(|b| b.name == "左腕"));
+        assert!(pmx.bones.iter().any(|b| b.name == "左手首"));
+        assert!(pmx.bones.iter().any(|b| b.name == "上半身"));
+        assert!(pmx.bones.iter().all(|b| b.name != "右腕"));
+        assert!(pmx.vertices.len() < original.vertices.len());
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut pmx = read_pmx();
+        pmx.faces[0] = pmx.vertices.len() as u32;
+        let result = pmx.extract(&Selection::Materials(vec![0]));
+        assert!(matches!(result, Err(Error::OutOfRange(Kind::Vertex, _))));
+        let result = pmx.extract(&Selection::BoneSubtree(0));
+        assert!(matches!(result, Err(Error::OutOfRange(Kind::Vertex, _))));
+
+        let mut pmx = read_pmx();
+        pmx.vertices[0].weight = Weight::Bdef1(Bdef1 {
+            bone: Some(pmx.bones.len()),
+        });
+        let result = pmx.extract(&Selection::BoneSubtree(0));
+        assert!(matches!(result, Err(Error::OutOfRange(Kind::Bone, _))));
+    }
+}
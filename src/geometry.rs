@@ -0,0 +1,134 @@
+//! Recomputation of normals and, with the `tangents` feature, generation of tangents.
+//!
+//! The free functions take positions and normals separately so that they work
+//! for posed meshes as well as for the rest pose stored in a `Pmx`.
+
+use crate::math::*;
+use crate::*;
+use std::collections::HashMap;
+
+#[cfg(feature = "tangents")]
+mod tangents;
+
+#[cfg(feature = "tangents")]
+pub use tangents::tangents;
+
+/// Computes area-weighted smooth normals.
+///
+/// Faces meeting at the same position are averaged together even across vertices
+/// split by UV seams, unless they bend away from the faces of the vertex itself by
+/// more than `angle` radians. Vertices referenced by no face get a zero normal.
+pub fn smooth_normals(positions: &[[f32; 3]], faces: &[u32], angle: f32) -> Vec<[f32; 3]> {
+    let face_normals = faces
+        .chunks_exact(3)
+        .map(|f| {
+            let p = [f[0], f[1], f[2]].map(|i| positions[i as usize]);
+            cross(sub(p[1], p[0]), sub(p[2], p[0]))
+        })
+        .collect::<Vec<_>>();
+    let mut groups = HashMap::new();
+    let group_of = positions
+        .iter()
+        .map(|p| {
+            let next = groups.len();
+            *groups.entry(p.map(|x| (x + 0.0).to_bits())).or_insert(next)
+        })
+        .collect::<Vec<_>>();
+    let mut group_faces = vec![vec![]; groups.len()];
+    let mut own = vec![[0.0; 3]; positions.len()];
+    for (t, f) in faces.chunks_exact(3).enumerate() {
+        let n = normalize(face_normals[t]);
+        for &i in f {
+            group_faces[group_of[i as usize]].push(t);
+            own[i as usize] = add(own[i as usize], n);
+        }
+    }
+    let threshold = angle.cos();
+    own.iter()
+        .zip(&group_of)
+        .map(|(&own, &group)| {
+            let own = normalize(own);
+            let mut faces = group_faces[group].clone();
+            faces.sort_unstable();
+            faces.dedup();
+            let sum = faces
+                .into_iter()
+                .map(|t| face_normals[t])
+                .filter(|&n| dot(normalize(n), own) >= threshold)
+                .fold([0.0; 3], add);
+            normalize(sum)
+        })
+        .collect()
+}
+
+impl Pmx {
+    /// Replaces the normals of all vertices used by a face, see [`smooth_normals`].
+    pub fn recompute_normals(&mut self, angle: f32) {
+        let positions = self.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+        let normals = smooth_normals(&positions, &self.faces, angle);
+        for (v, n) in self.vertices.iter_mut().zip(normals) {
+            if n != [0.0; 3] {
+                v.normal = n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    // a unit quad facing -z
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+    ];
+    const FACES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn quad() {
+        let normals = smooth_normals(&POSITIONS, &FACES, 0.5);
+        assert!(normals.iter().all(|n| *n == [0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn hard_edge() {
+        // two faces folded by 90 degrees
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let faces = [0, 1, 2, 3, 5, 4];
+        let sharp = smooth_normals(&positions, &faces, 0.5);
+        assert!(sharp[0] == [0.0, 0.0, -1.0]);
+        assert!(sharp[3] == [-1.0, 0.0, 0.0]);
+        let smooth = smooth_normals(&positions, &faces, 2.0);
+        assert!(smooth[0] == smooth[3]);
+        assert!((length(smooth[0]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn alicia() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        pmx.recompute_normals(std::f32::consts::PI);
+        let agree = pmx
+            .vertices
+            .iter()
+            .zip(&original.vertices)
+            .filter(|(a, b)| dot(a.normal, b.normal) > 0.5)
+            .count();
+        assert!(agree * 10 > original.vertices.len() * 9);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::math::*;
+use crate::*;
+
+/// Computes per-vertex MikkTSpace tangents, matching what normal map bakers expect.
+///
+/// MikkTSpace assigns tangents to triangle corners, so a vertex whose corners get
+/// different tangents takes the one of its first corner, and corners whose UVs
+/// are degenerate get `[1, 0, 0]` as in the reference implementation. Vertices
+/// referenced by no face get a tangent perpendicular to their normal. `w` holds
+/// the handedness, so that the bitangent is `cross(normal, tangent) * w`.
+pub fn tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    faces: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut mesh = MikkMesh {
+        positions,
+        normals,
+        uvs,
+        faces,
+        tangents: vec![None; positions.len()],
+    };
+    mikktspace::generate_tangents(&mut mesh);
+    mesh.tangents
+        .into_iter()
+        .zip(normals)
+        .map(|(t, &n)| {
+            t.unwrap_or_else(|| {
+                let t = perpendicular(n);
+                [t[0], t[1], t[2], 1.0]
+            })
+        })
+        .collect()
+}
+
+struct MikkMesh<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    uvs: &'a [[f32; 2]],
+    faces: &'a [u32],
+    tangents: Vec<Option<[f32; 4]>>,
+}
+
+impl MikkMesh<'_> {
+    fn index(&self, face: usize, vert: usize) -> usize {
+        self.faces[face * 3 + vert] as usize
+    }
+}
+
+impl mikktspace::Geometry for MikkMesh<'_> {
+    fn num_faces(&self) -> usize {
+        self.faces.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.index(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.index(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.uvs[self.index(face, vert)]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let i = self.index(face, vert);
+        self.tangents[i].get_or_insert(tangent);
+    }
+}
+
+/// Returns any unit vector perpendicular to `n`.
+fn perpendicular(n: [f32; 3]) -> [f32; 3] {
+    let axis = if n[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    normalize(cross(n, axis))
+}
+
+impl Pmx {
+    /// Returns the tangents of the rest pose, see [`tangents`].
+    pub fn tangents(&self) -> Vec<[f32; 4]> {
+        let positions = self.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+        let normals = self.vertices.iter().map(|v| v.normal).collect::<Vec<_>>();
+        let uvs = self.vertices.iter().map(|v| v.uv).collect::<Vec<_>>();
+        tangents(&positions, &normals, &uvs, &self.faces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn quad() {
+        // a unit quad facing -z with u along +x and v along -y
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ];
+        let uvs = [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        let faces = [0, 1, 2, 0, 2, 3];
+        let normals = [[0.0, 0.0, -1.0]; 4];
+        let tangents = tangents(&positions, &normals, &uvs, &faces);
+        assert!(tangents.iter().all(|t| *t == [1.0, 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn reference_values() {
+        // the +x side of the cube in the MikkTSpace regression test, a fan of four
+        // triangles around the center, with tangents from the reference implementation
+        let corners = [
+            ([1.0, -1.0, 1.0], [0.0, 0.0]),
+            ([1.0, -1.0, -1.0], [0.0, 1.0]),
+            ([1.0, 1.0, -1.0], [1.0, 1.0]),
+            ([1.0, 1.0, 1.0], [1.0, 0.0]),
+            ([1.0, 0.0, 0.0], [0.5, 0.5]),
+        ];
+        let positions = corners.map(|(p, _)| scale(p, 0.5));
+        let normals = corners.map(|(p, _)| normalize(p));
+        let uvs = corners.map(|(_, uv)| uv);
+        let faces = [0, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4];
+        let a = 0.408_248_25;
+        let b = 0.816_496_55;
+        let expected = [
+            [a, b, a, -1.0],
+            [a, b, -a, -1.0],
+            [-a, b, a, -1.0],
+            [-a, b, -a, -1.0],
+            [0.0, 1.0, 0.0, -1.0],
+        ];
+        let tangents = tangents(&positions, &normals, &uvs, &faces);
+        for (t, e) in tangents.iter().zip(&expected) {
+            assert!(t.iter().zip(e).all(|(t, e)| (t - e).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn alicia() {
+        let mut pmx = read_pmx();
+        pmx.recompute_normals(std::f32::consts::PI);
+        for (t, v) in pmx.tangents().iter().zip(&pmx.vertices) {
+            let tangent = [t[0], t[1], t[2]];
+            assert!((length(tangent) - 1.0).abs() < 1e-3);
+            // MikkTSpace falls back to +x where the UVs of every triangle are degenerate
+            assert!(dot(tangent, v.normal).abs() < 1e-3 || tangent == [1.0, 0.0, 0.0]);
+            assert!(t[3].abs() == 1.0);
+        }
+    }
+}
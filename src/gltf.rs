@@ -0,0 +1,887 @@
+//! Import and export of skinned glTF 2.0 models.
+//!
+//! glTF is right-handed and measured in meters while MMD is left-handed and
+//! uses units of roughly 8 cm, so positions are mirrored along Z and scaled by
+//! [`ImportOptions::scale`]. Mirroring turns the faces inside out, so the
+//! second and third index of every triangle are swapped to keep the faces
+//! pointing the same way as their normals.
+
+use crate::math::{self, Mat4};
+use crate::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+mod export;
+
+pub use export::{export, ExportOptions};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("gltf error: {}", .0)]
+    Gltf(::gltf::Error),
+    #[error("invalid data: {}", .0)]
+    InvalidData(String),
+    #[error("image {} is embedded in the file", .0)]
+    EmbeddedImage(usize),
+    #[error("io error: {}", .0)]
+    Io(std::io::Error),
+}
+
+impl From<::gltf::Error> for Error {
+    fn from(src: ::gltf::Error) -> Self {
+        Self::Gltf(src)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(src: std::io::Error) -> Self {
+        Self::Io(src)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Factor applied to every position and morph offset.
+    pub scale: f32,
+    /// Scene to import. `None` selects the default scene, or the first one.
+    pub scene: Option<usize>,
+    /// What to do with textures whose image is stored inside the file.
+    pub embedded_images: EmbeddedImages,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            scale: 12.5,
+            scene: None,
+            embedded_images: EmbeddedImages::Error,
+        }
+    }
+}
+
+/// Handling of images held in a buffer view or a `data:` URI, which a PMX file cannot contain.
+#[derive(Clone, Debug)]
+pub enum EmbeddedImages {
+    /// Fails the import with [`Error::EmbeddedImage`].
+    Error,
+    /// Imports the materials using them without a texture.
+    Ignore,
+    /// Writes each image to the directory as `image{index}.{ext}` and refers to it by file
+    /// name, so this is normally the directory the PMX file will be saved to.
+    Write(PathBuf),
+}
+
+/// Imports a `.gltf` or `.glb` file. External buffers are resolved relative to `path`.
+pub fn import(path: impl AsRef<Path>, options: &ImportOptions) -> Result<Pmx, Error> {
+    let path = path.as_ref();
+    let ::gltf::Gltf { document, blob } = ::gltf::Gltf::open(path)?;
+    let buffers = ::gltf::import_buffers(&document, path.parent(), blob)?;
+    let mut pmx = Importer::new(&document, &buffers, options).import()?;
+    if pmx.model_info.name.is_empty() {
+        if let Some(stem) = path.file_stem() {
+            pmx.model_info.name = stem.to_string_lossy().into_owned();
+        }
+    }
+    Ok(pmx)
+}
+
+/// Imports a glTF or GLB document held in memory. External buffers are resolved relative to `base`.
+pub fn import_slice(
+    data: &[u8],
+    base: Option<&Path>,
+    options: &ImportOptions,
+) -> Result<Pmx, Error> {
+    let ::gltf::Gltf { document, blob } = ::gltf::Gltf::from_slice(data)?;
+    let buffers = ::gltf::import_buffers(&document, base, blob)?;
+    Importer::new(&document, &buffers, options).import()
+}
+
+struct Importer<'a> {
+    document: &'a ::gltf::Document,
+    buffers: &'a [::gltf::buffer::Data],
+    options: &'a ImportOptions,
+    globals: HashMap<usize, Mat4>,
+    joint_bones: HashMap<usize, usize>,
+    pmx: Pmx,
+    texture_map: HashMap<String, usize>,
+    morph_map: HashMap<String, usize>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(
+        document: &'a ::gltf::Document,
+        buffers: &'a [::gltf::buffer::Data],
+        options: &'a ImportOptions,
+    ) -> Self {
+        Self {
+            document,
+            buffers,
+            options,
+            globals: HashMap::new(),
+            joint_bones: HashMap::new(),
+            pmx: Pmx {
+                header: Header {
+                    version: 2.0,
+                    encoding: Encoding::Utf16,
+                    extended_uv: 0,
+                    vertex_index_size: 1,
+                    texture_index_size: 1,
+                    material_index_size: 1,
+                    bone_index_size: 1,
+                    morph_index_size: 1,
+                    rigid_index_size: 1,
+                },
+                model_info: ModelInfo {
+                    name: String::new(),
+                    name_en: String::new(),
+                    comment: String::new(),
+                    comment_en: String::new(),
+                },
+                vertices: vec![],
+                faces: vec![],
+                textures: vec![],
+                materials: vec![],
+                bones: vec![],
+                morphs: vec![],
+                display_groups: vec![],
+                rigids: vec![],
+                joints: vec![],
+            },
+            texture_map: HashMap::new(),
+            morph_map: HashMap::new(),
+        }
+    }
+
+    fn import(mut self) -> Result<Pmx, Error> {
+        let scene = match self.options.scene {
+            Some(i) => self
+                .document
+                .scenes()
+                .nth(i)
+                .ok_or_else(|| Error::InvalidData(format!("scene({})", i)))?,
+            None => self
+                .document
+                .default_scene()
+                .or_else(|| self.document.scenes().next())
+                .ok_or_else(|| Error::InvalidData("no scene".into()))?,
+        };
+        if let Some(name) = scene.name() {
+            self.pmx.model_info.name = name.into();
+        }
+        let joints = self
+            .document
+            .skins()
+            .flat_map(|skin| skin.joints().map(|node| node.index()))
+            .collect::<std::collections::HashSet<_>>();
+        for node in scene.nodes() {
+            self.bones(node, &math::IDENTITY, None, &joints);
+        }
+        if self.pmx.bones.is_empty() {
+            self.pmx
+                .bones
+                .push(new_bone("全ての親".into(), [0.0; 3], None));
+        }
+        self.connect_bones();
+        for node in scene.nodes() {
+            self.meshes(node, None)?;
+        }
+        self.display_groups();
+        self.pmx.update_index_sizes();
+        Ok(self.pmx)
+    }
+
+    fn convert_position(&self, p: [f32; 3]) -> [f32; 3] {
+        let s = self.options.scale;
+        [p[0] * s, p[1] * s, -p[2] * s]
+    }
+
+    fn bones(
+        &mut self,
+        node: ::gltf::Node,
+        parent_global: &Mat4,
+        parent_bone: Option<usize>,
+        joints: &std::collections::HashSet<usize>,
+    ) {
+        let global = math::mul(parent_global, &node.transform().matrix());
+        let mut bone = parent_bone;
+        if joints.contains(&node.index()) {
+            let index = self.pmx.bones.len();
+            let name = node
+                .name()
+                .map(Into::into)
+                .unwrap_or_else(|| format!("bone{}", index));
+            let position = self.convert_position([global[3][0], global[3][1], global[3][2]]);
+            self.pmx.bones.push(new_bone(name, position, parent_bone));
+            self.joint_bones.insert(node.index(), index);
+            bone = Some(index);
+        }
+        self.globals.insert(node.index(), global);
+        for child in node.children() {
+            self.bones(child, &global, bone, joints);
+        }
+    }
+
+    fn connect_bones(&mut self) {
+        let mut children = vec![vec![]; self.pmx.bones.len()];
+        for (i, bone) in self.pmx.bones.iter().enumerate() {
+            if let Some(parent) = bone.parent {
+                children[parent].push(i);
+            }
+        }
+        for (bone, children) in self.pmx.bones.iter_mut().zip(children) {
+            if let [child] = children[..] {
+                bone.connected_to = ConnectedTo::Bone(Some(child));
+            }
+        }
+    }
+
+    fn meshes(&mut self, node: ::gltf::Node, parent_bone: Option<usize>) -> Result<(), Error> {
+        let bone = self.joint_bones.get(&node.index()).copied().or(parent_bone);
+        if let Some(mesh) = node.mesh() {
+            let global = self.globals[&node.index()];
+            let skin = node
+                .skin()
+                .map(|skin| self.skin_matrices(&skin))
+                .transpose()?;
+            let target_names = target_names(&mesh);
+            for primitive in mesh.primitives() {
+                if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let fallback = bone.unwrap_or(0);
+                self.primitive(
+                    &primitive,
+                    &global,
+                    skin.as_deref(),
+                    fallback,
+                    &target_names,
+                )?;
+            }
+        }
+        for child in node.children() {
+            self.meshes(child, bone)?;
+        }
+        Ok(())
+    }
+
+    fn skin_matrices(&self, skin: &::gltf::Skin) -> Result<Vec<(usize, Mat4)>, Error> {
+        let reader = skin.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let inverse_binds = reader
+            .read_inverse_bind_matrices()
+            .map(|iter| iter.collect::<Vec<_>>());
+        skin.joints()
+            .enumerate()
+            .map(|(i, joint)| {
+                let not_in_scene =
+                    || Error::InvalidData(format!("joint({}) is not in the scene", joint.index()));
+                let bone = *self
+                    .joint_bones
+                    .get(&joint.index())
+                    .ok_or_else(not_in_scene)?;
+                let global = self.globals.get(&joint.index()).ok_or_else(not_in_scene)?;
+                let inverse_bind = inverse_binds
+                    .as_ref()
+                    .and_then(|m| m.get(i).copied())
+                    .unwrap_or(math::IDENTITY);
+                Ok((bone, math::mul(global, &inverse_bind)))
+            })
+            .collect()
+    }
+
+    fn primitive(
+        &mut self,
+        primitive: &::gltf::Primitive,
+        global: &Mat4,
+        skin: Option<&[(usize, Mat4)]>,
+        fallback_bone: usize,
+        target_names: &[String],
+    ) -> Result<(), Error> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| Error::InvalidData("primitive without positions".into()))?
+            .collect::<Vec<_>>();
+        let len = positions.len();
+        let normals = reader
+            .read_normals()
+            .map(|iter| iter.collect::<Vec<_>>())
+            .unwrap_or_else(|| vec![[0.0; 3]; len]);
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|iter| iter.into_f32().collect::<Vec<_>>())
+            .unwrap_or_else(|| vec![[0.0; 2]; len]);
+        let mut influences = vec![vec![]; len];
+        if let Some(skin) = skin {
+            let mut set = 0;
+            while let (Some(joints), Some(weights)) =
+                (reader.read_joints(set), reader.read_weights(set))
+            {
+                for ((dst, joints), weights) in influences
+                    .iter_mut()
+                    .zip(joints.into_u16())
+                    .zip(weights.into_f32())
+                {
+                    for (j, w) in joints.iter().zip(weights) {
+                        let bone = skin
+                            .get(*j as usize)
+                            .ok_or_else(|| Error::InvalidData(format!("joint index({})", j)))?;
+                        dst.push((*j as usize, bone.0, w));
+                    }
+                }
+                set += 1;
+            }
+        }
+        let transforms = influences
+            .iter()
+            .map(|influences| skinning_matrix(influences, skin, global))
+            .collect::<Vec<_>>();
+        let base = self.pmx.vertices.len();
+        for i in 0..len {
+            let m = &transforms[i];
+            let position = self.convert_position(math::transform_point(m, positions[i]));
+            let n = math::normalize(math::transform_vector(m, normals[i]));
+            let weight = to_weight(
+                influences[i].iter().map(|&(_, bone, w)| (bone, w)),
+                fallback_bone,
+            );
+            self.pmx.vertices.push(Vertex {
+                position,
+                normal: [n[0], n[1], -n[2]],
+                uv: uvs[i],
+                extended_uv: vec![],
+                weight,
+                edge_ratio: 1.0,
+            });
+        }
+        for (t, (displacements, _, _)) in reader.read_morph_targets().enumerate() {
+            let Some(displacements) = displacements else {
+                continue;
+            };
+            let name = target_names
+                .get(t)
+                .cloned()
+                .unwrap_or_else(|| format!("morph{}", t));
+            let offsets = displacements
+                .enumerate()
+                .filter(|(_, d)| d.iter().any(|v| *v != 0.0))
+                .map(|(i, d)| morph::Vertex {
+                    vertex: Some(base + i),
+                    offset: self.convert_position(math::transform_vector(&transforms[i], d)),
+                })
+                .collect::<Vec<_>>();
+            self.morph(name, offsets);
+        }
+        let faces = match reader.read_indices() {
+            Some(indices) => indices
+                .into_u32()
+                .map(|i| match (i as usize) < len {
+                    true => Ok((base as u32) + i),
+                    false => Err(Error::InvalidData(format!(
+                        "index {} out of range for {} vertices",
+                        i, len
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => (base as u32..(base + len) as u32).collect(),
+        };
+        let index_count = faces.len() as u32 - faces.len() as u32 % 3;
+        self.pmx
+            .faces
+            .extend(faces.chunks_exact(3).flat_map(|f| [f[0], f[2], f[1]]));
+        let material = self.material(&primitive.material(), index_count)?;
+        self.pmx.materials.push(material);
+        Ok(())
+    }
+
+    fn morph(&mut self, name: String, offsets: Vec<morph::Vertex>) {
+        let index = *self.morph_map.entry(name.clone()).or_insert_with(|| {
+            self.pmx.morphs.push(Morph {
+                name,
+                name_en: String::new(),
+                panel: Panel::Other,
+                kind: morph::Kind::Vertex(vec![]),
+            });
+            self.pmx.morphs.len() - 1
+        });
+        if let morph::Kind::Vertex(v) = &mut self.pmx.morphs[index].kind {
+            v.extend(offsets);
+        }
+    }
+
+    fn texture(&mut self, info: Option<::gltf::texture::Info>) -> Result<Option<usize>, Error> {
+        let Some(info) = info else {
+            return Ok(None);
+        };
+        let image = info.texture().source();
+        let path = match image.source() {
+            ::gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                percent_decode(uri)
+            }
+            _ => match self.embedded_image(&image)? {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        let textures = &mut self.pmx.textures;
+        Ok(Some(*self.texture_map.entry(path.clone()).or_insert_with(
+            || {
+                textures.push(path.into());
+                textures.len() - 1
+            },
+        )))
+    }
+
+    /// Writes out an image held in a buffer view or a `data:` URI, returning its file name.
+    fn embedded_image(&self, image: &::gltf::Image) -> Result<Option<String>, Error> {
+        let dir = match &self.options.embedded_images {
+            EmbeddedImages::Error => return Err(Error::EmbeddedImage(image.index())),
+            EmbeddedImages::Ignore => return Ok(None),
+            EmbeddedImages::Write(dir) => dir,
+        };
+        let (data, mime_type) = match image.source() {
+            ::gltf::image::Source::View { view, mime_type } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                let data = buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or_else(|| Error::InvalidData("image outside of its buffer".into()))?;
+                (data.to_vec(), mime_type)
+            }
+            ::gltf::image::Source::Uri { uri, mime_type } => {
+                let (header, data) = uri
+                    .split_once(',')
+                    .filter(|(header, _)| header.ends_with(";base64"))
+                    .ok_or_else(|| Error::InvalidData("image data URI is not base64".into()))?;
+                let data = base64::decode(data)
+                    .map_err(|e| Error::InvalidData(format!("image data URI: {}", e)))?;
+                let mime_type = mime_type.unwrap_or(&header[5..header.len() - 7]);
+                (data, mime_type)
+            }
+        };
+        let extension = match mime_type {
+            "image/jpeg" => "jpg",
+            "image/png" => "png",
+            m => m.rsplit('/').next().unwrap_or(m),
+        };
+        let name = format!("image{}.{}", image.index(), extension);
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(&name), data)?;
+        Ok(Some(name))
+    }
+
+    fn material(
+        &mut self,
+        material: &::gltf::Material,
+        index_count: u32,
+    ) -> Result<Material, Error> {
+        let pbr = material.pbr_metallic_roughness();
+        let diffuse = pbr.base_color_factor();
+        let texture = self.texture(pbr.base_color_texture())?;
+        let name = material
+            .name()
+            .map(Into::into)
+            .unwrap_or_else(|| format!("material{}", self.pmx.materials.len()));
+        Ok(Material {
+            name,
+            name_en: String::new(),
+            diffuse,
+            specular: [0.0; 3],
+            specular_power: 5.0,
+            ambient: [diffuse[0] * 0.5, diffuse[1] * 0.5, diffuse[2] * 0.5],
+            both: material.double_sided(),
+            ground_shadow: true,
+            self_shadow_map: true,
+            self_shadow: true,
+            edge: false,
+            edge_color: [0.0, 0.0, 0.0, 1.0],
+            edge_size: 1.0,
+            texture,
+            sphere: None,
+            sphere_mode: SphereMode::None,
+            toon: Toon::Texture(None),
+            memo: String::new(),
+            index_count,
+        })
+    }
+
+    fn display_groups(&mut self) {
+        self.pmx.display_groups = vec![
+            DisplayGroup {
+                name: "Root".into(),
+                name_en: "Root".into(),
+                special: true,
+                elements: vec![DisplayElement::Bone(Some(0))],
+            },
+            DisplayGroup {
+                name: "表情".into(),
+                name_en: "Exp".into(),
+                special: true,
+                elements: (0..self.pmx.morphs.len())
+                    .map(|i| DisplayElement::Morph(Some(i)))
+                    .collect(),
+            },
+        ];
+        if self.pmx.bones.len() > 1 {
+            self.pmx.display_groups.push(DisplayGroup {
+                name: "ボーン".into(),
+                name_en: "Bones".into(),
+                special: false,
+                elements: (1..self.pmx.bones.len())
+                    .map(|i| DisplayElement::Bone(Some(i)))
+                    .collect(),
+            });
+        }
+    }
+}
+
+fn new_bone(name: String, position: [f32; 3], parent: Option<usize>) -> Bone {
+    Bone {
+        name,
+        name_en: String::new(),
+        position,
+        parent,
+        deform_hierarchy: 0,
+        connected_to: ConnectedTo::Offset([0.0; 3]),
+        rotatable: true,
+        translatable: parent.is_none(),
+        visibility: true,
+        operable: true,
+        ik: None,
+        addition: None,
+        after_physics: false,
+        fixed_pole: None,
+        local_pole: None,
+        external_parent: None,
+    }
+}
+
+/// Blends the joint matrices of a vertex, falling back to the node transform for unskinned vertices.
+fn skinning_matrix(
+    influences: &[(usize, usize, f32)],
+    skin: Option<&[(usize, Mat4)]>,
+    global: &Mat4,
+) -> Mat4 {
+    let total = influences.iter().map(|(_, _, w)| w).sum::<f32>();
+    let Some(skin) = skin.filter(|_| total > 0.0) else {
+        return *global;
+    };
+    let mut m = [[0.0f32; 4]; 4];
+    for &(joint, _, w) in influences {
+        let joint = &skin[joint].1;
+        for c in 0..4 {
+            for r in 0..4 {
+                m[c][r] += joint[c][r] * w / total;
+            }
+        }
+    }
+    m
+}
+
+/// Picks the simplest `Weight` that holds the four strongest influences.
+fn to_weight(influences: impl Iterator<Item = (usize, f32)>, fallback: usize) -> Weight {
+    let mut merged: Vec<(usize, f32)> = vec![];
+    for (bone, w) in influences.filter(|(_, w)| *w > 0.0) {
+        match merged.iter_mut().find(|(b, _)| *b == bone) {
+            Some(m) => m.1 += w,
+            None => merged.push((bone, w)),
+        }
+    }
+    merged.sort_by(|a, b| b.1.total_cmp(&a.1));
+    merged.truncate(4);
+    let total = merged.iter().map(|(_, w)| w).sum::<f32>();
+    match merged[..] {
+        [] => Weight::Bdef1(Bdef1 {
+            bone: Some(fallback),
+        }),
+        [(bone, _)] => Weight::Bdef1(Bdef1 { bone: Some(bone) }),
+        [(b0, w0), (b1, _)] => Weight::Bdef2(Bdef2 {
+            bones: [Some(b0), Some(b1)],
+            weight: w0 / total,
+        }),
+        _ => {
+            let mut bones = [None; 4];
+            let mut weights = [0.0; 4];
+            for (i, (bone, w)) in merged.into_iter().enumerate() {
+                bones[i] = Some(bone);
+                weights[i] = w / total;
+            }
+            Weight::Bdef4(Bdef4 { bones, weights })
+        }
+    }
+}
+
+/// Reads the `targetNames` extra written by Blender and most other exporters.
+fn target_names(mesh: &::gltf::Mesh) -> Vec<String> {
+    mesh.extras()
+        .as_ref()
+        .and_then(|extras| {
+            ::gltf::json::deserialize::from_str::<::gltf::json::Value>(extras.get()).ok()
+        })
+        .and_then(|extras| {
+            extras.get("targetNames")?.as_array().map(|names| {
+                names
+                    .iter()
+                    .map(|name| name.as_str().unwrap_or_default().to_string())
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(v)) => {
+                decoded.push(v);
+                i += 3;
+            }
+            (c, _) => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes every byte of `path` except unreserved characters and `/`, the reverse of `percent_decode`.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &b in path.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_f32s(buffer: &mut Vec<u8>, values: &[f32]) {
+        for v in values {
+            buffer.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    // A single triangle skinned to two joints with one morph target.
+    fn sample_glb() -> Vec<u8> {
+        sample_glb_with(&[], |json| json.into())
+    }
+
+    // The sample with `extra` appended to its buffer and its JSON rewritten by `edit`.
+    fn sample_glb_with(extra: &[u8], edit: impl FnOnce(&str) -> String) -> Vec<u8> {
+        let mut bin = vec![];
+        push_f32s(&mut bin, &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0]);
+        bin.extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0]);
+        push_f32s(&mut bin, &[1.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0]);
+        push_f32s(&mut bin, &[1.0, 0.0, 0.0, 0.0]);
+        push_f32s(&mut bin, &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1]);
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"name": "sample", "nodes": [0, 2]}, {"name": "mesh only", "nodes": [2]}],
+            "nodes": [
+                {"name": "root", "children": [1]},
+                {"name": "child", "translation": [0, 1, 0]},
+                {"mesh": 0, "skin": 0}
+            ],
+            "skins": [{"joints": [0, 1]}],
+            "meshes": [{
+                "primitives": [{
+                    "attributes": {"POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2},
+                    "targets": [{"POSITION": 3}],
+                    "material": 0
+                }],
+                "extras": {"targetNames": ["smile"]}
+            }],
+            "materials": [{
+                "name": "skin",
+                "doubleSided": true,
+                "pbrMetallicRoughness": {"baseColorFactor": [1, 0.5, 0.25, 1], "baseColorTexture": {"index": 0}}
+            }],
+            "textures": [{"source": 0}],
+            "images": [{"uri": "tex/skin%20a.png"}],
+            "buffers": [{"byteLength": BIN_LENGTH}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 12},
+                {"buffer": 0, "byteOffset": 48, "byteLength": 48},
+                {"buffer": 0, "byteOffset": 96, "byteLength": 36}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 1]},
+                {"bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4"},
+                {"bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4"},
+                {"bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 0.1]}
+            ]
+        }"#;
+        bin.extend_from_slice(extra);
+        let json = edit(json).replace("BIN_LENGTH", &bin.len().to_string());
+        let mut json = json.into_bytes();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut glb = vec![];
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    fn import_sample() -> Pmx {
+        import_slice(&sample_glb(), None, &ImportOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn bones() {
+        let pmx = import_sample();
+        assert!(pmx.model_info.name == "sample");
+        assert!(pmx.bones.len() == 2);
+        assert!(pmx.bones[1].name == "child");
+        assert!(pmx.bones[1].parent == Some(0));
+        assert!(pmx.bones[1].position == [0.0, 12.5, 0.0]);
+        assert!(matches!(
+            pmx.bones[0].connected_to,
+            ConnectedTo::Bone(Some(1))
+        ));
+    }
+
+    #[test]
+    fn weights() {
+        let pmx = import_sample();
+        assert!(pmx.vertices.len() == 3);
+        assert!(matches!(
+            pmx.vertices[0].weight,
+            Weight::Bdef1(Bdef1 { bone: Some(0) })
+        ));
+        match &pmx.vertices[1].weight {
+            Weight::Bdef2(w) => {
+                assert!(w.bones == [Some(1), Some(0)] || w.bones == [Some(0), Some(1)]);
+                assert!(w.weight == 0.5);
+            }
+            _ => panic!("expected Bdef2"),
+        }
+        assert!(matches!(
+            pmx.vertices[2].weight,
+            Weight::Bdef1(Bdef1 { bone: Some(1) })
+        ));
+    }
+
+    #[test]
+    fn winding() {
+        fn normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ]
+        }
+        let pmx = import_sample();
+        let p = |i: usize| pmx.vertices[pmx.faces[i] as usize].position;
+        let unmirror = |p: [f32; 3]| [p[0], p[1], -p[2]];
+        // glTF lists the triangle as (0, 2, 1) of the imported one
+        let n = normal(unmirror(p(0)), unmirror(p(2)), unmirror(p(1)));
+        let imported = normal(p(0), p(1), p(2));
+        let dot = imported[0] * n[0] + imported[1] * n[1] - imported[2] * n[2];
+        assert!(dot > 0.0);
+    }
+
+    #[test]
+    fn joint_outside_scene() {
+        let options = ImportOptions {
+            scene: Some(1),
+            ..Default::default()
+        };
+        let result = import_slice(&sample_glb(), None, &options);
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn morphs_and_materials() {
+        let pmx = import_sample();
+        assert!(pmx.morphs.len() == 1);
+        assert!(pmx.morphs[0].name == "smile");
+        match &pmx.morphs[0].kind {
+            morph::Kind::Vertex(v) => {
+                assert!(v.len() == 1);
+                assert!(v[0].vertex == Some(2));
+                assert!(v[0].offset == [0.0, 0.0, -1.25]);
+            }
+            _ => panic!("expected a vertex morph"),
+        }
+        assert!(pmx.faces == [0, 2, 1]);
+        assert!(pmx.materials.len() == 1);
+        assert!(pmx.materials[0].index_count == 3);
+        assert!(pmx.materials[0].both);
+        assert!(pmx.materials[0].texture == Some(0));
+        assert!(pmx.textures[0] == std::path::Path::new("tex/skin a.png"));
+    }
+
+    #[test]
+    fn indices_out_of_range() {
+        let glb = sample_glb_with(&[0, 1, 3], |json| {
+            json.replace(r#""material": 0"#, r#""material": 0, "indices": 4"#)
+                .replace(
+                    r#"{"buffer": 0, "byteOffset": 96, "byteLength": 36}"#,
+                    r#"{"buffer": 0, "byteOffset": 96, "byteLength": 36},
+                    {"buffer": 0, "byteOffset": 132, "byteLength": 3}"#,
+                )
+                .replace(
+                    r#""max": [0, 0, 0.1]}"#,
+                    r#""max": [0, 0, 0.1]},
+                    {"bufferView": 4, "componentType": 5121, "count": 3, "type": "SCALAR"}"#,
+                )
+        });
+        let result = import_slice(&glb, None, &ImportOptions::default());
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn embedded_images() {
+        // "png" in base64
+        let glb = sample_glb_with(&[], |json| {
+            json.replace("tex/skin%20a.png", "data:image/png;base64,cG5n")
+        });
+        let result = import_slice(&glb, None, &ImportOptions::default());
+        assert!(matches!(result, Err(Error::EmbeddedImage(0))));
+
+        let options = ImportOptions {
+            embedded_images: EmbeddedImages::Ignore,
+            ..Default::default()
+        };
+        let pmx = import_slice(&glb, None, &options).unwrap();
+        assert!(pmx.materials[0].texture.is_none());
+
+        let dir = std::env::temp_dir().join(format!("pmx_rs_gltf_{}", std::process::id()));
+        let options = ImportOptions {
+            embedded_images: EmbeddedImages::Write(dir.clone()),
+            ..Default::default()
+        };
+        let pmx = import_slice(&glb, None, &options).unwrap();
+        assert!(pmx.textures == [PathBuf::from("image0.png")]);
+        assert!(std::fs::read(dir.join("image0.png")).unwrap() == b"png");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
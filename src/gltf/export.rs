@@ -0,0 +1,596 @@
+//! Export of skinned glTF 2.0 models, the reverse of [`import`](super::import).
+//!
+//! Positions are mirrored along Z and divided by [`ExportOptions::scale`], and
+//! every triangle is written as `i0 i2 i1`. Each material becomes a primitive
+//! holding a copy of the vertices it uses. Bones become the joints of one skin
+//! and vertex morphs become morph targets named by the `targetNames` extra.
+//! Other morphs, rigid bodies and joints have no glTF counterpart and are left
+//! out, and SDEF weights are exported as linear blends.
+
+use super::{percent_encode, Error};
+use crate::math::{self, Vec3};
+use crate::*;
+use ::gltf::json;
+use json::accessor::{ComponentType, GenericComponentType, Type};
+use json::buffer::Target;
+use json::mesh::{Mode, Semantic};
+use json::validation::{Checked::Valid, USize64};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions<'a> {
+    /// Factor every position and morph offset is divided by.
+    pub scale: f32,
+    /// Directory of the model, used to resolve every texture path.
+    pub model_dir: Option<&'a Path>,
+}
+
+impl Default for ExportOptions<'_> {
+    fn default() -> Self {
+        Self {
+            scale: 12.5,
+            model_dir: None,
+        }
+    }
+}
+
+/// Writes a `.glb` file, or a `.gltf` file with its buffer in a `.bin` file of the same stem.
+pub fn export(pmx: &Pmx, path: impl AsRef<Path>, options: &ExportOptions) -> Result<(), Error> {
+    let path = path.as_ref();
+    let (mut root, bin) = Exporter::new(pmx, options).export()?;
+    let glb = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    if glb {
+        let json = json::serialize::to_vec(&root).map_err(std::io::Error::from)?;
+        let glb = ::gltf::Glb {
+            // the length is computed by `to_writer`
+            header: ::gltf::binary::Header {
+                magic: *b"glTF",
+                version: 2,
+                length: 0,
+            },
+            json: Cow::Owned(json),
+            bin: (!bin.is_empty()).then_some(Cow::Owned(bin)),
+        };
+        glb.to_writer(&mut file)?;
+    } else {
+        if let Some(buffer) = root.buffers.first_mut() {
+            let bin_path = path.with_extension("bin");
+            let name = bin_path.file_name().unwrap_or_default().to_string_lossy();
+            buffer.uri = Some(percent_encode(&name));
+            std::fs::write(&bin_path, bin)?;
+        }
+        json::serialize::to_writer_pretty(&mut file, &root).map_err(std::io::Error::from)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+struct Exporter<'a> {
+    pmx: &'a Pmx,
+    options: &'a ExportOptions<'a>,
+    root: json::Root,
+    bin: Vec<u8>,
+    textures: Vec<Option<json::Index<json::Texture>>>,
+}
+
+impl<'a> Exporter<'a> {
+    fn new(pmx: &'a Pmx, options: &'a ExportOptions<'a>) -> Self {
+        Self {
+            pmx,
+            options,
+            root: json::Root::default(),
+            bin: vec![],
+            textures: vec![None; pmx.textures.len()],
+        }
+    }
+
+    fn export(mut self) -> Result<(json::Root, Vec<u8>), Error> {
+        self.root.asset = json::Asset {
+            copyright: None,
+            extensions: None,
+            extras: Default::default(),
+            generator: Some("pmx_rs".into()),
+            min_version: None,
+            version: "2.0".into(),
+        };
+        let mut nodes = self.bones()?;
+        let skin = self.skin();
+        if let Some(mesh) = self.mesh()? {
+            nodes.push(self.root.push(json::Node {
+                name: Some(self.pmx.model_info.name.clone()),
+                mesh: Some(mesh),
+                skin,
+                ..Default::default()
+            }));
+        }
+        let scene = self.root.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: Some(self.pmx.model_info.name.clone()),
+            nodes,
+        });
+        self.root.scene = Some(scene);
+        if !self.bin.is_empty() {
+            self.root.push(json::Buffer {
+                byte_length: USize64::from(self.bin.len()),
+                name: None,
+                uri: None,
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        Ok((self.root, self.bin))
+    }
+
+    fn convert_position(&self, p: Vec3) -> Vec3 {
+        let s = self.options.scale;
+        [p[0] / s, p[1] / s, -p[2] / s]
+    }
+
+    /// Adds a node for every bone at the same index, returning the nodes without a parent.
+    fn bones(&mut self) -> Result<Vec<json::Index<json::Node>>, Error> {
+        let bones = &self.pmx.bones;
+        let mut children = vec![vec![]; bones.len()];
+        let mut roots = vec![];
+        for (i, bone) in bones.iter().enumerate() {
+            match bone.parent {
+                Some(parent) if parent >= bones.len() => {
+                    return Err(Error::InvalidData(format!(
+                        "bone {} has parent {} out of range",
+                        i, parent
+                    )))
+                }
+                Some(parent) => children[parent].push(i),
+                None => roots.push(i),
+            }
+        }
+        // bones on a cycle of parents cannot be reached from any root
+        let mut reached = 0;
+        let mut stack = roots.clone();
+        while let Some(i) = stack.pop() {
+            reached += 1;
+            stack.extend_from_slice(&children[i]);
+        }
+        if reached < bones.len() {
+            return Err(Error::InvalidData("bone parents form a cycle".into()));
+        }
+        for (bone, children) in bones.iter().zip(children) {
+            let parent = bone.parent.map_or([0.0; 3], |p| bones[p].position);
+            let children = children
+                .into_iter()
+                .map(|i| json::Index::new(i as u32))
+                .collect::<Vec<_>>();
+            let node = json::Node {
+                name: Some(bone.name.clone()),
+                translation: Some(self.convert_position(math::sub(bone.position, parent))),
+                children: (!children.is_empty()).then_some(children),
+                ..Default::default()
+            };
+            self.root.push(node);
+        }
+        Ok(roots
+            .into_iter()
+            .map(|i| json::Index::new(i as u32))
+            .collect())
+    }
+
+    fn skin(&mut self) -> Option<json::Index<json::Skin>> {
+        if self.pmx.bones.is_empty() {
+            return None;
+        }
+        let mut matrices = vec![];
+        for bone in self.pmx.bones.iter() {
+            let p = self.convert_position(bone.position);
+            matrices.extend_from_slice(&[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+            matrices.extend_from_slice(&[0.0, 0.0, 1.0, 0.0, -p[0], -p[1], -p[2], 1.0]);
+        }
+        let inverse_bind_matrices = self.accessor(
+            f32_bytes(matrices),
+            self.pmx.bones.len(),
+            ComponentType::F32,
+            Type::Mat4,
+            None,
+        );
+        Some(
+            self.root.push(json::Skin {
+                extensions: None,
+                extras: Default::default(),
+                inverse_bind_matrices: Some(inverse_bind_matrices),
+                joints: (0..self.pmx.bones.len())
+                    .map(|i| json::Index::new(i as u32))
+                    .collect(),
+                name: None,
+                skeleton: None,
+            }),
+        )
+    }
+
+    fn mesh(&mut self) -> Result<Option<json::Index<json::Mesh>>, Error> {
+        let pmx = self.pmx;
+        let morphs = pmx
+            .morphs
+            .iter()
+            .filter_map(|morph| match &morph.kind {
+                morph::Kind::Vertex(offsets) => Some((&morph.name, offsets)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut primitives = vec![];
+        let mut start = 0;
+        for (i, material) in pmx.materials.iter().enumerate() {
+            let end = start + material.index_count as usize;
+            let faces = pmx.faces.get(start..end).ok_or_else(|| {
+                Error::InvalidData(format!("material {} indices beyond the faces", i))
+            })?;
+            start = end;
+            let material = self.material(material)?;
+            if faces.len() >= 3 {
+                primitives.push(self.primitive(faces, material, &morphs)?);
+            }
+        }
+        if primitives.is_empty() {
+            return Ok(None);
+        }
+        let names = morphs.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        let extras = match names.is_empty() {
+            true => None,
+            false => {
+                let extras = format!(
+                    r#"{{"targetNames":{}}}"#,
+                    json::serialize::to_string(&names).map_err(std::io::Error::from)?
+                );
+                Some(json::extras::RawValue::from_string(extras).map_err(std::io::Error::from)?)
+            }
+        };
+        Ok(Some(self.root.push(json::Mesh {
+            extensions: None,
+            extras,
+            name: Some(pmx.model_info.name.clone()),
+            primitives,
+            weights: (!names.is_empty()).then(|| vec![0.0; names.len()]),
+        })))
+    }
+
+    /// Builds a primitive from `faces` with its own copy of the vertices they use.
+    fn primitive(
+        &mut self,
+        faces: &[u32],
+        material: json::Index<json::Material>,
+        morphs: &[(&String, &Vec<morph::Vertex>)],
+    ) -> Result<json::mesh::Primitive, Error> {
+        let pmx = self.pmx;
+        let mut local = HashMap::new();
+        let mut used = vec![];
+        let indices = faces
+            .chunks_exact(3)
+            .flat_map(|f| [f[0], f[2], f[1]])
+            .map(|v| match (v as usize) < pmx.vertices.len() {
+                true => Ok(*local.entry(v as usize).or_insert_with(|| {
+                    used.push(v as usize);
+                    used.len() as u32 - 1
+                })),
+                false => Err(Error::InvalidData(format!(
+                    "index {} out of range for {} vertices",
+                    v,
+                    pmx.vertices.len()
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let vertices = used.iter().map(|&v| &pmx.vertices[v]).collect::<Vec<_>>();
+
+        let mut attributes = BTreeMap::new();
+        let positions = vertices
+            .iter()
+            .map(|v| self.convert_position(v.position))
+            .collect::<Vec<_>>();
+        attributes.insert(Semantic::Positions, self.positions(&positions));
+        let normals = vertices
+            .iter()
+            .flat_map(|v| math::normalize([v.normal[0], v.normal[1], -v.normal[2]]))
+            .collect();
+        let normals = self.accessor(
+            f32_bytes(normals),
+            used.len(),
+            ComponentType::F32,
+            Type::Vec3,
+            Some(Target::ArrayBuffer),
+        );
+        attributes.insert(Semantic::Normals, normals);
+        let uvs = vertices.iter().flat_map(|v| v.uv).collect();
+        let uvs = self.accessor(
+            f32_bytes(uvs),
+            used.len(),
+            ComponentType::F32,
+            Type::Vec2,
+            Some(Target::ArrayBuffer),
+        );
+        attributes.insert(Semantic::TexCoords(0), uvs);
+        if !pmx.bones.is_empty() {
+            let (joints, weights) = self.skin_attributes(&vertices)?;
+            attributes.insert(Semantic::Joints(0), joints);
+            attributes.insert(Semantic::Weights(0), weights);
+        }
+
+        let mut targets = vec![];
+        let mut still = None;
+        for (name, offsets) in morphs {
+            let mut displacements = vec![[0.0; 3]; used.len()];
+            let mut moved = false;
+            for offset in offsets.iter() {
+                let Some(v) = offset.vertex else {
+                    continue;
+                };
+                if v >= pmx.vertices.len() {
+                    return Err(Error::InvalidData(format!(
+                        "morph {} moves vertex {} out of range",
+                        name, v
+                    )));
+                }
+                if let Some(&i) = local.get(&v) {
+                    let d = self.convert_position(offset.offset);
+                    displacements[i as usize] = math::add(displacements[i as usize], d);
+                    moved = true;
+                }
+            }
+            // the targets that leave every vertex in place share one accessor
+            let positions = match moved {
+                true => self.positions(&displacements),
+                false => *still.get_or_insert_with(|| self.positions(&displacements)),
+            };
+            targets.push(json::mesh::MorphTarget {
+                positions: Some(positions),
+                normals: None,
+                tangents: None,
+            });
+        }
+
+        let indices = self.accessor(
+            indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            indices.len(),
+            ComponentType::U32,
+            Type::Scalar,
+            Some(Target::ElementArrayBuffer),
+        );
+        Ok(json::mesh::Primitive {
+            attributes: attributes.into_iter().map(|(k, v)| (Valid(k), v)).collect(),
+            extensions: None,
+            extras: Default::default(),
+            indices: Some(indices),
+            material: Some(material),
+            mode: Valid(Mode::Triangles),
+            targets: (!targets.is_empty()).then_some(targets),
+        })
+    }
+
+    /// Builds `JOINTS_0` and `WEIGHTS_0` from the four strongest influences of every vertex.
+    fn skin_attributes(
+        &mut self,
+        vertices: &[&Vertex],
+    ) -> Result<(json::Index<json::Accessor>, json::Index<json::Accessor>), Error> {
+        let bones = self.pmx.bones.len();
+        let mut joints = vec![];
+        let mut weights = vec![];
+        for vertex in vertices {
+            let mut influences = weight::influences(&vertex.weight);
+            if let Some(&(bone, _)) = influences.iter().find(|(b, _)| *b >= bones) {
+                return Err(Error::InvalidData(format!("bone {} out of range", bone)));
+            }
+            influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+            influences.truncate(4);
+            if influences.is_empty() {
+                influences.push((0, 1.0));
+            }
+            let total = influences.iter().map(|(_, w)| w).sum::<f32>();
+            for i in 0..4 {
+                let (bone, w) = influences.get(i).copied().unwrap_or((0, 0.0));
+                let bone = u16::try_from(bone)
+                    .map_err(|_| Error::InvalidData(format!("bone {} is not a u16", bone)))?;
+                joints.extend_from_slice(&bone.to_le_bytes());
+                weights.push(w / total);
+            }
+        }
+        let joints = self.accessor(
+            joints,
+            vertices.len(),
+            ComponentType::U16,
+            Type::Vec4,
+            Some(Target::ArrayBuffer),
+        );
+        let weights = self.accessor(
+            f32_bytes(weights),
+            vertices.len(),
+            ComponentType::F32,
+            Type::Vec4,
+            Some(Target::ArrayBuffer),
+        );
+        Ok((joints, weights))
+    }
+
+    fn material(&mut self, material: &Material) -> Result<json::Index<json::Material>, Error> {
+        let texture = material
+            .texture
+            .map(|i| self.texture(i))
+            .transpose()?
+            .map(|index| json::texture::Info {
+                index,
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            });
+        let alpha_mode = match material.diffuse[3] < 1.0 {
+            true => json::material::AlphaMode::Blend,
+            false => json::material::AlphaMode::Opaque,
+        };
+        Ok(self.root.push(json::Material {
+            name: Some(material.name.clone()),
+            alpha_mode: Valid(alpha_mode),
+            double_sided: material.both,
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(material.diffuse),
+                base_color_texture: texture,
+                metallic_factor: json::material::StrengthFactor(0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        }))
+    }
+
+    fn texture(&mut self, index: usize) -> Result<json::Index<json::Texture>, Error> {
+        let path = self
+            .pmx
+            .textures
+            .get(index)
+            .ok_or_else(|| Error::InvalidData(format!("texture {} out of range", index)))?;
+        if let Some(texture) = self.textures[index] {
+            return Ok(texture);
+        }
+        let uri = obj::texture_path(path, self.options.model_dir);
+        let image = self.root.push(json::Image {
+            buffer_view: None,
+            mime_type: None,
+            name: None,
+            uri: Some(percent_encode(&uri)),
+            extensions: None,
+            extras: Default::default(),
+        });
+        let texture = self.root.push(json::Texture {
+            name: None,
+            sampler: None,
+            source: image,
+            extensions: None,
+            extras: Default::default(),
+        });
+        self.textures[index] = Some(texture);
+        Ok(texture)
+    }
+
+    /// Adds a `VEC3` position accessor with the bounds glTF requires.
+    fn positions(&mut self, values: &[Vec3]) -> json::Index<json::Accessor> {
+        let (min, max) = values
+            .iter()
+            .fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), p| {
+                (
+                    [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+                    [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+                )
+            });
+        let index = self.accessor(
+            f32_bytes(values.iter().flatten().copied().collect()),
+            values.len(),
+            ComponentType::F32,
+            Type::Vec3,
+            Some(Target::ArrayBuffer),
+        );
+        let accessor = &mut self.root.accessors[index.value()];
+        accessor.min = Some(json::Value::from(min.to_vec()));
+        accessor.max = Some(json::Value::from(max.to_vec()));
+        index
+    }
+
+    /// Adds an accessor over `data` in a new buffer view.
+    fn accessor(
+        &mut self,
+        data: Vec<u8>,
+        count: usize,
+        component_type: ComponentType,
+        type_: Type,
+        target: Option<Target>,
+    ) -> json::Index<json::Accessor> {
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+        let view = self.root.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: USize64::from(data.len()),
+            byte_offset: Some(USize64::from(self.bin.len())),
+            byte_stride: None,
+            name: None,
+            target: target.map(Valid),
+            extensions: None,
+            extras: Default::default(),
+        });
+        self.bin.extend_from_slice(&data);
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: USize64::from(count),
+            component_type: Valid(GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: None,
+            max: None,
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+}
+
+fn f32_bytes(values: Vec<f32>) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gltf::{import, ImportOptions};
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn assert_round_trip(pmx: &Pmx, imported: &Pmx) {
+        assert!(imported.faces.len() == pmx.faces.len());
+        for (&a, &b) in pmx.faces.iter().zip(imported.faces.iter()) {
+            let a = pmx.vertices[a as usize].position;
+            let b = imported.vertices[b as usize].position;
+            assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-4));
+        }
+        assert!(imported.bones.len() == pmx.bones.len());
+        assert!(imported.materials.len() == pmx.materials.len());
+        for (a, b) in pmx.materials.iter().zip(imported.materials.iter()) {
+            assert!(a.name == b.name && a.index_count == b.index_count && a.both == b.both);
+            let texture = |pmx: &Pmx, m: &Material| m.texture.map(|i| pmx.textures[i].clone());
+            let expected = texture(pmx, a).map(|p| p.to_string_lossy().replace('\\', "/"));
+            assert!(expected.as_deref() == texture(imported, b).as_deref().and_then(Path::to_str));
+        }
+        let vertex_morphs = pmx
+            .morphs
+            .iter()
+            .filter(|m| matches!(m.kind, morph::Kind::Vertex(_)))
+            .map(|m| &m.name);
+        assert!(vertex_morphs.eq(imported.morphs.iter().map(|m| &m.name)));
+    }
+
+    #[test]
+    fn round_trip() {
+        let pmx = read_pmx();
+        let dir = std::env::temp_dir().join(format!("pmx_rs_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["Alicia.glb", "Alicia.gltf"] {
+            export(&pmx, dir.join(name), &ExportOptions::default()).unwrap();
+            let imported = import(dir.join(name), &ImportOptions::default()).unwrap();
+            assert_round_trip(&pmx, &imported);
+        }
+        assert!(dir.join("Alicia.bin").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bone_cycle() {
+        let mut pmx = read_pmx();
+        let (root, child) = (pmx.bones.len() - 2, pmx.bones.len() - 1);
+        pmx.bones[root].parent = Some(child);
+        pmx.bones[child].parent = Some(root);
+        let result = Exporter::new(&pmx, &ExportOptions::default()).export();
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+    }
+}
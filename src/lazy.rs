@@ -0,0 +1,269 @@
+//! Selective loading of sections from seekable sources.
+//!
+//! [`LazyReader::new`] reads the header and walks the file once, skipping over
+//! every element to record where each section starts. Sections can then be
+//! loaded in any order, and the ones that are never asked for are never decoded.
+
+use crate::reader::{Error, Reader, Seeker, Skip};
+use crate::*;
+use std::io::{Read, Seek, SeekFrom};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SectionKind {
+    ModelInfo,
+    Vertices,
+    Faces,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    DisplayGroups,
+    Rigids,
+    Joints,
+}
+
+impl SectionKind {
+    /// Returns every section in file order.
+    pub fn all() -> [SectionKind; 10] {
+        [
+            Self::ModelInfo,
+            Self::Vertices,
+            Self::Faces,
+            Self::Textures,
+            Self::Materials,
+            Self::Bones,
+            Self::Morphs,
+            Self::DisplayGroups,
+            Self::Rigids,
+            Self::Joints,
+        ]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SectionInfo {
+    /// Position of the section relative to the start of the file.
+    pub offset: u64,
+    /// Size of the section in bytes, including its element count.
+    pub size: u64,
+    /// Number of elements, which is 1 for `ModelInfo` and the number of indices for `Faces`.
+    pub len: usize,
+}
+
+pub struct LazyReader<R> {
+    reader: Reader<Seeker<R>>,
+    header: Header,
+    start: u64,
+    sections: [SectionInfo; 10],
+}
+
+impl<R> LazyReader<R>
+where
+    R: Read + Seek,
+{
+    /// Reads the header at the current position and records the location of every section.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut reader = Reader::new(Seeker(reader));
+        let start = reader.get_mut().0.stream_position()?;
+        let header = reader.header()?;
+        reader.set_header(&header);
+        let mut sections = [SectionInfo {
+            offset: 0,
+            size: 0,
+            len: 0,
+        }; 10];
+        for (kind, info) in SectionKind::all().into_iter().zip(sections.iter_mut()) {
+            let offset = reader.get_mut().0.stream_position()?;
+            let len = match kind {
+                SectionKind::ModelInfo => {
+                    reader.skip_model_info()?;
+                    1
+                }
+                SectionKind::Faces => {
+                    let len = reader.read_u32()?;
+                    reader.skip_faces(len)?;
+                    len
+                }
+                _ => {
+                    let len = reader.read_u32()?;
+                    reader.skip_elements(len, skipper(kind))?;
+                    len
+                }
+            };
+            let end = reader.get_mut().0.stream_position()?;
+            *info = SectionInfo {
+                offset: offset - start,
+                size: end - offset,
+                len: len as usize,
+            };
+        }
+        // long skips seek, which does not fail at the end of the file
+        let end = reader.get_mut().0.stream_position()?;
+        if reader.get_mut().0.seek(SeekFrom::End(0))? < end {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(Self {
+            reader,
+            header,
+            start,
+            sections,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    #[inline]
+    pub fn section(&self, kind: SectionKind) -> SectionInfo {
+        let i = SectionKind::all().iter().position(|k| *k == kind).unwrap();
+        self.sections[i]
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner().0
+    }
+
+    fn seek(&mut self, kind: SectionKind) -> Result<&mut Reader<Seeker<R>>, Error> {
+        let offset = self.start + self.section(kind).offset;
+        self.reader.get_mut().0.seek(SeekFrom::Start(offset))?;
+        Ok(&mut self.reader)
+    }
+
+    pub fn model_info(&mut self) -> Result<ModelInfo, Error> {
+        self.seek(SectionKind::ModelInfo)?.model_info()
+    }
+
+    pub fn vertices(&mut self) -> Result<Vec<Vertex>, Error> {
+        self.seek(SectionKind::Vertices)?.vertices()
+    }
+
+    pub fn faces(&mut self) -> Result<Vec<u32>, Error> {
+        self.seek(SectionKind::Faces)?.faces()
+    }
+
+    pub fn textures(&mut self) -> Result<Vec<PathBuf>, Error> {
+        self.seek(SectionKind::Textures)?.textures()
+    }
+
+    pub fn materials(&mut self) -> Result<Vec<Material>, Error> {
+        self.seek(SectionKind::Materials)?.materials()
+    }
+
+    pub fn bones(&mut self) -> Result<Vec<Bone>, Error> {
+        self.seek(SectionKind::Bones)?.bones()
+    }
+
+    pub fn morphs(&mut self) -> Result<Vec<Morph>, Error> {
+        self.seek(SectionKind::Morphs)?.morphs()
+    }
+
+    pub fn display_groups(&mut self) -> Result<Vec<DisplayGroup>, Error> {
+        self.seek(SectionKind::DisplayGroups)?.display_groups()
+    }
+
+    pub fn rigids(&mut self) -> Result<Vec<Rigid>, Error> {
+        self.seek(SectionKind::Rigids)?.rigids()
+    }
+
+    pub fn joints(&mut self) -> Result<Vec<Joint>, Error> {
+        self.seek(SectionKind::Joints)?.joints()
+    }
+
+    /// Loads every section, leaving the ones in `skip` empty.
+    pub fn load(&mut self, skip: &[SectionKind]) -> Result<Pmx, Error> {
+        let load = |kind| !skip.contains(&kind);
+        Ok(Pmx {
+            header: self.header,
+            model_info: if load(SectionKind::ModelInfo) {
+                self.model_info()?
+            } else {
+                ModelInfo::default()
+            },
+            vertices: load_if(load(SectionKind::Vertices), || self.vertices())?,
+            faces: load_if(load(SectionKind::Faces), || self.faces())?,
+            textures: load_if(load(SectionKind::Textures), || self.textures())?,
+            materials: load_if(load(SectionKind::Materials), || self.materials())?,
+            bones: load_if(load(SectionKind::Bones), || self.bones())?,
+            morphs: load_if(load(SectionKind::Morphs), || self.morphs())?,
+            display_groups: load_if(load(SectionKind::DisplayGroups), || self.display_groups())?,
+            rigids: load_if(load(SectionKind::Rigids), || self.rigids())?,
+            joints: load_if(load(SectionKind::Joints), || self.joints())?,
+        })
+    }
+}
+
+/// Returns the function skipping one element of `kind`, which must not be `ModelInfo` or `Faces`.
+pub(crate) fn skipper<T: Skip>(kind: SectionKind) -> fn(&mut Reader<T>) -> Result<(), Error> {
+    match kind {
+        SectionKind::Vertices => Reader::skip_vertex,
+        SectionKind::Textures => Reader::skip_texture,
+        SectionKind::Materials => Reader::skip_material,
+        SectionKind::Bones => Reader::skip_bone,
+        SectionKind::Morphs => Reader::skip_morph,
+        SectionKind::DisplayGroups => Reader::skip_display_group,
+        SectionKind::Rigids => Reader::skip_rigid,
+        SectionKind::Joints => Reader::skip_joint,
+        SectionKind::ModelInfo | SectionKind::Faces => unreachable!(),
+    }
+}
+
+fn load_if<T>(load: bool, f: impl FnOnce() -> Result<Vec<T>, Error>) -> Result<Vec<T>, Error> {
+    if load {
+        f()
+    } else {
+        Ok(vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> LazyReader<std::io::BufReader<std::fs::File>> {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        LazyReader::new(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn alicia() {
+        let data = std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap();
+        let pmx = crate::read(data.as_slice()).unwrap();
+        let mut reader = open();
+        assert!(*reader.header() == pmx.header);
+        assert!(reader.section(SectionKind::Vertices).len == pmx.vertices.len());
+        assert!(reader.section(SectionKind::Faces).len == pmx.faces.len());
+        let joints = reader.section(SectionKind::Joints);
+        assert!(joints.offset + joints.size == data.len() as u64);
+
+        // out of order
+        assert!(reader.bones().unwrap() == pmx.bones);
+        assert!(reader.model_info().unwrap() == pmx.model_info);
+        assert!(reader.joints().unwrap() == pmx.joints);
+        assert!(reader.vertices().unwrap() == pmx.vertices);
+        assert!(reader.load(&[]).unwrap() == pmx);
+
+        let skipped = reader
+            .load(&[SectionKind::Vertices, SectionKind::Faces])
+            .unwrap();
+        assert!(skipped.vertices.is_empty() && skipped.faces.is_empty());
+        assert!(skipped.morphs == pmx.morphs);
+    }
+
+    #[test]
+    fn offset_start() {
+        let mut data = vec![0xff; 10];
+        data.extend(std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap());
+        let mut cursor = std::io::Cursor::new(&data);
+        cursor.set_position(10);
+        let mut reader = LazyReader::new(cursor).unwrap();
+        assert!(reader.section(SectionKind::ModelInfo).offset == 17);
+        assert!(reader.model_info().unwrap().name_en == "Alicia Solid");
+
+        data.pop();
+        let mut cursor = std::io::Cursor::new(&data);
+        cursor.set_position(10);
+        assert!(LazyReader::new(cursor).is_err());
+    }
+}
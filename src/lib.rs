@@ -1,15 +1,44 @@
+pub mod borrowed;
+pub mod diff;
+pub mod edit;
+pub mod extract;
+pub mod geometry;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod lazy;
+pub mod lint;
+mod math;
+pub mod merge;
+pub mod mirror;
+pub mod obj;
+pub mod optimize;
+pub mod pmd;
 mod reader;
+pub mod semi_standard;
+pub mod standard;
+pub mod stream;
+pub mod texture;
+pub mod translate;
+pub mod validate;
+pub mod weight;
+mod writer;
 
 use std::path::PathBuf;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum Encoding {
     Utf16 = 0,
     Utf8 = 1,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub version: f32,
     pub encoding: Encoding,
@@ -22,7 +51,8 @@ pub struct Header {
     pub rigid_index_size: u8,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModelInfo {
     pub name: String,
     pub name_en: String,
@@ -30,24 +60,28 @@ pub struct ModelInfo {
     pub comment_en: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bdef1 {
     pub bone: Option<usize>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bdef2 {
     pub bones: [Option<usize>; 2],
     pub weight: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bdef4 {
     pub bones: [Option<usize>; 4],
     pub weights: [f32; 4],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sdef {
     pub bones: [Option<usize>; 2],
     pub weight: f32,
@@ -56,7 +90,12 @@ pub struct Sdef {
     pub r1: [f32; 3],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
 pub enum Weight {
     Bdef1(Bdef1),
     Bdef2(Bdef2),
@@ -64,7 +103,8 @@ pub enum Weight {
     Sdef(Sdef),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
@@ -74,7 +114,12 @@ pub struct Vertex {
     pub edge_ratio: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum SphereMode {
     None,
     Mul,
@@ -82,13 +127,19 @@ pub enum SphereMode {
     SubTexture,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Toon {
     Texture(Option<usize>),
     Shared(u32),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     pub name: String,
     pub name_en: String,
@@ -111,25 +162,33 @@ pub struct Material {
     pub index_count: u32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ConnectedTo {
     Offset([f32; 3]),
     Bone(Option<usize>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AngleLimit {
     pub lower: [f32; 3],
     pub upper: [f32; 3],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IkLink {
     pub bone: Option<usize>,
     pub limits: Option<AngleLimit>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ik {
     pub bone: Option<usize>,
     pub loop_count: u32,
@@ -137,7 +196,8 @@ pub struct Ik {
     pub links: Vec<IkLink>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Addition {
     pub rotation: bool,
     pub translation: bool,
@@ -146,13 +206,15 @@ pub struct Addition {
     pub ratio: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalPole {
     pub x: [f32; 3],
     pub z: [f32; 3],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bone {
     pub name: String,
     pub name_en: String,
@@ -172,7 +234,12 @@ pub struct Bone {
     pub external_parent: Option<usize>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Panel {
     Reserved,
     Eyebrow,
@@ -182,19 +249,22 @@ pub enum Panel {
 }
 
 pub mod morph {
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Vertex {
         pub vertex: Option<usize>,
         pub offset: [f32; 3],
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Uv {
         pub vertex: Option<usize>,
         pub offset: [f32; 4],
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Bone {
         pub bone: Option<usize>,
         pub offset: [f32; 3],
@@ -202,12 +272,18 @@ pub mod morph {
     }
 
     #[derive(Clone, PartialEq, Eq, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(rename_all = "snake_case")
+    )]
     pub enum MaterialOp {
         Mul,
         Add,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Material {
         pub material: Option<usize>,
         pub op: MaterialOp,
@@ -222,24 +298,32 @@ pub mod morph {
         pub toon: [f32; 4],
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Group {
         pub morph: Option<usize>,
         pub ratio: f32,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(tag = "type", content = "offsets", rename_all = "snake_case")
+    )]
     pub enum Kind {
         Vertex(Vec<Vertex>),
         Uv(Vec<Uv>),
         Bone(Vec<Bone>),
+        #[cfg_attr(feature = "serde", serde(rename = "material"))]
         Maerial(Vec<Material>),
         Group(Vec<Group>),
         ExtendedUv(usize, Vec<Uv>),
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Morph {
     pub name: String,
     pub name_en: String,
@@ -247,13 +331,19 @@ pub struct Morph {
     pub kind: morph::Kind,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum DisplayElement {
     Bone(Option<usize>),
     Morph(Option<usize>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DisplayGroup {
     pub name: String,
     pub name_en: String,
@@ -262,14 +352,24 @@ pub struct DisplayGroup {
 }
 
 pub mod rigid {
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(rename_all = "snake_case")
+    )]
     pub enum Shape {
         Sphere,
         Box,
         Capsule,
     }
 
-    #[derive(Clone, Debug)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(rename_all = "snake_case")
+    )]
     pub enum Method {
         Static,
         Dynamic,
@@ -277,7 +377,8 @@ pub mod rigid {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rigid {
     pub name: String,
     pub name_en: String,
@@ -296,7 +397,8 @@ pub struct Rigid {
     pub method: rigid::Method,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Joint {
     pub name: String,
     pub name_en: String,
@@ -309,7 +411,8 @@ pub struct Joint {
     pub spring_rotation: [f32; 3],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pmx {
     pub header: Header,
     pub model_info: ModelInfo,
@@ -324,12 +427,115 @@ pub struct Pmx {
     pub joints: Vec<Joint>,
 }
 
+/// Returns the smallest index size able to address `len` elements.
+///
+/// Signed indices reserve -1 for "none", so their largest index is 127 or 32767.
+/// Unsigned vertex indices can use the whole range up to 255 or 65535.
+pub(crate) fn index_size(len: usize, signed: bool) -> u8 {
+    let max = len.saturating_sub(1);
+    let (one, two) = if signed {
+        (i8::MAX as usize, i16::MAX as usize)
+    } else {
+        (u8::MAX as usize, u16::MAX as usize)
+    };
+    if max <= one {
+        1
+    } else if max <= two {
+        2
+    } else {
+        4
+    }
+}
+
+/// Size in bytes of each kind of index.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexSizes {
+    pub vertex: u8,
+    pub texture: u8,
+    pub material: u8,
+    pub bone: u8,
+    pub morph: u8,
+    pub rigid: u8,
+}
+
+impl Header {
+    #[inline]
+    pub fn index_sizes(&self) -> IndexSizes {
+        IndexSizes {
+            vertex: self.vertex_index_size,
+            texture: self.texture_index_size,
+            material: self.material_index_size,
+            bone: self.bone_index_size,
+            morph: self.morph_index_size,
+            rigid: self.rigid_index_size,
+        }
+    }
+
+    #[inline]
+    pub fn set_index_sizes(&mut self, sizes: IndexSizes) {
+        self.vertex_index_size = sizes.vertex;
+        self.texture_index_size = sizes.texture;
+        self.material_index_size = sizes.material;
+        self.bone_index_size = sizes.bone;
+        self.morph_index_size = sizes.morph;
+        self.rigid_index_size = sizes.rigid;
+    }
+}
+
+impl Pmx {
+    /// Returns the smallest legal index sizes for the current contents.
+    pub fn minimal_index_sizes(&self) -> IndexSizes {
+        IndexSizes {
+            vertex: index_size(self.vertices.len(), false),
+            texture: index_size(self.textures.len(), true),
+            material: index_size(self.materials.len(), true),
+            bone: index_size(self.bones.len(), true),
+            morph: index_size(self.morphs.len(), true),
+            rigid: index_size(self.rigids.len(), true),
+        }
+    }
+
+    /// Sets the index sizes of the header to [`Pmx::minimal_index_sizes`].
+    #[inline]
+    pub fn update_index_sizes(&mut self) {
+        self.header.set_index_sizes(self.minimal_index_sizes());
+    }
+}
+
 #[inline]
 pub fn read<T: std::io::Read>(reader: T) -> Result<Pmx, reader::Error> {
     let mut reader = reader::Reader::new(reader);
     reader.read()
 }
 
+/// Writes a PMX file with the encoding, extended UV count and index sizes of `pmx.header`.
+///
+/// An index too large for its size is an error, so call [`Pmx::update_index_sizes`]
+/// first when elements were added. `writer` is not buffered.
+#[inline]
+pub fn write<T: std::io::Write>(writer: T, pmx: &Pmx) -> Result<(), reader::Error> {
+    let mut writer = writer::Writer::new(writer);
+    writer.write(pmx)
+}
+
+/// Reads a PMX file from an asynchronous source.
+///
+/// The whole input is buffered in memory and then decoded synchronously on the
+/// calling task, which takes milliseconds for large models. Run [`read`] inside
+/// `tokio::task::spawn_blocking` instead when that would stall the executor.
+#[cfg(feature = "tokio")]
+pub async fn read_async<T>(mut reader: T) -> Result<Pmx, reader::Error>
+where
+    T: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut data = vec![];
+    reader.read_to_end(&mut data).await?;
+    read(data.as_slice())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -387,4 +593,74 @@ mod tests {
         let pmx = read_pmx();
         assert!(pmx.joints[52].name == "リボン右");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn minimal_index_sizes() {
+        let mut pmx = read_pmx();
+        let sizes = pmx.minimal_index_sizes();
+        assert!(sizes.vertex == 2);
+        assert!(sizes.texture == 1);
+        assert!(sizes.bone == 2);
+        assert!(sizes.rigid == 1);
+        pmx.bones.truncate(128);
+        pmx.vertices.truncate(256);
+        let sizes = pmx.minimal_index_sizes();
+        assert!(sizes.bone == 1);
+        assert!(sizes.vertex == 1);
+        pmx.update_index_sizes();
+        assert!(pmx.header.index_sizes() == sizes);
+        assert!(index_size(129, true) == 2);
+        assert!(index_size(257, false) == 2);
+        assert!(index_size(65537, false) == 4);
+        assert!(index_size(0, true) == 1);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let data = std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap();
+        let pmx = read_pmx();
+        let mut written = vec![];
+        write(&mut written, &pmx).unwrap();
+        assert!(written == data);
+
+        let mut pmx = pmx;
+        pmx.header.encoding = Encoding::Utf8;
+        pmx.header.vertex_index_size = 4;
+        let mut written = vec![];
+        write(&mut written, &pmx).unwrap();
+        assert!(read(written.as_slice()).unwrap() == pmx);
+
+        pmx.header.bone_index_size = 1;
+        assert!(write(&mut vec![], &pmx).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn read_async() {
+        let file = tokio::fs::File::open("resource/Alicia/Alicia_solid.pmx")
+            .await
+            .unwrap();
+        let pmx = super::read_async(tokio::io::BufReader::new(file))
+            .await
+            .unwrap();
+        assert!(pmx == read_pmx());
+
+        let data = std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap();
+        assert!(super::read_async(&data[..data.len() - 1]).await.is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let pmx = read_pmx();
+        let json = serde_json::to_string(&pmx).unwrap();
+        assert!(serde_json::from_str::<Pmx>(&json).unwrap() == pmx);
+
+        let weight = serde_json::to_value(&pmx.vertices[0].weight).unwrap();
+        assert!(weight["type"] == "bdef2");
+        assert!(weight["bones"] == serde_json::json!([35, 47]));
+        let morph = serde_json::to_value(&pmx.morphs[0]).unwrap();
+        assert!(morph["kind"]["type"] == "vertex");
+        assert!(morph["panel"] == "mouth");
+    }
+}
@@ -0,0 +1,431 @@
+//! Warnings for authoring mistakes that still produce a valid file.
+//!
+//! Each [`Rule`] has a default [`Severity`] that can be overridden per rule,
+//! and rules set to [`Severity::Allow`] are not evaluated at all.
+
+use crate::math;
+use crate::weight::influences;
+use crate::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Rule {
+    /// A texture that no material uses.
+    UnusedTexture,
+    /// A triangle that repeats a vertex or has no area.
+    DegenerateFace,
+    /// A vertex that no triangle uses.
+    UnusedVertex,
+    /// A bone without weights, children or references.
+    UnusedBone,
+    /// A morph that does not change anything.
+    EmptyMorph,
+    /// A material with `index_count == 0`.
+    EmptyMaterial,
+    /// A rigid body that is attached to no bone.
+    UnattachedRigid,
+    /// A joint that connects a rigid body with itself.
+    SelfJoint,
+}
+
+const RULES: &[(Rule, &str, Severity)] = &[
+    (Rule::UnusedTexture, "unused-texture", Severity::Warning),
+    (Rule::DegenerateFace, "degenerate-face", Severity::Warning),
+    (Rule::UnusedVertex, "unused-vertex", Severity::Info),
+    (Rule::UnusedBone, "unused-bone", Severity::Info),
+    (Rule::EmptyMorph, "empty-morph", Severity::Warning),
+    (Rule::EmptyMaterial, "empty-material", Severity::Warning),
+    (Rule::UnattachedRigid, "unattached-rigid", Severity::Warning),
+    (Rule::SelfJoint, "self-joint", Severity::Error),
+];
+
+impl Rule {
+    pub fn all() -> impl Iterator<Item = Rule> {
+        RULES.iter().map(|(rule, _, _)| *rule)
+    }
+
+    /// Returns the name used in configuration, e.g. `unused-texture`.
+    pub fn name(self) -> &'static str {
+        RULES.iter().find(|(r, _, _)| *r == self).unwrap().1
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        RULES
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(r, _, _)| *r)
+    }
+
+    pub fn default_severity(self) -> Severity {
+        RULES.iter().find(|(r, _, _)| *r == self).unwrap().2
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Severity {
+    Allow,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Allow, Self::Info, Self::Warning, Self::Error]
+            .into_iter()
+            .find(|s| s.name() == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LintOptions {
+    /// Severities replacing [`Rule::default_severity`].
+    pub severities: HashMap<Rule, Severity>,
+    /// Triangles with a smaller area are reported as degenerate.
+    pub min_area: f32,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            severities: HashMap::new(),
+            min_area: 1e-10,
+        }
+    }
+}
+
+impl LintOptions {
+    #[inline]
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+
+    #[inline]
+    pub fn set(&mut self, rule: Rule, severity: Severity) -> &mut Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// Index of the offending element, whose kind depends on the rule.
+    pub index: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.severity.name(),
+            self.rule.name(),
+            self.message
+        )
+    }
+}
+
+impl Pmx {
+    /// Runs every rule that is not allowed, ordered by rule and then by index.
+    pub fn lint(&self, options: &LintOptions) -> Vec<Lint> {
+        let mut lints = vec![];
+        for rule in Rule::all() {
+            let severity = options.severity(rule);
+            if severity == Severity::Allow {
+                continue;
+            }
+            let found = match rule {
+                Rule::UnusedTexture => self.unused_textures(),
+                Rule::DegenerateFace => self.degenerate_faces(options.min_area),
+                Rule::UnusedVertex => self.unused_vertices(),
+                Rule::UnusedBone => self.unused_bones(),
+                Rule::EmptyMorph => self.empty_morphs(),
+                Rule::EmptyMaterial => self.empty_materials(),
+                Rule::UnattachedRigid => self.unattached_rigids(),
+                Rule::SelfJoint => self.self_joints(),
+            };
+            lints.extend(found.into_iter().map(|(index, message)| Lint {
+                rule,
+                severity,
+                index,
+                message,
+            }));
+        }
+        lints
+    }
+
+    fn unused_textures(&self) -> Vec<(usize, String)> {
+        let mut used = vec![false; self.textures.len()];
+        for m in self.materials.iter() {
+            let toon = match m.toon {
+                Toon::Texture(t) => t,
+                Toon::Shared(_) => None,
+            };
+            for t in [m.texture, m.sphere, toon].into_iter().flatten() {
+                if let Some(used) = used.get_mut(t) {
+                    *used = true;
+                }
+            }
+        }
+        used.iter()
+            .enumerate()
+            .filter(|(_, used)| !**used)
+            .map(|(i, _)| {
+                let path = self.textures[i].display();
+                (i, format!("texture {} `{}` is not used", i, path))
+            })
+            .collect()
+    }
+
+    fn degenerate_faces(&self, min_area: f32) -> Vec<(usize, String)> {
+        let mut found = vec![];
+        for (i, f) in self.faces.chunks_exact(3).enumerate() {
+            if f[0] == f[1] || f[1] == f[2] || f[2] == f[0] {
+                found.push((i, format!("triangle {} repeats a vertex", i)));
+                continue;
+            }
+            let p = |i: u32| self.vertices.get(i as usize).map(|v| v.position);
+            let (Some(p0), Some(p1), Some(p2)) = (p(f[0]), p(f[1]), p(f[2])) else {
+                continue;
+            };
+            let n = math::cross(math::sub(p1, p0), math::sub(p2, p0));
+            let area = math::length(n) / 2.0;
+            if area < min_area {
+                found.push((i, format!("triangle {} has zero area", i)));
+            }
+        }
+        found
+    }
+
+    fn unused_vertices(&self) -> Vec<(usize, String)> {
+        let mut used = vec![false; self.vertices.len()];
+        for &i in self.faces.iter() {
+            if let Some(used) = used.get_mut(i as usize) {
+                *used = true;
+            }
+        }
+        used.iter()
+            .enumerate()
+            .filter(|(_, used)| !**used)
+            .map(|(i, _)| (i, format!("vertex {} is not used by any triangle", i)))
+            .collect()
+    }
+
+    fn unused_bones(&self) -> Vec<(usize, String)> {
+        let mut used = vec![false; self.bones.len()];
+        let mut mark = |bone: Option<usize>| {
+            if let Some(used) = bone.and_then(|b| used.get_mut(b)) {
+                *used = true;
+            }
+        };
+        for v in self.vertices.iter() {
+            for (bone, _) in influences(&v.weight) {
+                mark(Some(bone));
+            }
+        }
+        for (i, b) in self.bones.iter().enumerate() {
+            mark(b.parent);
+            if let ConnectedTo::Bone(target) = b.connected_to {
+                mark(target);
+            }
+            if let Some(ik) = &b.ik {
+                // an IK bone is used through its target even if nothing refers to it
+                mark(Some(i));
+                mark(ik.bone);
+                ik.links.iter().for_each(|l| mark(l.bone));
+            }
+            if let Some(addition) = &b.addition {
+                mark(addition.bone);
+            }
+        }
+        for m in self.morphs.iter() {
+            if let morph::Kind::Bone(offsets) = &m.kind {
+                offsets.iter().for_each(|o| mark(o.bone));
+            }
+        }
+        for r in self.rigids.iter() {
+            mark(r.bone);
+        }
+        used.iter()
+            .enumerate()
+            .filter(|(_, used)| !**used)
+            .map(|(i, _)| {
+                let name = &self.bones[i].name;
+                (
+                    i,
+                    format!(
+                        "bone {} `{}` has no weights, children or references",
+                        i, name
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    fn empty_morphs(&self) -> Vec<(usize, String)> {
+        let zero = |v: &[f32]| v.iter().all(|x| *x == 0.0);
+        self.morphs
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| match &m.kind {
+                morph::Kind::Vertex(v) => v.iter().all(|o| zero(&o.offset)),
+                morph::Kind::Uv(v) | morph::Kind::ExtendedUv(_, v) => {
+                    v.iter().all(|o| zero(&o.offset))
+                }
+                morph::Kind::Bone(v) => v.iter().all(|o| {
+                    let [x, y, z, w] = o.rotation;
+                    zero(&o.offset) && zero(&[x, y, z]) && (w == 1.0 || w == -1.0)
+                }),
+                morph::Kind::Maerial(v) => v.iter().all(|o| {
+                    let values = [
+                        o.diffuse.as_slice(),
+                        &o.specular,
+                        &[o.specular_power],
+                        &o.ambient,
+                        &o.edge_color,
+                        &[o.edge_size],
+                        &o.texture,
+                        &o.sphere,
+                        &o.toon,
+                    ]
+                    .concat();
+                    match o.op {
+                        morph::MaterialOp::Mul => values.iter().all(|x| *x == 1.0),
+                        morph::MaterialOp::Add => zero(&values),
+                    }
+                }),
+                morph::Kind::Group(v) => v.iter().all(|o| o.ratio == 0.0),
+            })
+            .map(|(i, m)| (i, format!("morph {} `{}` changes nothing", i, m.name)))
+            .collect()
+    }
+
+    fn empty_materials(&self) -> Vec<(usize, String)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.index_count == 0)
+            .map(|(i, m)| (i, format!("material {} `{}` has no triangles", i, m.name)))
+            .collect()
+    }
+
+    fn unattached_rigids(&self) -> Vec<(usize, String)> {
+        self.rigids
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.bone.is_none())
+            .map(|(i, r)| {
+                (
+                    i,
+                    format!("rigid {} `{}` is attached to no bone", i, r.name),
+                )
+            })
+            .collect()
+    }
+
+    fn self_joints(&self) -> Vec<(usize, String)> {
+        self.joints
+            .iter()
+            .enumerate()
+            .filter(|(_, j)| j.rigids[0].is_some() && j.rigids[0] == j.rigids[1])
+            .map(|(i, j)| {
+                (
+                    i,
+                    format!("joint {} `{}` connects a rigid with itself", i, j.name),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn names() {
+        for rule in Rule::all() {
+            assert!(Rule::from_name(rule.name()) == Some(rule));
+        }
+        assert!(Severity::from_name("warning") == Some(Severity::Warning));
+        assert!(Rule::from_name("unknown").is_none());
+    }
+
+    #[test]
+    fn alicia() {
+        let mut pmx = read_pmx();
+        let mut options = LintOptions::default();
+        let lints = pmx.lint(&options);
+        // two triangles using distinct vertices at the same position, and the weapon bones
+        assert!(lints.len() == 4);
+        assert!(lints[0].rule == Rule::DegenerateFace && lints[0].index == 24663);
+        assert!(lints[1].rule == Rule::DegenerateFace && lints[1].index == 24692);
+        assert!(lints[2].rule == Rule::UnusedBone && pmx.bones[lints[2].index].name == "左武器");
+        assert!(lints[3].rule == Rule::UnusedBone && pmx.bones[lints[3].index].name == "右武器");
+
+        pmx.textures.push("unused.png".into());
+        pmx.faces[1] = pmx.faces[0];
+        pmx.morphs[0].kind = morph::Kind::Vertex(vec![]);
+        pmx.rigids[2].bone = None;
+        pmx.joints[1].rigids[1] = pmx.joints[1].rigids[0];
+        let mut material = pmx.materials[0].clone();
+        material.index_count = 0;
+        pmx.materials.push(material);
+        let lints = pmx.lint(&options);
+        let found = |rule: Rule, index: usize| {
+            lints.iter().any(|l| {
+                l.rule == rule && l.index == index && l.severity == rule.default_severity()
+            })
+        };
+        assert!(found(Rule::UnusedTexture, pmx.textures.len() - 1));
+        assert!(found(Rule::DegenerateFace, 0));
+        assert!(found(Rule::EmptyMorph, 0));
+        assert!(found(Rule::UnattachedRigid, 2));
+        assert!(found(Rule::SelfJoint, 1));
+        assert!(found(Rule::EmptyMaterial, pmx.materials.len() - 1));
+
+        options.set(Rule::SelfJoint, Severity::Allow);
+        options.set(Rule::EmptyMorph, Severity::Error);
+        let lints = pmx.lint(&options);
+        assert!(lints.iter().all(|l| l.rule != Rule::SelfJoint));
+        assert!(lints
+            .iter()
+            .any(|l| l.rule == Rule::EmptyMorph && l.severity == Severity::Error));
+    }
+
+    #[test]
+    fn unused_bone() {
+        let mut pmx = read_pmx();
+        let options = LintOptions::default();
+        let before = pmx.lint(&options).len();
+        let mut bone = pmx.bones[0].clone();
+        bone.name = "unused".into();
+        bone.connected_to = ConnectedTo::Offset([0.0; 3]);
+        pmx.bones.push(bone);
+        let lints = pmx.lint(&options);
+        assert!(lints.len() == before + 1);
+        assert!(lints
+            .iter()
+            .any(|l| l.rule == Rule::UnusedBone && l.index == pmx.bones.len() - 1));
+    }
+}
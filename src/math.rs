@@ -0,0 +1,87 @@
+pub(crate) type Vec3 = [f32; 3];
+#[cfg(feature = "gltf")]
+pub(crate) type Mat4 = [[f32; 4]; 4];
+
+#[cfg(feature = "gltf")]
+pub(crate) const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[inline]
+pub(crate) fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[inline]
+pub(crate) fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+#[inline]
+pub(crate) fn scale(a: Vec3, s: f32) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+#[inline]
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline]
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[inline]
+pub(crate) fn length(a: Vec3) -> f32 {
+    dot(a, a).sqrt()
+}
+
+#[inline]
+pub(crate) fn normalize(a: Vec3) -> Vec3 {
+    let len = length(a);
+    if len > f32::EPSILON {
+        scale(a, 1.0 / len)
+    } else {
+        [0.0; 3]
+    }
+}
+
+/// Multiplies two column-major matrices.
+#[cfg(feature = "gltf")]
+pub(crate) fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [[0.0f32; 4]; 4];
+    for (c, col) in m.iter_mut().enumerate() {
+        for (r, v) in col.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    m
+}
+
+/// Transforms a point by a column-major affine matrix.
+#[cfg(feature = "gltf")]
+pub(crate) fn transform_point(m: &Mat4, p: Vec3) -> Vec3 {
+    [
+        m[0][0] * p[0] + m[1][0] * p[1] + m[2][0] * p[2] + m[3][0],
+        m[0][1] * p[0] + m[1][1] * p[1] + m[2][1] * p[2] + m[3][1],
+        m[0][2] * p[0] + m[1][2] * p[1] + m[2][2] * p[2] + m[3][2],
+    ]
+}
+
+/// Transforms a direction by the upper 3x3 part of a column-major matrix.
+#[cfg(feature = "gltf")]
+pub(crate) fn transform_vector(m: &Mat4, v: Vec3) -> Vec3 {
+    [
+        m[0][0] * v[0] + m[1][0] * v[1] + m[2][0] * v[2],
+        m[0][1] * v[0] + m[1][1] * v[1] + m[2][1] * v[2],
+        m[0][2] * v[0] + m[1][2] * v[1] + m[2][2] * v[2],
+    ]
+}
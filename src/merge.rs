@@ -0,0 +1,299 @@
+//! Merging of two models, e.g. a body with an accessory or an outfit.
+
+use crate::edit::{Kind, Report};
+use crate::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct MergeOptions {
+    /// Binds bones of the other model to bones of the same name in this model.
+    pub unify_bones: bool,
+    /// Reuses textures whose paths only differ in case or separators.
+    pub dedup_textures: bool,
+    /// Appends display elements to the group of the same name instead of adding a new group.
+    pub merge_display_groups: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            unify_bones: true,
+            dedup_textures: true,
+            merge_display_groups: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Merged {
+    /// Index in the merged model of each bone of the other model.
+    pub bones: Vec<usize>,
+    /// Index in the merged model of each texture of the other model.
+    pub textures: Vec<usize>,
+    /// Bones of the other model that were bound to an existing bone.
+    pub unified_bones: Vec<usize>,
+}
+
+impl Pmx {
+    /// Appends `other` with every index offset into this model.
+    pub fn merge(&mut self, mut other: Pmx, options: &MergeOptions) -> Merged {
+        let offset = |base: usize, len: usize| (base..base + len).map(Some).collect::<Vec<_>>();
+
+        let mut texture_keys = self
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (texture_key(path), i))
+            .collect::<HashMap<_, _>>();
+        let mut textures = vec![];
+        let mut new_textures = vec![];
+        for path in std::mem::take(&mut other.textures) {
+            let key = texture_key(&path);
+            let index = match texture_keys.get(&key) {
+                Some(&i) if options.dedup_textures => i,
+                _ => {
+                    let i = self.textures.len() + new_textures.len();
+                    texture_keys.entry(key).or_insert(i);
+                    new_textures.push(path);
+                    i
+                }
+            };
+            textures.push(index);
+        }
+
+        let mut bones = vec![];
+        let mut unified_bones = vec![];
+        let mut appended = 0;
+        for (i, bone) in other.bones.iter().enumerate() {
+            let existing = options
+                .unify_bones
+                .then(|| self.bones.iter().position(|b| b.name == bone.name))
+                .flatten();
+            match existing {
+                Some(index) => {
+                    unified_bones.push(i);
+                    bones.push(index);
+                }
+                None => {
+                    bones.push(self.bones.len() + appended);
+                    appended += 1;
+                }
+            }
+        }
+
+        // a material morph offset without a material applies to every material
+        // of its model, so on both sides it is expanded to the materials the
+        // model had before merging and never reaches the other model's ones
+        let other_materials = other.materials.len();
+        expand_all_materials(&mut self.morphs, self.materials.len());
+        expand_all_materials(&mut other.morphs, other_materials);
+
+        let mut report = Report::default();
+        let maps = [
+            (
+                Kind::Vertex,
+                offset(self.vertices.len(), other.vertices.len()),
+            ),
+            (Kind::Texture, textures.iter().copied().map(Some).collect()),
+            (
+                Kind::Material,
+                offset(self.materials.len(), other_materials),
+            ),
+            (Kind::Bone, bones.iter().copied().map(Some).collect()),
+            (Kind::Morph, offset(self.morphs.len(), other.morphs.len())),
+            (Kind::Rigid, offset(self.rigids.len(), other.rigids.len())),
+        ];
+        for (kind, map) in maps.iter() {
+            other.rewrite_references(*kind, map, &mut report);
+        }
+
+        let extended_uv = self.header.extended_uv.max(other.header.extended_uv);
+        self.vertices.extend(other.vertices);
+        for v in self.vertices.iter_mut() {
+            v.extended_uv.resize(extended_uv as usize, [0.0; 4]);
+        }
+        self.faces.extend(other.faces);
+        self.textures.extend(new_textures);
+        self.materials.extend(other.materials);
+        self.bones.extend(
+            other
+                .bones
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| !unified_bones.contains(i))
+                .map(|(_, bone)| bone),
+        );
+        self.morphs.extend(other.morphs);
+        self.rigids.extend(other.rigids);
+        self.joints.extend(other.joints);
+        self.merge_display_groups(other.display_groups, options.merge_display_groups);
+
+        self.header.extended_uv = extended_uv;
+        // never narrow the authored sizes, only widen what no longer fits
+        let current = self.header.index_sizes();
+        let minimal = self.minimal_index_sizes();
+        self.header.set_index_sizes(IndexSizes {
+            vertex: current.vertex.max(minimal.vertex),
+            texture: current.texture.max(minimal.texture),
+            material: current.material.max(minimal.material),
+            bone: current.bone.max(minimal.bone),
+            morph: current.morph.max(minimal.morph),
+            rigid: current.rigid.max(minimal.rigid),
+        });
+
+        Merged {
+            bones,
+            textures,
+            unified_bones,
+        }
+    }
+
+    fn merge_display_groups(&mut self, groups: Vec<DisplayGroup>, merge: bool) {
+        let contains = |groups: &[DisplayGroup], e: &DisplayElement| {
+            groups
+                .iter()
+                .flat_map(|g| &g.elements)
+                .any(|x| match (x, e) {
+                    (DisplayElement::Bone(a), DisplayElement::Bone(b)) => a == b,
+                    (DisplayElement::Morph(a), DisplayElement::Morph(b)) => a == b,
+                    _ => false,
+                })
+        };
+        for mut group in groups {
+            group
+                .elements
+                .retain(|e| !contains(&self.display_groups, e));
+            let existing = merge
+                .then(|| {
+                    self.display_groups
+                        .iter_mut()
+                        .find(|g| g.name == group.name)
+                })
+                .flatten();
+            match existing {
+                Some(existing) => existing.elements.extend(group.elements),
+                None if group.elements.is_empty() => {}
+                None => self.display_groups.push(group),
+            }
+        }
+    }
+}
+
+/// Replaces material morph offsets without a material by one offset for each of `materials`.
+fn expand_all_materials(morphs: &mut [Morph], materials: usize) {
+    for morph in morphs.iter_mut() {
+        if let morph::Kind::Maerial(offsets) = &mut morph.kind {
+            *offsets = offsets
+                .drain(..)
+                .flat_map(|o| match o.material {
+                    Some(_) => vec![o],
+                    None => (0..materials)
+                        .map(|m| morph::Material {
+                            material: Some(m),
+                            ..o.clone()
+                        })
+                        .collect(),
+                })
+                .collect();
+        }
+    }
+}
+
+fn texture_key(path: &std::path::Path) -> String {
+    path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx(name: &str) -> Pmx {
+        let file = std::fs::File::open(format!("resource/Alicia/{}", name)).unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn merge_blade() {
+        let solid = read_pmx("Alicia_solid.pmx");
+        let blade = read_pmx("Alicia_blade.pmx");
+        let mut pmx = solid.clone();
+        let merged = pmx.merge(blade.clone(), &MergeOptions::default());
+        assert!(pmx.vertices.len() == 22311 + blade.vertices.len());
+        assert!(pmx.faces.len() == 95598 + blade.faces.len());
+        assert!(pmx.materials.len() == 22 + blade.materials.len());
+        assert!(pmx.morphs.len() == solid.morphs.len() + blade.morphs.len());
+        assert!(pmx.bones.len() == 150);
+        assert!(merged.unified_bones == [0]);
+        assert!(pmx.bones[merged.bones[0]].name == "センター");
+        assert!(pmx.faces[95598..].iter().all(|&f| f >= 22311));
+        assert!(pmx.faces.iter().all(|&f| (f as usize) < pmx.vertices.len()));
+        assert!(pmx.textures.len() == 12 + 4);
+        assert!(pmx.display_groups.len() == solid.display_groups.len());
+        let expressions = solid.display_groups[1].elements.len() + blade.morphs.len();
+        assert!(pmx.display_groups[1].elements.len() == expressions);
+        assert!(pmx.header.vertex_index_size == 2);
+    }
+
+    #[test]
+    fn keep_separate() {
+        let mut pmx = read_pmx("Alicia_solid.pmx");
+        let blade = read_pmx("Alicia_blade.pmx");
+        let options = MergeOptions {
+            unify_bones: false,
+            ..Default::default()
+        };
+        let merged = pmx.merge(blade, &options);
+        assert!(pmx.bones.len() == 151);
+        assert!(merged.bones == [150]);
+        assert!(merged.unified_bones.is_empty());
+        assert!(pmx.header.bone_index_size == 2);
+    }
+
+    #[test]
+    fn all_materials_morphs() {
+        fn all_materials() -> Morph {
+            Morph {
+                name: "all".into(),
+                name_en: String::new(),
+                panel: Panel::Other,
+                kind: morph::Kind::Maerial(vec![morph::Material {
+                    material: None,
+                    op: morph::MaterialOp::Mul,
+                    diffuse: [0.0; 4],
+                    specular: [0.0; 3],
+                    specular_power: 0.0,
+                    ambient: [0.0; 3],
+                    edge_color: [0.0; 4],
+                    edge_size: 0.0,
+                    texture: [0.0; 4],
+                    sphere: [0.0; 4],
+                    toon: [0.0; 4],
+                }]),
+            }
+        }
+        let materials = |morph: &Morph| match &morph.kind {
+            morph::Kind::Maerial(offsets) => offsets.iter().map(|o| o.material).collect(),
+            _ => vec![],
+        };
+        let mut pmx = read_pmx("Alicia_solid.pmx");
+        let mut blade = read_pmx("Alicia_blade.pmx");
+        pmx.morphs.push(all_materials());
+        blade.morphs.push(all_materials());
+        let host = pmx.morphs.len() - 1;
+        let blade_materials = blade.materials.len();
+        pmx.merge(blade, &MergeOptions::default());
+        assert!(materials(&pmx.morphs[host]) == (0..22).map(Some).collect::<Vec<_>>());
+        let expected = (22..22 + blade_materials).map(Some).collect::<Vec<_>>();
+        assert!(materials(pmx.morphs.last().unwrap()) == expected);
+    }
+
+    #[test]
+    fn dedup_textures() {
+        let mut pmx = read_pmx("Alicia_blade.pmx");
+        let mut other = pmx.clone();
+        other.textures[0] = "ALICIA_ROD.TGA".into();
+        let merged = pmx.merge(other, &MergeOptions::default());
+        assert!(pmx.textures.len() == 4);
+        assert!(merged.textures == [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,438 @@
+//! Mirroring of one side of a model onto the other side.
+//!
+//! The model is mirrored across the YZ plane. The left side of a character is +X.
+
+use crate::standard::{split_side, Side};
+use crate::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct MirrorOptions {
+    /// Side that is copied onto the other side.
+    pub from: Side,
+    /// Largest distance between a vertex and the mirrored position of its counterpart.
+    pub tolerance: f32,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        Self {
+            from: Side::Left,
+            tolerance: 0.001,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MirrorReport {
+    pub bones: usize,
+    pub rigids: usize,
+    pub joints: usize,
+    /// Vertices of the other side that received the weight of their counterpart.
+    pub vertices: usize,
+    /// Vertices of the other side without a counterpart, whose weights are left untouched.
+    pub unmatched_vertices: Vec<usize>,
+    /// Vertex morphs that were overwritten from their counterpart.
+    pub morphs: usize,
+    /// Vertex morphs that were added because the counterpart did not exist.
+    pub generated_morphs: Vec<usize>,
+    /// Bones of the source side whose counterpart does not exist.
+    pub unpaired_bones: Vec<usize>,
+}
+
+impl Pmx {
+    /// Copies bones, rigids, joints, weights and vertex morphs of one side onto the other side.
+    ///
+    /// Elements are paired by name, swapping `左` and `右` or `.L`/`_L` and `.R`/`_R` suffixes.
+    /// Vertices are paired by position, so only the attributes of existing vertices change.
+    pub fn mirror(&mut self, options: &MirrorOptions) -> MirrorReport {
+        let mut report = MirrorReport::default();
+        let bones = pairs(self.bones.iter().map(|b| &b.name));
+        let rigids = pairs(self.rigids.iter().map(|r| &r.name));
+        let joints = pairs(self.joints.iter().map(|j| &j.name));
+        report.unpaired_bones = self
+            .bones
+            .iter()
+            .enumerate()
+            .filter(|(i, b)| {
+                mirror_name(&b.name).is_some_and(|(side, _)| side == options.from)
+                    && bones[*i] == *i
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let bone = |b: Option<usize>| b.map(|b| bones.get(b).copied().unwrap_or(b));
+        let rigid = |r: Option<usize>| r.map(|r| rigids.get(r).copied().unwrap_or(r));
+
+        for (source, &target) in bones.iter().enumerate() {
+            if source == target || !is_source(&self.bones[source].name, options.from) {
+                continue;
+            }
+            let mut b = self.bones[source].clone();
+            b.name = std::mem::take(&mut self.bones[target].name);
+            b.name_en = std::mem::take(&mut self.bones[target].name_en);
+            b.position = reflect(b.position);
+            b.parent = bone(b.parent);
+            b.connected_to = match b.connected_to {
+                ConnectedTo::Offset(offset) => ConnectedTo::Offset(reflect(offset)),
+                ConnectedTo::Bone(to) => ConnectedTo::Bone(bone(to)),
+            };
+            if let Some(ik) = b.ik.as_mut() {
+                ik.bone = bone(ik.bone);
+                for link in ik.links.iter_mut() {
+                    link.bone = bone(link.bone);
+                    link.limits = link.limits.as_ref().map(reflect_rotation_limit);
+                }
+            }
+            if let Some(addition) = b.addition.as_mut() {
+                addition.bone = bone(addition.bone);
+            }
+            b.fixed_pole = b.fixed_pole.map(reflect);
+            if let Some(pole) = b.local_pole.as_mut() {
+                pole.x = reflect(pole.x);
+                pole.z = reflect(pole.z);
+            }
+            self.bones[target] = b;
+            report.bones += 1;
+        }
+
+        for (source, &target) in rigids.iter().enumerate() {
+            if source == target || !is_source(&self.rigids[source].name, options.from) {
+                continue;
+            }
+            let mut r = self.rigids[source].clone();
+            r.name = std::mem::take(&mut self.rigids[target].name);
+            r.name_en = std::mem::take(&mut self.rigids[target].name_en);
+            r.bone = bone(r.bone);
+            r.position = reflect(r.position);
+            r.rotation = reflect_rotation(r.rotation);
+            self.rigids[target] = r;
+            report.rigids += 1;
+        }
+
+        for (source, &target) in joints.iter().enumerate() {
+            if source == target || !is_source(&self.joints[source].name, options.from) {
+                continue;
+            }
+            let mut j = self.joints[source].clone();
+            j.name = std::mem::take(&mut self.joints[target].name);
+            j.name_en = std::mem::take(&mut self.joints[target].name_en);
+            j.rigids = j.rigids.map(rigid);
+            j.position = reflect(j.position);
+            j.rotation = reflect_rotation(j.rotation);
+            j.limit_translation = AngleLimit {
+                lower: [
+                    -j.limit_translation.upper[0],
+                    j.limit_translation.lower[1],
+                    j.limit_translation.lower[2],
+                ],
+                upper: [
+                    -j.limit_translation.lower[0],
+                    j.limit_translation.upper[1],
+                    j.limit_translation.upper[2],
+                ],
+            };
+            j.limit_rotation = reflect_rotation_limit(&j.limit_rotation);
+            self.joints[target] = j;
+            report.joints += 1;
+        }
+
+        let counterparts = self.vertex_counterparts(options.tolerance);
+        let target_side = |x: f32| match options.from {
+            Side::Left => x < -options.tolerance,
+            Side::Right => x > options.tolerance,
+        };
+        for (t, &counterpart) in counterparts.iter().enumerate() {
+            if !target_side(self.vertices[t].position[0]) {
+                continue;
+            }
+            let Some(s) = counterpart else {
+                report.unmatched_vertices.push(t);
+                continue;
+            };
+            let mut weight = self.vertices[s].weight.clone();
+            match &mut weight {
+                Weight::Bdef1(w) => w.bone = bone(w.bone),
+                Weight::Bdef2(w) => w.bones = w.bones.map(bone),
+                Weight::Bdef4(w) => w.bones = w.bones.map(bone),
+                Weight::Sdef(w) => {
+                    w.bones = w.bones.map(bone);
+                    w.c = reflect(w.c);
+                    w.r0 = reflect(w.r0);
+                    w.r1 = reflect(w.r1);
+                }
+            }
+            self.vertices[t].weight = weight;
+            report.vertices += 1;
+        }
+
+        self.mirror_morphs(&counterparts, options.from, &mut report);
+        report
+    }
+
+    /// Returns for every vertex the vertex closest to its mirrored position.
+    fn vertex_counterparts(&self, tolerance: f32) -> Vec<Option<usize>> {
+        let tolerance = tolerance.max(f32::EPSILON);
+        let cell = |p: [f32; 3]| p.map(|x| (x / tolerance).floor() as i64);
+        let mut grid = HashMap::<[i64; 3], Vec<usize>>::new();
+        for (i, v) in self.vertices.iter().enumerate() {
+            grid.entry(cell(v.position)).or_default().push(i);
+        }
+        self.vertices
+            .iter()
+            .map(|v| {
+                let q = reflect(v.position);
+                let c = cell(q);
+                let mut best = None;
+                let mut best_distance = tolerance * tolerance;
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        for dz in -1..=1 {
+                            let Some(candidates) = grid.get(&[c[0] + dx, c[1] + dy, c[2] + dz])
+                            else {
+                                continue;
+                            };
+                            for &i in candidates {
+                                let p = self.vertices[i].position;
+                                let d = (0..3).map(|k| (p[k] - q[k]).powi(2)).sum::<f32>();
+                                if d <= best_distance {
+                                    best_distance = d;
+                                    best = Some(i);
+                                }
+                            }
+                        }
+                    }
+                }
+                best
+            })
+            .collect()
+    }
+
+    /// Mirrors vertex morphs named for the source side into their counterparts, adding missing ones.
+    fn mirror_morphs(
+        &mut self,
+        counterparts: &[Option<usize>],
+        from: Side,
+        report: &mut MirrorReport,
+    ) {
+        let names = self
+            .morphs
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.name.clone(), i))
+            .collect::<HashMap<_, _>>();
+        for source in 0..self.morphs.len() {
+            let Some((side, name)) = mirror_name(&self.morphs[source].name) else {
+                continue;
+            };
+            let morph::Kind::Vertex(offsets) = &self.morphs[source].kind else {
+                continue;
+            };
+            if side != from {
+                continue;
+            }
+            let offsets = offsets
+                .iter()
+                .filter_map(|o| {
+                    let v = counterparts.get(o.vertex?).copied().flatten()?;
+                    Some(morph::Vertex {
+                        vertex: Some(v),
+                        offset: reflect(o.offset),
+                    })
+                })
+                .collect::<Vec<_>>();
+            match names.get(&name) {
+                Some(&target) => {
+                    if let morph::Kind::Vertex(target) = &mut self.morphs[target].kind {
+                        *target = offsets;
+                        report.morphs += 1;
+                    }
+                }
+                None => {
+                    let source_morph = &self.morphs[source];
+                    let morph = Morph {
+                        name,
+                        name_en: mirror_name(&source_morph.name_en)
+                            .map(|(_, n)| n)
+                            .unwrap_or_default(),
+                        panel: source_morph.panel.clone(),
+                        kind: morph::Kind::Vertex(offsets),
+                    };
+                    let index = self.morphs.len();
+                    self.morphs.push(morph);
+                    for group in self.display_groups.iter_mut() {
+                        let position = group.elements.iter().position(
+                            |e| matches!(e, DisplayElement::Morph(Some(m)) if *m == source),
+                        );
+                        if let Some(position) = position {
+                            group
+                                .elements
+                                .insert(position + 1, DisplayElement::Morph(Some(index)));
+                            break;
+                        }
+                    }
+                    report.generated_morphs.push(index);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the side a name belongs to and the name of its counterpart.
+fn mirror_name(name: &str) -> Option<(Side, String)> {
+    let left = name.find('左');
+    let right = name.find('右');
+    let side = match (left, right) {
+        (Some(l), Some(r)) if r < l => Side::Right,
+        (Some(_), _) => Side::Left,
+        (None, Some(_)) => Side::Right,
+        (None, None) => {
+            let (side, base) = split_side(name)?;
+            let suffix = &name[base.len()..];
+            let mirrored = match side {
+                Side::Left => suffix.replace('L', "R"),
+                Side::Right => suffix.replace('R', "L"),
+            };
+            return Some((side, format!("{}{}", base, mirrored)));
+        }
+    };
+    let mirrored = name
+        .chars()
+        .map(|c| match c {
+            '左' => '右',
+            '右' => '左',
+            c => c,
+        })
+        .collect();
+    Some((side, mirrored))
+}
+
+fn is_source(name: &str, from: Side) -> bool {
+    mirror_name(name).is_some_and(|(side, _)| side == from)
+}
+
+/// Returns for every element the index of its counterpart, or its own index if it has none.
+fn pairs<'a>(names: impl Iterator<Item = &'a String> + Clone) -> Vec<usize> {
+    let indices = names
+        .clone()
+        .enumerate()
+        .map(|(i, n)| (n.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    names
+        .enumerate()
+        .map(|(i, name)| {
+            mirror_name(name)
+                .and_then(|(_, n)| indices.get(n.as_str()).copied())
+                .unwrap_or(i)
+        })
+        .collect()
+}
+
+#[inline]
+fn reflect(v: [f32; 3]) -> [f32; 3] {
+    [-v[0], v[1], v[2]]
+}
+
+/// Mirrors Euler angles: rotations about Y and Z change direction.
+#[inline]
+fn reflect_rotation(r: [f32; 3]) -> [f32; 3] {
+    [r[0], -r[1], -r[2]]
+}
+
+fn reflect_rotation_limit(limit: &AngleLimit) -> AngleLimit {
+    AngleLimit {
+        lower: [limit.lower[0], -limit.upper[1], -limit.upper[2]],
+        upper: [limit.upper[0], -limit.lower[1], -limit.lower[2]],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn bone(pmx: &Pmx, name: &str) -> usize {
+        pmx.bones.iter().position(|b| b.name == name).unwrap()
+    }
+
+    #[test]
+    fn names() {
+        assert!(mirror_name("左腕").unwrap() == (Side::Left, "右腕".into()));
+        assert!(mirror_name("腰キャンセル右").unwrap() == (Side::Right, "腰キャンセル左".into()));
+        assert!(mirror_name("hair.L").unwrap() == (Side::Left, "hair.R".into()));
+        assert!(mirror_name("センター").is_none());
+    }
+
+    #[test]
+    fn alicia() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let arm = bone(&pmx, "左腕");
+        pmx.bones[arm].position[1] += 1.0;
+        let report = pmx.mirror(&MirrorOptions {
+            tolerance: 0.01,
+            ..Default::default()
+        });
+        assert!(report.bones > 40);
+        assert!(report.rigids > 0 && report.joints > 0);
+        let right = bone(&pmx, "右腕");
+        assert!(pmx.bones[right].position[1] == pmx.bones[arm].position[1]);
+        assert!(pmx.bones[right].position[0] == -pmx.bones[arm].position[0]);
+        assert!(pmx.bones[right].parent == original.bones[right].parent);
+        assert!(pmx.bones[right].name == "右腕");
+        assert!(report.vertices * 10 > report.unmatched_vertices.len() * 9);
+        assert!(report.morphs == 1);
+        assert!(report.generated_morphs.is_empty());
+
+        let counterparts = pmx.vertex_counterparts(0.01);
+        let named = |v: &Vertex, mirrored: bool| {
+            let mut names = crate::weight::influences(&v.weight)
+                .into_iter()
+                .map(|(b, w)| {
+                    let name = &pmx.bones[b].name;
+                    let name = mirror_name(name)
+                        .filter(|_| mirrored && !report.unpaired_bones.contains(&b))
+                        .map_or(name.clone(), |(_, n)| n);
+                    (name, (w * 1000.0).round() as i32)
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        for (v, counterpart) in pmx.vertices.iter().zip(counterparts) {
+            if let Some(c) = counterpart.filter(|_| v.position[0] < -0.01) {
+                assert!(named(v, false) == named(&pmx.vertices[c], true));
+            }
+        }
+    }
+
+    #[test]
+    fn generate_morph() {
+        let mut pmx = read_pmx();
+        let wink = pmx
+            .morphs
+            .iter()
+            .position(|m| m.name == "ウィンク右")
+            .unwrap();
+        let morphs = pmx.morphs.len();
+        let report = pmx.mirror(&MirrorOptions {
+            from: Side::Right,
+            tolerance: 0.01,
+        });
+        assert!(report.generated_morphs.contains(&morphs));
+        let generated = &pmx.morphs[morphs];
+        assert!(generated.name == "ウィンク左");
+        let (morph::Kind::Vertex(a), morph::Kind::Vertex(b)) =
+            (&pmx.morphs[wink].kind, &generated.kind)
+        else {
+            panic!();
+        };
+        assert!(b.len() <= a.len() && b.len() * 10 > a.len() * 9);
+        for o in b {
+            assert!(pmx.vertices[o.vertex.unwrap()].position[0] > 0.0);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Static export to Wavefront OBJ and MTL.
+//!
+//! Positions and normals are mirrored along Z to convert MMD's left-handed
+//! coordinates into OBJ's right-handed ones. Mirroring turns the faces inside
+//! out, so every triangle is written as `i0 i2 i1` to keep it facing the same
+//! way as its normals. Texture coordinates are flipped vertically because OBJ
+//! puts the origin at the bottom left.
+
+use crate::*;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Default, Debug)]
+pub struct Options<'a> {
+    /// Positions to export instead of the rest pose, e.g. from a posed mesh.
+    pub positions: Option<&'a [[f32; 3]]>,
+    /// Normals to export instead of `Vertex::normal`.
+    pub normals: Option<&'a [[f32; 3]]>,
+    /// Directory of the model, used to resolve every texture path in the MTL.
+    pub model_dir: Option<&'a Path>,
+    /// Name of the MTL file referenced by `mtllib`.
+    pub mtl_name: Option<&'a str>,
+}
+
+/// Writes `path` and an MTL file with the same stem next to it.
+pub fn export(pmx: &Pmx, path: impl AsRef<Path>, options: &Options) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let options = Options {
+        mtl_name: mtl_name.as_deref(),
+        ..options.clone()
+    };
+    let obj = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_obj(pmx, obj, &options)?;
+    let mtl = std::io::BufWriter::new(std::fs::File::create(&mtl_path)?);
+    write_mtl(pmx, mtl, &options)
+}
+
+pub fn write_obj<W: Write>(pmx: &Pmx, mut w: W, options: &Options) -> std::io::Result<()> {
+    let len = pmx.vertices.len();
+    if options.positions.is_some_and(|p| p.len() != len)
+        || options.normals.is_some_and(|n| n.len() != len)
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "vertex count mismatch",
+        ));
+    }
+    writeln!(w, "# {}", pmx.model_info.name)?;
+    if let Some(mtl_name) = options.mtl_name {
+        writeln!(w, "mtllib {}", mtl_name)?;
+    }
+    for (i, v) in pmx.vertices.iter().enumerate() {
+        let p = options.positions.map_or(v.position, |p| p[i]);
+        writeln!(w, "v {} {} {}", p[0], p[1], -p[2])?;
+    }
+    for v in pmx.vertices.iter() {
+        writeln!(w, "vt {} {}", v.uv[0], 1.0 - v.uv[1])?;
+    }
+    for (i, v) in pmx.vertices.iter().enumerate() {
+        let n = options.normals.map_or(v.normal, |n| n[i]);
+        writeln!(w, "vn {} {} {}", n[0], n[1], -n[2])?;
+    }
+    let names = material_names(pmx);
+    let mut faces = pmx.faces.chunks_exact(3);
+    for (material, name) in pmx.materials.iter().zip(names.iter()) {
+        writeln!(w, "g {}", name)?;
+        writeln!(w, "usemtl {}", name)?;
+        for face in faces.by_ref().take(material.index_count as usize / 3) {
+            write!(w, "f")?;
+            for i in [face[0], face[2], face[1]] {
+                let i = i + 1;
+                write!(w, " {}/{}/{}", i, i, i)?;
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_mtl<W: Write>(pmx: &Pmx, mut w: W, options: &Options) -> std::io::Result<()> {
+    writeln!(w, "# {}", pmx.model_info.name)?;
+    let names = material_names(pmx);
+    for (material, name) in pmx.materials.iter().zip(names.iter()) {
+        let [r, g, b, a] = material.diffuse;
+        writeln!(w)?;
+        writeln!(w, "newmtl {}", name)?;
+        writeln!(
+            w,
+            "Ka {} {} {}",
+            material.ambient[0], material.ambient[1], material.ambient[2]
+        )?;
+        writeln!(w, "Kd {} {} {}", r, g, b)?;
+        writeln!(
+            w,
+            "Ks {} {} {}",
+            material.specular[0], material.specular[1], material.specular[2]
+        )?;
+        writeln!(w, "Ns {}", material.specular_power)?;
+        writeln!(w, "d {}", a)?;
+        writeln!(w, "illum 2")?;
+        if let Some(path) = material.texture.and_then(|i| pmx.textures.get(i)) {
+            writeln!(w, "map_Kd {}", texture_path(path, options.model_dir))?;
+        }
+    }
+    Ok(())
+}
+
+/// Makes material names unique and free of whitespace, as OBJ readers split on it.
+fn material_names(pmx: &Pmx) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(pmx.materials.len());
+    for (i, material) in pmx.materials.iter().enumerate() {
+        let mut name = material
+            .name
+            .chars()
+            .map(|c| if c.is_whitespace() { '_' } else { c })
+            .collect::<String>();
+        if name.is_empty() || names.contains(&name) {
+            name = format!("{}_{}", name, i);
+        }
+        names.push(name);
+    }
+    names
+}
+
+pub(crate) fn texture_path(path: &Path, model_dir: Option<&Path>) -> String {
+    let normalized = PathBuf::from(path.to_string_lossy().replace('\\', "/"));
+    let path = match model_dir {
+        Some(dir) => texture::Resolver::new(dir)
+            .resolve(path)
+            .unwrap_or_else(|| dir.join(normalized)),
+        None => normalized,
+    };
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn obj() {
+        let pmx = read_pmx();
+        let mut buffer = vec![];
+        write_obj(&pmx, &mut buffer, &Options::default()).unwrap();
+        let obj = String::from_utf8(buffer).unwrap();
+        let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+        assert!(count("v ") == pmx.vertices.len());
+        assert!(count("vt ") == pmx.vertices.len());
+        assert!(count("f ") == pmx.faces.len() / 3);
+        assert!(count("usemtl ") == pmx.materials.len());
+        let [a, b, c] = [pmx.faces[0] + 1, pmx.faces[1] + 1, pmx.faces[2] + 1];
+        let first = obj.lines().find(|l| l.starts_with("f ")).unwrap();
+        assert!(first == format!("f {a}/{a}/{a} {c}/{c}/{c} {b}/{b}/{b}"));
+    }
+
+    #[test]
+    fn mtl() {
+        let pmx = read_pmx();
+        let mut buffer = vec![];
+        let options = Options {
+            model_dir: Some(Path::new("resource/Alicia")),
+            ..Default::default()
+        };
+        write_mtl(&pmx, &mut buffer, &options).unwrap();
+        let mtl = String::from_utf8(buffer).unwrap();
+        assert!(mtl.lines().filter(|l| l.starts_with("newmtl ")).count() == 22);
+        assert!(mtl
+            .lines()
+            .filter_map(|l| l.strip_prefix("map_Kd "))
+            .all(|path| path.starts_with("resource/Alicia/") && !path.contains('\\')));
+    }
+
+    #[test]
+    fn vertex_count_mismatch() {
+        let pmx = read_pmx();
+        let options = Options {
+            positions: Some(&[[0.0; 3]]),
+            ..Default::default()
+        };
+        assert!(write_obj(&pmx, std::io::sink(), &options).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! Mesh optimization: welding of duplicate vertices and cache-friendly ordering.
+//!
+//! Triangles are reordered with Tom Forsyth's linear-speed vertex cache
+//! optimization, which only moves triangles within a material so that every
+//! `index_count` range stays intact. Vertices are then sorted by first use.
+
+use crate::edit::Kind;
+use crate::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct OptimizeOptions {
+    /// Welds vertices whose attributes, weights and morph offsets are all equal.
+    pub weld: bool,
+    pub reorder_faces: bool,
+    pub reorder_vertices: bool,
+    /// Size of the simulated post-transform vertex cache.
+    pub cache_size: usize,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            weld: true,
+            reorder_faces: true,
+            reorder_vertices: true,
+            cache_size: 32,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OptimizeReport {
+    pub welded: usize,
+    /// Average cache miss ratio, i.e. vertex transforms per triangle, before the optimization.
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl Pmx {
+    pub fn optimize(&mut self, options: &OptimizeOptions) -> OptimizeReport {
+        let acmr_before = acmr(&self.faces, options.cache_size);
+        let welded = if options.weld { self.weld() } else { 0 };
+        if options.reorder_faces {
+            let mut start = 0;
+            for material in self.materials.iter() {
+                let end = (start + material.index_count as usize).min(self.faces.len());
+                let range = &mut self.faces[start..end];
+                let ordered = forsyth(range, options.cache_size);
+                range.copy_from_slice(&ordered);
+                start = end;
+            }
+        }
+        if options.reorder_vertices {
+            let mut map = vec![None; self.vertices.len()];
+            let mut next = 0;
+            for &f in self.faces.iter() {
+                let m = &mut map[f as usize];
+                if m.is_none() {
+                    *m = Some(next);
+                    next += 1;
+                }
+            }
+            for m in map.iter_mut().filter(|m| m.is_none()) {
+                *m = Some(next);
+                next += 1;
+            }
+            self.remap(Kind::Vertex, &map, next);
+        }
+        OptimizeReport {
+            welded,
+            acmr_before,
+            acmr_after: acmr(&self.faces, options.cache_size),
+        }
+    }
+
+    /// Welds identical vertices and returns how many were removed.
+    fn weld(&mut self) -> usize {
+        let mut morph_offsets = vec![vec![]; self.vertices.len()];
+        for (m, morph) in self.morphs.iter().enumerate() {
+            match &morph.kind {
+                morph::Kind::Vertex(offsets) => {
+                    for o in offsets {
+                        if let Some(v) = o.vertex.and_then(|v| morph_offsets.get_mut(v)) {
+                            v.push(m as u32);
+                            v.extend(o.offset.map(bits));
+                        }
+                    }
+                }
+                morph::Kind::Uv(offsets) | morph::Kind::ExtendedUv(_, offsets) => {
+                    for o in offsets {
+                        if let Some(v) = o.vertex.and_then(|v| morph_offsets.get_mut(v)) {
+                            v.push(m as u32);
+                            v.extend(o.offset.map(bits));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut keys = HashMap::new();
+        let mut map = Vec::with_capacity(self.vertices.len());
+        for (vertex, offsets) in self.vertices.iter().zip(morph_offsets) {
+            let mut key = vertex_key(vertex);
+            key.extend(offsets);
+            let next = keys.len();
+            map.push(Some(*keys.entry(key).or_insert(next)));
+        }
+        let new_len = keys.len();
+        let welded = self.vertices.len() - new_len;
+        if welded == 0 {
+            return 0;
+        }
+        self.remap(Kind::Vertex, &map, new_len);
+        for morph in self.morphs.iter_mut() {
+            match &mut morph.kind {
+                morph::Kind::Vertex(offsets) => {
+                    let mut seen = vec![false; new_len];
+                    offsets.retain(|o| {
+                        o.vertex
+                            .is_none_or(|v| !std::mem::replace(&mut seen[v], true))
+                    });
+                }
+                morph::Kind::Uv(offsets) | morph::Kind::ExtendedUv(_, offsets) => {
+                    let mut seen = vec![false; new_len];
+                    offsets.retain(|o| {
+                        o.vertex
+                            .is_none_or(|v| !std::mem::replace(&mut seen[v], true))
+                    });
+                }
+                _ => {}
+            }
+        }
+        welded
+    }
+}
+
+/// Returns the bits of `v`, treating `-0.0` as `0.0`.
+fn bits(v: f32) -> u32 {
+    (v + 0.0).to_bits()
+}
+
+fn vertex_key(v: &Vertex) -> Vec<u32> {
+    let mut key = vec![];
+    key.extend(v.position.map(bits));
+    key.extend(v.normal.map(bits));
+    key.extend(v.uv.map(bits));
+    for uv in v.extended_uv.iter() {
+        key.extend(uv.map(bits));
+    }
+    key.push(bits(v.edge_ratio));
+    let index = |b: Option<usize>| b.map_or(u32::MAX, |b| b as u32);
+    match &v.weight {
+        Weight::Bdef1(w) => key.extend([0, index(w.bone)]),
+        Weight::Bdef2(w) => key.extend([1, index(w.bones[0]), index(w.bones[1]), bits(w.weight)]),
+        Weight::Bdef4(w) => {
+            key.push(2);
+            key.extend(w.bones.map(index));
+            key.extend(w.weights.map(bits));
+        }
+        Weight::Sdef(w) => {
+            key.extend([3, index(w.bones[0]), index(w.bones[1]), bits(w.weight)]);
+            key.extend(w.c.map(bits));
+            key.extend(w.r0.map(bits));
+            key.extend(w.r1.map(bits));
+        }
+    }
+    key
+}
+
+/// Simulates a FIFO vertex cache and returns the number of misses per triangle.
+pub(crate) fn acmr(faces: &[u32], cache_size: usize) -> f32 {
+    if faces.len() < 3 {
+        return 0.0;
+    }
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for &f in faces {
+        if !cache.contains(&f) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(f);
+        }
+    }
+    misses as f32 / (faces.len() / 3) as f32
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: usize, cache_size: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache = match cache_position {
+        Some(p) if p < 3 => 0.75,
+        Some(p) => (1.0 - (p - 3) as f32 / (cache_size - 3) as f32).powf(1.5),
+        None => 0.0,
+    };
+    cache + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Orders the triangles of `faces` for vertex cache reuse.
+fn forsyth(faces: &[u32], cache_size: usize) -> Vec<u32> {
+    let cache_size = cache_size.max(4);
+    let triangles = faces.len() / 3;
+    if triangles < 2 {
+        return faces.to_vec();
+    }
+    let mut local = HashMap::new();
+    let indices = faces
+        .iter()
+        .map(|&f| {
+            let next = local.len();
+            *local.entry(f).or_insert(next)
+        })
+        .collect::<Vec<_>>();
+    let vertices = local.len();
+    let mut adjacency = vec![vec![]; vertices];
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for &v in tri {
+            adjacency[v].push(t);
+        }
+    }
+    let mut remaining = adjacency.iter().map(|a| a.len()).collect::<Vec<_>>();
+    let mut position = vec![None; vertices];
+    let mut scores = (0..vertices)
+        .map(|v| vertex_score(None, remaining[v], cache_size))
+        .collect::<Vec<_>>();
+    let triangle_score = |t: usize, scores: &[f32]| -> f32 {
+        indices[t * 3..t * 3 + 3].iter().map(|&v| scores[v]).sum()
+    };
+    let mut emitted = vec![false; triangles];
+    let mut cache: Vec<usize> = Vec::with_capacity(cache_size + 3);
+    let mut order = Vec::with_capacity(faces.len());
+    let mut cursor = 0;
+    let mut best = None;
+    for _ in 0..triangles {
+        let t = match best.take() {
+            Some(t) => t,
+            None => {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                // only look ahead a little so that the fallback stays linear
+                (cursor..triangles.min(cursor + 64))
+                    .filter(|&c| !emitted[c])
+                    .map(|c| (c, triangle_score(c, &scores)))
+                    .fold((cursor, f32::MIN), |a, b| if b.1 > a.1 { b } else { a })
+                    .0
+            }
+        };
+        emitted[t] = true;
+        let tri = &indices[t * 3..t * 3 + 3];
+        order.extend_from_slice(&faces[t * 3..t * 3 + 3]);
+        for &v in tri {
+            remaining[v] -= 1;
+            adjacency[v].retain(|&x| x != t);
+            cache.retain(|&x| x != v);
+        }
+        for &v in tri.iter().rev() {
+            cache.insert(0, v);
+        }
+        let evicted = cache.split_off(cache.len().min(cache_size));
+        for v in evicted {
+            position[v] = None;
+            scores[v] = vertex_score(None, remaining[v], cache_size);
+        }
+        for (p, &v) in cache.iter().enumerate() {
+            position[v] = Some(p);
+            scores[v] = vertex_score(Some(p), remaining[v], cache_size);
+        }
+        let mut best_score = -1.0;
+        for &v in cache.iter() {
+            for &c in adjacency[v].iter() {
+                let s = triangle_score(c, &scores);
+                if s > best_score {
+                    best_score = s;
+                    best = Some(c);
+                }
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn triangles(pmx: &Pmx) -> Vec<Vec<[u32; 3]>> {
+        let mut faces = pmx.faces.as_slice();
+        pmx.materials
+            .iter()
+            .map(|m| {
+                let (range, rest) = faces.split_at(m.index_count as usize);
+                faces = rest;
+                let mut tris = range
+                    .chunks_exact(3)
+                    .map(|t| {
+                        let p = |i: u32| pmx.vertices[i as usize].position.map(bits);
+                        let mut t = [p(t[0]), p(t[1]), p(t[2])];
+                        t.sort();
+                        [t[0][0], t[1][0], t[2][0]]
+                    })
+                    .collect::<Vec<_>>();
+                tris.sort();
+                tris
+            })
+            .collect()
+    }
+
+    fn morph_positions(pmx: &Pmx) -> Vec<Vec<[u32; 6]>> {
+        pmx.morphs
+            .iter()
+            .map(|m| match &m.kind {
+                morph::Kind::Vertex(offsets) => {
+                    let mut v = offsets
+                        .iter()
+                        .map(|o| {
+                            let p = pmx.vertices[o.vertex.unwrap()].position.map(bits);
+                            let d = o.offset.map(bits);
+                            [p[0], p[1], p[2], d[0], d[1], d[2]]
+                        })
+                        .collect::<Vec<_>>();
+                    v.sort();
+                    v.dedup();
+                    v
+                }
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optimize_alicia() {
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let report = pmx.optimize(&OptimizeOptions::default());
+        assert!(pmx.vertices.len() == original.vertices.len() - report.welded);
+        assert!(pmx.faces.len() == original.faces.len());
+        assert!(report.acmr_after < report.acmr_before);
+        assert!(triangles(&pmx) == triangles(&original));
+        assert!(morph_positions(&pmx) == morph_positions(&original));
+    }
+
+    #[test]
+    fn weld() {
+        let mut pmx = read_pmx();
+        let vertex = pmx.vertices[pmx.faces[0] as usize].clone();
+        let len = pmx.vertices.len();
+        pmx.insert_vertex(len, vertex).unwrap();
+        pmx.faces[0] = len as u32;
+        let options = OptimizeOptions {
+            reorder_faces: false,
+            reorder_vertices: false,
+            ..Default::default()
+        };
+        let report = pmx.optimize(&options);
+        assert!(report.welded >= 1);
+        assert!(pmx.faces.iter().all(|&f| (f as usize) < pmx.vertices.len()));
+    }
+
+    #[test]
+    fn cache_miss_ratio() {
+        assert!(acmr(&[0, 1, 2, 0, 2, 3], 32) == 2.0);
+        assert!(acmr(&[0, 1, 2, 3, 4, 5], 32) == 3.0);
+    }
+}
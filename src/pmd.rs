@@ -0,0 +1,738 @@
+//! Reading of PMD files, the format PMX replaced, into the PMX model.
+//!
+//! PMD stores fixed-length Shift-JIS strings, two bones per vertex, IK chains in
+//! a list of their own, morphs as offsets of a base morph and rigid positions
+//! relative to their bone. Everything is converted to its PMX equivalent, and
+//! the English names, toon textures and physics that older files lack are
+//! treated as empty.
+
+use crate::reader::Error;
+use crate::texture::shared_toon_name;
+use crate::*;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Reads a PMD file and converts it to a PMX model.
+pub fn read<T: Read>(reader: T) -> Result<Pmx, Error> {
+    Reader { reader }.read()
+}
+
+struct Reader<T> {
+    reader: T,
+}
+
+/// Texture paths of the materials and toon list, each stored once.
+struct Textures {
+    paths: Vec<PathBuf>,
+    map: HashMap<String, usize>,
+}
+
+impl Textures {
+    fn index(&mut self, path: &str) -> usize {
+        let paths = &mut self.paths;
+        *self.map.entry(path.into()).or_insert_with(|| {
+            paths.push(path.into());
+            paths.len() - 1
+        })
+    }
+}
+
+impl<T> Reader<T>
+where
+    T: Read,
+{
+    fn read_bin<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut buffer = [0; N];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bin::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.read_bin()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.read_bin()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.read_bin()?))
+    }
+
+    fn read_vec<const N: usize>(&mut self) -> Result<[f32; N], Error> {
+        let mut v = [0.0; N];
+        for v in v.iter_mut() {
+            *v = self.read_f32()?;
+        }
+        Ok(v)
+    }
+
+    /// Reads a zero-terminated Shift-JIS string padded to `N` bytes.
+    fn read_string<const N: usize>(&mut self) -> Result<String, Error> {
+        let buffer = self.read_bin::<N>()?;
+        let len = buffer.iter().position(|&b| b == 0).unwrap_or(N);
+        let (s, _, _) = encoding_rs::SHIFT_JIS.decode(&buffer[..len]);
+        Ok(s.into_owned())
+    }
+
+    /// Reads a bone index, where `0xFFFF` means none.
+    fn read_bone_index(&mut self) -> Result<Option<usize>, Error> {
+        let v = self.read_u16()?;
+        Ok((v != 0xFFFF).then_some(v as usize))
+    }
+
+    /// Reads the flag that starts an optional section, or `None` at the end of the file.
+    fn read_optional(&mut self) -> Result<Option<u8>, Error> {
+        match self.read_u8() {
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            r => r.map(Some),
+        }
+    }
+
+    fn read(&mut self) -> Result<Pmx, Error> {
+        if self.read_bin::<3>()? != *b"Pmd" {
+            return Err(Error::InvalidData("magic number".into()));
+        }
+        if self.read_f32()? != 1.0 {
+            return Err(Error::UnsupportedVersion);
+        }
+        let mut model_info = ModelInfo {
+            name: self.read_string::<20>()?,
+            name_en: String::new(),
+            comment: self.read_string::<256>()?,
+            comment_en: String::new(),
+        };
+
+        let len = self.read_u32()?;
+        let vertices = (0..len)
+            .map(|_| self.vertex())
+            .collect::<Result<Vec<_>, Error>>()?;
+        let len = self.read_u32()?;
+        let faces = (0..len)
+            .map(|_| Ok(self.read_u16()? as u32))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut textures = Textures {
+            paths: vec![],
+            map: HashMap::new(),
+        };
+        let len = self.read_u32()?;
+        let mut toons = vec![];
+        let mut materials = vec![];
+        for i in 0..len {
+            let (material, toon) = self.material(i as usize, &mut textures)?;
+            materials.push(material);
+            toons.push(toon);
+        }
+
+        let len = self.read_u16()?;
+        let mut bones = vec![];
+        let mut tails = vec![];
+        for _ in 0..len {
+            let (bone, tail) = self.bone()?;
+            bones.push(bone);
+            tails.push(tail);
+        }
+        // twist bones turn around the axis towards their tail
+        for (i, tail) in tails.into_iter().enumerate() {
+            if let Some(tail) = tail.and_then(|t| bones.get(t)) {
+                let axis = math::normalize(math::sub(tail.position, bones[i].position));
+                bones[i].fixed_pole = Some(axis);
+            }
+        }
+        let len = self.read_u16()?;
+        for _ in 0..len {
+            let (bone, ik) = self.ik()?;
+            let bone = bones
+                .get_mut(bone)
+                .ok_or_else(|| Error::InvalidData("ik::bone".into()))?;
+            bone.ik = Some(ik);
+            bone.translatable = true;
+        }
+        let knees = bones
+            .iter()
+            .map(|b| b.name.contains("ひざ"))
+            .collect::<Vec<_>>();
+        for link in bones
+            .iter_mut()
+            .flat_map(|b| b.ik.iter_mut())
+            .flat_map(|ik| ik.links.iter_mut())
+        {
+            // PMD has no angle limits, MMD restricts knees to bending backwards
+            if link.bone.is_some_and(|b| knees.get(b) == Some(&true)) {
+                link.limits = Some(AngleLimit {
+                    lower: [-std::f32::consts::PI, 0.0, 0.0],
+                    upper: [-0.5f32.to_radians(), 0.0, 0.0],
+                });
+            }
+        }
+
+        let len = self.read_u16()?;
+        let mut morph_map = vec![];
+        let mut base: Option<Vec<usize>> = None;
+        let mut morphs = vec![];
+        for _ in 0..len {
+            let name = self.read_string::<20>()?;
+            let len = self.read_u32()?;
+            let panel = self.read_u8()?;
+            let offsets = (0..len)
+                .map(|_| Ok((self.read_u32()? as usize, self.read_vec()?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            let Some(base) = &base else {
+                base = Some(offsets.into_iter().map(|(i, _)| i).collect());
+                morph_map.push(None);
+                continue;
+            };
+            let offsets = offsets
+                .into_iter()
+                .map(|(i, offset)| {
+                    let vertex = *base
+                        .get(i)
+                        .ok_or_else(|| Error::InvalidData("morph::vertex".into()))?;
+                    Ok(morph::Vertex {
+                        vertex: Some(vertex),
+                        offset,
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            morph_map.push(Some(morphs.len()));
+            morphs.push(Morph {
+                name,
+                name_en: String::new(),
+                panel: match panel {
+                    1 => Panel::Eyebrow,
+                    2 => Panel::Eye,
+                    3 => Panel::Mouth,
+                    _ => Panel::Other,
+                },
+                kind: morph::Kind::Vertex(offsets),
+            });
+        }
+
+        let mut display_groups = vec![
+            DisplayGroup {
+                name: "Root".into(),
+                name_en: "Root".into(),
+                special: true,
+                elements: bones
+                    .first()
+                    .map(|_| DisplayElement::Bone(Some(0)))
+                    .into_iter()
+                    .collect(),
+            },
+            DisplayGroup {
+                name: "表情".into(),
+                name_en: "Exp".into(),
+                special: true,
+                elements: vec![],
+            },
+        ];
+        let len = self.read_u8()?;
+        for _ in 0..len {
+            let i = self.read_u16()? as usize;
+            if let Some(&Some(m)) = morph_map.get(i) {
+                display_groups[1]
+                    .elements
+                    .push(DisplayElement::Morph(Some(m)));
+            }
+        }
+        let len = self.read_u8()?;
+        for _ in 0..len {
+            let name = self.read_string::<50>()?;
+            display_groups.push(DisplayGroup {
+                name: name.trim_end().into(),
+                name_en: String::new(),
+                special: false,
+                elements: vec![],
+            });
+        }
+        let len = self.read_u32()?;
+        for _ in 0..len {
+            let bone = self.read_bone_index()?;
+            let group = self.read_u8()? as usize;
+            // frames are numbered from 1 after the two special groups
+            let group = display_groups
+                .get_mut(group + 1)
+                .filter(|_| group > 0)
+                .ok_or_else(|| Error::InvalidData("display_group".into()))?;
+            group.elements.push(DisplayElement::Bone(bone));
+        }
+
+        let mut toon_names = (0..10).map(shared_toon_name).collect::<Vec<_>>();
+        let mut rigids = vec![];
+        let mut joints = vec![];
+        if let Some(english) = self.read_optional()? {
+            if english == 1 {
+                model_info.name_en = self.read_string::<20>()?;
+                model_info.comment_en = self.read_string::<256>()?;
+                for bone in bones.iter_mut() {
+                    bone.name_en = self.read_string::<20>()?;
+                }
+                for morph in morphs.iter_mut() {
+                    morph.name_en = self.read_string::<20>()?;
+                }
+                for group in display_groups.iter_mut().skip(2) {
+                    group.name_en = self.read_string::<50>()?.trim_end().into();
+                }
+            }
+            for name in toon_names.iter_mut() {
+                *name = self.read_string::<100>()?;
+            }
+            let len = self.read_u32()?;
+            for _ in 0..len {
+                rigids.push(self.rigid(&bones)?);
+            }
+            let len = self.read_u32()?;
+            for _ in 0..len {
+                joints.push(self.joint()?);
+            }
+        }
+
+        for (material, toon) in materials.iter_mut().zip(toons) {
+            material.toon = match toon.and_then(|t| Some((t, toon_names.get(t)?))) {
+                Some((t, name)) if *name == shared_toon_name(t as u32) => Toon::Shared(t as u32),
+                Some((_, name)) => Toon::Texture(Some(textures.index(name))),
+                None => Toon::Texture(None),
+            };
+        }
+
+        let mut pmx = Pmx {
+            header: Header {
+                version: 2.0,
+                encoding: Encoding::Utf16,
+                extended_uv: 0,
+                vertex_index_size: 1,
+                texture_index_size: 1,
+                material_index_size: 1,
+                bone_index_size: 1,
+                morph_index_size: 1,
+                rigid_index_size: 1,
+            },
+            model_info,
+            vertices,
+            faces,
+            textures: textures.paths,
+            materials,
+            bones,
+            morphs,
+            display_groups,
+            rigids,
+            joints,
+        };
+        pmx.update_index_sizes();
+        Ok(pmx)
+    }
+
+    fn vertex(&mut self) -> Result<Vertex, Error> {
+        let position = self.read_vec()?;
+        let normal = self.read_vec()?;
+        let uv = self.read_vec()?;
+        let bones = [self.read_bone_index()?, self.read_bone_index()?];
+        let weight = self.read_u8()?.min(100) as f32 / 100.0;
+        let no_edge = self.read_u8()?;
+        let weight = if weight == 1.0 || bones[0] == bones[1] {
+            Weight::Bdef1(Bdef1 { bone: bones[0] })
+        } else if weight == 0.0 {
+            Weight::Bdef1(Bdef1 { bone: bones[1] })
+        } else {
+            Weight::Bdef2(Bdef2 { bones, weight })
+        };
+        Ok(Vertex {
+            position,
+            normal,
+            uv,
+            extended_uv: vec![],
+            weight,
+            edge_ratio: if no_edge == 0 { 1.0 } else { 0.0 },
+        })
+    }
+
+    /// Reads a material and the index into the toon texture list it uses.
+    fn material(
+        &mut self,
+        index: usize,
+        textures: &mut Textures,
+    ) -> Result<(Material, Option<usize>), Error> {
+        let diffuse: [f32; 3] = self.read_vec()?;
+        let alpha = self.read_f32()?;
+        let specular_power = self.read_f32()?;
+        let specular = self.read_vec()?;
+        let ambient = self.read_vec()?;
+        let toon = self.read_u8()?;
+        let edge = self.read_u8()? == 1;
+        let index_count = self.read_u32()?;
+        let path = self.read_string::<20>()?;
+        let mut texture = None;
+        let mut sphere = None;
+        let mut sphere_mode = SphereMode::None;
+        // "texture.bmp*sphere.sph", where either part may be missing
+        for part in path.split('*').filter(|p| !p.is_empty()) {
+            let extension = part.rsplit('.').next().unwrap_or("").to_lowercase();
+            match extension.as_str() {
+                "sph" => (sphere, sphere_mode) = (Some(textures.index(part)), SphereMode::Mul),
+                "spa" => (sphere, sphere_mode) = (Some(textures.index(part)), SphereMode::Add),
+                _ => texture = Some(textures.index(part)),
+            }
+        }
+        // MMD draws both sides of translucent materials and no self shadow for an alpha of 0.98
+        let self_shadow = alpha != 0.98;
+        let material = Material {
+            name: format!("材質{}", index + 1),
+            name_en: format!("Material{}", index + 1),
+            diffuse: [diffuse[0], diffuse[1], diffuse[2], alpha],
+            specular,
+            specular_power,
+            ambient,
+            both: alpha < 1.0,
+            ground_shadow: true,
+            self_shadow_map: self_shadow,
+            self_shadow,
+            edge,
+            edge_color: [0.0, 0.0, 0.0, 1.0],
+            edge_size: 1.0,
+            texture,
+            sphere,
+            sphere_mode,
+            toon: Toon::Texture(None),
+            memo: String::new(),
+            index_count,
+        };
+        Ok((material, (toon < 10).then_some(toon as usize)))
+    }
+
+    /// Reads a bone, and the bone its tail points to if it is a twist bone.
+    fn bone(&mut self) -> Result<(Bone, Option<usize>), Error> {
+        let name = self.read_string::<20>()?;
+        let parent = self.read_bone_index()?;
+        let tail = self.read_u16()?;
+        let kind = self.read_u8()?;
+        let target = self.read_bone_index()?;
+        let position = self.read_vec()?;
+        // a tail of 0 also means none, since no bone points at the first one
+        let tail_bone = (tail != 0 && tail != 0xFFFF).then_some(tail as usize);
+        let addition = match kind {
+            // rotation influenced, following the target completely
+            5 => Some((target, 1.0)),
+            // rotation linked, with the ratio in percent stored in place of the tail
+            9 => Some((target, tail as f32 / 100.0)),
+            _ => None,
+        };
+        let bone = Bone {
+            name,
+            name_en: String::new(),
+            position,
+            parent,
+            deform_hierarchy: 0,
+            connected_to: ConnectedTo::Bone(tail_bone.filter(|_| kind != 9)),
+            rotatable: kind != 7,
+            translatable: kind == 1,
+            visibility: !matches!(kind, 6 | 7),
+            operable: !matches!(kind, 6 | 7),
+            ik: None,
+            addition: addition.map(|(bone, ratio)| Addition {
+                rotation: true,
+                translation: false,
+                local: false,
+                bone,
+                ratio,
+            }),
+            after_physics: false,
+            fixed_pole: None,
+            local_pole: None,
+            external_parent: None,
+        };
+        Ok((bone, tail_bone.filter(|_| kind == 8)))
+    }
+
+    /// Reads an IK chain and the bone it belongs to.
+    fn ik(&mut self) -> Result<(usize, Ik), Error> {
+        let bone = self.read_u16()? as usize;
+        let target = self.read_bone_index()?;
+        let len = self.read_u8()?;
+        let loop_count = self.read_u16()? as u32;
+        // PMD stores the limit per iteration in units of 4 radians
+        let angle = self.read_f32()? * 4.0;
+        let links = (0..len)
+            .map(|_| {
+                Ok(IkLink {
+                    bone: self.read_bone_index()?,
+                    limits: None,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok((
+            bone,
+            Ik {
+                bone: target,
+                loop_count,
+                angle,
+                links,
+            },
+        ))
+    }
+
+    fn rigid(&mut self, bones: &[Bone]) -> Result<Rigid, Error> {
+        let name = self.read_string::<20>()?;
+        let bone = self.read_bone_index()?;
+        let group = self.read_u8()?;
+        let non_collision_groups = self.read_u16()?;
+        let shape = match self.read_u8()? {
+            0 => rigid::Shape::Sphere,
+            1 => rigid::Shape::Box,
+            2 => rigid::Shape::Capsule,
+            _ => return Err(Error::InvalidData("rigid::shape".into())),
+        };
+        let size = self.read_vec()?;
+        // relative to the bone, or to the first bone for rigids without one
+        let origin = bones
+            .get(bone.unwrap_or(0))
+            .map(|b| b.position)
+            .unwrap_or_default();
+        let position = math::add(origin, self.read_vec()?);
+        let rotation = self.read_vec()?;
+        let mass = self.read_f32()?;
+        let dump_translation = self.read_f32()?;
+        let dump_rotation = self.read_f32()?;
+        let repulsive = self.read_f32()?;
+        let friction = self.read_f32()?;
+        let method = match self.read_u8()? {
+            0 => rigid::Method::Static,
+            1 => rigid::Method::Dynamic,
+            2 => rigid::Method::DynamicWithBone,
+            _ => return Err(Error::InvalidData("rigid::method".into())),
+        };
+        Ok(Rigid {
+            name,
+            name_en: String::new(),
+            bone,
+            group,
+            non_collision_groups,
+            shape,
+            size,
+            position,
+            rotation,
+            mass,
+            dump_translation,
+            dump_rotation,
+            repulsive,
+            friction,
+            method,
+        })
+    }
+
+    fn joint(&mut self) -> Result<Joint, Error> {
+        let name = self.read_string::<20>()?;
+        let rigid = |v: u32| (v != u32::MAX).then_some(v as usize);
+        let rigids = [rigid(self.read_u32()?), rigid(self.read_u32()?)];
+        Ok(Joint {
+            name,
+            name_en: String::new(),
+            rigids,
+            position: self.read_vec()?,
+            rotation: self.read_vec()?,
+            limit_translation: AngleLimit {
+                lower: self.read_vec()?,
+                upper: self.read_vec()?,
+            },
+            limit_rotation: AngleLimit {
+                lower: self.read_vec()?,
+                upper: self.read_vec()?,
+            },
+            spring_translation: self.read_vec()?,
+            spring_rotation: self.read_vec()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_string(data: &mut Vec<u8>, s: &str, len: usize) {
+        let (bytes, _, _) = encoding_rs::SHIFT_JIS.encode(s);
+        let mut bytes = bytes.into_owned();
+        bytes.resize(len, 0);
+        data.extend_from_slice(&bytes);
+    }
+
+    fn push_f32s(data: &mut Vec<u8>, values: &[f32]) {
+        for v in values {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    // Returns a model with a knee IK chain and a twist bone, and the length of
+    // the part before the optional sections.
+    fn sample_pmd() -> (Vec<u8>, usize) {
+        let mut data = b"Pmd".to_vec();
+        push_f32s(&mut data, &[1.0]);
+        push_string(&mut data, "テスト", 20);
+        push_string(&mut data, "コメント", 256);
+
+        data.extend_from_slice(&3u32.to_le_bytes());
+        for (bones, weight, edge) in [([0u16, 1], 100, 0), ([1, 2], 50, 1), ([2, 2], 0, 0)] {
+            push_f32s(&mut data, &[0.0; 8]);
+            data.extend_from_slice(&bones[0].to_le_bytes());
+            data.extend_from_slice(&bones[1].to_le_bytes());
+            data.extend_from_slice(&[weight, edge]);
+        }
+        data.extend_from_slice(&3u32.to_le_bytes());
+        for f in [0u16, 1, 2] {
+            data.extend_from_slice(&f.to_le_bytes());
+        }
+
+        data.extend_from_slice(&1u32.to_le_bytes());
+        push_f32s(
+            &mut data,
+            &[1.0, 1.0, 1.0, 0.5, 5.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.5],
+        );
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&3u32.to_le_bytes());
+        push_string(&mut data, "tex.bmp*env.sph", 20);
+
+        // name, parent, tail, type, IK target and position
+        let bones = [
+            ("センター", 0xFFFFu16, 1u16, 1u8, 0u16, [0.0f32, 1.0, 0.0]),
+            ("左ひざ", 0, 2, 0, 0, [0.0, 0.5, 0.0]),
+            ("左足首", 1, 0, 0, 0, [0.0, 0.0, 0.0]),
+            ("左足ＩＫ", 0xFFFF, 0, 2, 2, [0.0, 0.0, 0.0]),
+            ("左腕捩", 0, 2, 8, 0, [1.0, 0.0, 0.0]),
+        ];
+        data.extend_from_slice(&(bones.len() as u16).to_le_bytes());
+        for (name, parent, tail, kind, target, position) in bones {
+            push_string(&mut data, name, 20);
+            data.extend_from_slice(&parent.to_le_bytes());
+            data.extend_from_slice(&tail.to_le_bytes());
+            data.push(kind);
+            data.extend_from_slice(&target.to_le_bytes());
+            push_f32s(&mut data, &position);
+        }
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&40u16.to_le_bytes());
+        push_f32s(&mut data, &[0.5]);
+        data.extend_from_slice(&1u16.to_le_bytes());
+
+        data.extend_from_slice(&2u16.to_le_bytes());
+        push_string(&mut data, "base", 20);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.push(0);
+        for i in [0u32, 2] {
+            data.extend_from_slice(&i.to_le_bytes());
+            push_f32s(&mut data, &[0.0; 3]);
+        }
+        push_string(&mut data, "あ", 20);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.push(3);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        push_f32s(&mut data, &[0.0, 0.0, 0.1]);
+
+        data.push(1);
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.push(1);
+        push_string(&mut data, "足\n", 50);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.push(1);
+        let required = data.len();
+
+        data.push(1);
+        push_string(&mut data, "test", 20);
+        push_string(&mut data, "comment", 256);
+        for name in ["center", "knee_L", "ankle_L", "leg IK_L", "arm twist_L"] {
+            push_string(&mut data, name, 20);
+        }
+        push_string(&mut data, "a", 20);
+        push_string(&mut data, "Legs", 50);
+        for i in 0..10 {
+            push_string(&mut data, &shared_toon_name(i), 100);
+        }
+
+        data.extend_from_slice(&1u32.to_le_bytes());
+        push_string(&mut data, "ひざ", 20);
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&0xFFFFu16.to_le_bytes());
+        data.push(1);
+        push_f32s(&mut data, &[1.0, 1.0, 1.0, 0.0, 0.1, 0.0, 0.0, 0.0, 0.0]);
+        push_f32s(&mut data, &[1.0, 0.5, 0.5, 0.0, 0.5]);
+        data.push(1);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        (data, required)
+    }
+
+    #[test]
+    fn sample() {
+        let (data, _) = sample_pmd();
+        let pmx = read(data.as_slice()).unwrap();
+        assert!(pmx.model_info.name == "テスト");
+        assert!(pmx.model_info.name_en == "test");
+
+        assert!(pmx.vertices[0].weight == Weight::Bdef1(Bdef1 { bone: Some(0) }));
+        assert!(matches!(
+            pmx.vertices[1].weight,
+            Weight::Bdef2(Bdef2 { weight: 0.5, .. })
+        ));
+        assert!(pmx.vertices[2].weight == Weight::Bdef1(Bdef1 { bone: Some(2) }));
+        assert!(pmx.vertices[1].edge_ratio == 0.0);
+        assert!(pmx.faces == [0, 1, 2]);
+
+        let material = &pmx.materials[0];
+        assert!(pmx.textures == [PathBuf::from("tex.bmp"), PathBuf::from("env.sph")]);
+        assert!(material.texture == Some(0) && material.sphere == Some(1));
+        assert!(material.sphere_mode == SphereMode::Mul);
+        assert!(material.toon == Toon::Shared(0));
+        assert!(material.diffuse == [1.0, 1.0, 1.0, 0.5] && material.both && material.edge);
+
+        assert!(pmx.bones[1].name_en == "knee_L");
+        assert!(pmx.bones[0].translatable);
+        assert!(pmx.bones[1].connected_to == ConnectedTo::Bone(Some(2)));
+        assert!(pmx.bones[2].connected_to == ConnectedTo::Bone(None));
+        assert!(pmx.bones[4].fixed_pole == Some([-1.0, 0.0, 0.0]));
+        let ik = pmx.bones[3].ik.as_ref().unwrap();
+        assert!(ik.bone == Some(2) && ik.loop_count == 40 && ik.angle == 2.0);
+        assert!(ik.links[0].bone == Some(1) && ik.links[0].limits.is_some());
+
+        assert!(pmx.morphs.len() == 1);
+        assert!(pmx.morphs[0].panel == Panel::Mouth);
+        assert!(pmx.morphs[0].name_en == "a");
+        assert!(
+            pmx.morphs[0].kind
+                == morph::Kind::Vertex(vec![morph::Vertex {
+                    vertex: Some(2),
+                    offset: [0.0, 0.0, 0.1],
+                }])
+        );
+
+        assert!(pmx.display_groups.len() == 3);
+        assert!(pmx.display_groups[1].elements == [DisplayElement::Morph(Some(0))]);
+        assert!(pmx.display_groups[2].name == "足" && pmx.display_groups[2].name_en == "Legs");
+        assert!(pmx.display_groups[2].elements == [DisplayElement::Bone(Some(1))]);
+
+        assert!(pmx.rigids.len() == 1);
+        assert!(pmx.rigids[0].position == [0.0, 0.6, 0.0]);
+        assert!(pmx.rigids[0].method == rigid::Method::Dynamic);
+        assert!(pmx.validate().is_empty());
+
+        let mut written = vec![];
+        crate::write(&mut written, &pmx).unwrap();
+        assert!(crate::read(written.as_slice()).unwrap() == pmx);
+    }
+
+    #[test]
+    fn optional_sections() {
+        let (data, required) = sample_pmd();
+        let pmx = read(&data[..required]).unwrap();
+        assert!(pmx.model_info.name_en.is_empty());
+        assert!(pmx.materials[0].toon == Toon::Shared(0));
+        assert!(pmx.rigids.is_empty());
+        assert!(read(&data[..required - 1]).is_err());
+        assert!(read(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -21,14 +21,21 @@ pub(crate) struct Reader<T> {
     reader: T,
     encoding: Encoding,
     extended_uv: usize,
-    vertex_index: Vec<u8>,
-    tex_index: Vec<u8>,
-    mat_index: Vec<u8>,
-    bone_index: Vec<u8>,
-    morph_index: Vec<u8>,
-    rig_index: Vec<u8>,
+    vertex_index: usize,
+    tex_index: usize,
+    mat_index: usize,
+    bone_index: usize,
+    morph_index: usize,
+    rig_index: usize,
+    buffer: Vec<u8>,
 }
 
+/// Largest number of bytes read at once when decoding the faces section.
+const FACE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Most vertices reserved before any of them has been read, about 1 MiB.
+const VERTEX_PREALLOC: usize = (1 << 20) / std::mem::size_of::<Vertex>();
+
 impl<T> Reader<T>
 where
     T: Read,
@@ -38,25 +45,19 @@ where
             reader,
             encoding: Encoding::Utf16,
             extended_uv: 0,
-            vertex_index: vec![],
-            tex_index: vec![],
-            mat_index: vec![],
-            bone_index: vec![],
-            morph_index: vec![],
-            rig_index: vec![],
+            vertex_index: 0,
+            tex_index: 0,
+            mat_index: 0,
+            bone_index: 0,
+            morph_index: 0,
+            rig_index: 0,
+            buffer: vec![],
         }
     }
 
     pub fn read(&mut self) -> Result<Pmx, Error> {
         let header = self.header()?;
-        self.encoding = header.encoding;
-        self.extended_uv = header.extended_uv as _;
-        self.vertex_index = vec![0u8; header.vertex_index_size as usize];
-        self.tex_index = vec![0u8; header.texture_index_size as usize];
-        self.mat_index = vec![0u8; header.material_index_size as usize];
-        self.bone_index = vec![0u8; header.bone_index_size as usize];
-        self.morph_index = vec![0u8; header.morph_index_size as usize];
-        self.rig_index = vec![0u8; header.rigid_index_size as usize];
+        self.set_header(&header);
         Ok(Pmx {
             header,
             model_info: self.model_info()?,
@@ -72,12 +73,39 @@ where
         })
     }
 
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> T {
+        self.reader
+    }
+
+    /// Prepares decoding of the sections that follow `header`.
+    pub fn set_header(&mut self, header: &Header) {
+        self.encoding = header.encoding;
+        self.extended_uv = header.extended_uv as _;
+        self.vertex_index = header.vertex_index_size as _;
+        self.tex_index = header.texture_index_size as _;
+        self.mat_index = header.material_index_size as _;
+        self.bone_index = header.bone_index_size as _;
+        self.morph_index = header.morph_index_size as _;
+        self.rig_index = header.rigid_index_size as _;
+    }
+
     fn read_bin<const N: usize>(&mut self) -> Result<[u8; N], Error> {
         let mut buffer = [0; N];
         self.reader.read_exact(&mut buffer)?;
         Ok(buffer)
     }
 
+    /// Reads `len` bytes into the internal buffer.
+    fn read_buffer(&mut self, len: usize) -> Result<&[u8], Error> {
+        self.buffer.resize(len, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+
     fn read_u8(&mut self) -> Result<u8, Error> {
         Ok(self.read_bin::<1>()?[0])
     }
@@ -87,7 +115,7 @@ where
         Ok(u16::from_le_bytes(self.read_bin::<SIZE>()?))
     }
 
-    fn read_u32(&mut self) -> Result<u32, Error> {
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
         const SIZE: usize = std::mem::size_of::<u32>();
         Ok(u32::from_le_bytes(self.read_bin::<SIZE>()?))
     }
@@ -103,15 +131,9 @@ where
     }
 
     fn read_vec<const N: usize>(&mut self) -> Result<[f32; N], Error> {
-        let mut buffer = [0.0f32; N];
-        for i in 0..N {
-            buffer[i] = self.read_f32()?;
-        }
-        Ok(buffer)
-    }
-
-    fn read_vec2(&mut self) -> Result<[f32; 2], Error> {
-        self.read_vec::<2>()
+        let mut buffer = [0u8; 16];
+        self.reader.read_exact(&mut buffer[..N * 4])?;
+        Ok(Decoder(&buffer).vec())
     }
 
     fn read_vec3(&mut self) -> Result<[f32; 3], Error> {
@@ -127,90 +149,49 @@ where
         let mut buffer = vec![0u8; len];
         self.reader.read_exact(&mut buffer)?;
         let s = match self.encoding {
-            Encoding::Utf16 => unsafe {
-                let buffer = std::slice::from_raw_parts(buffer.as_ptr() as *const u16, len / 2);
+            Encoding::Utf16 => {
+                let buffer = buffer
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
                 String::from_utf16_lossy(&buffer)
-            },
+            }
             Encoding::Utf8 => String::from_utf8_lossy(&buffer).to_string(),
         };
         Ok(s)
     }
 
-    fn read_signed_index(
-        &mut self,
-        f: impl FnOnce(&mut Self) -> Result<&Vec<u8>, Error>,
-    ) -> Result<Option<usize>, Error> {
-        let buffer = f(self)?;
-        match buffer.len() {
-            1 => {
-                let v = i8::from_le_bytes([buffer[0]]);
-                Ok((v >= 0).then(|| v as usize))
-            }
-            2 => {
-                let v = i16::from_le_bytes([buffer[0], buffer[1]]);
-                Ok((v >= 0).then(|| v as usize))
-            }
-            4 => {
-                let v = i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
-                Ok((v >= 0).then(|| v as usize))
-            }
-            _ => unreachable!(),
-        }
+    fn read_signed_index(&mut self, size: usize) -> Result<Option<usize>, Error> {
+        let mut buffer = [0u8; 4];
+        self.reader.read_exact(&mut buffer[..size])?;
+        Ok(decode_signed_index(&buffer[..size]))
     }
 
     fn read_vertex_index(&mut self) -> Result<Option<usize>, Error> {
-        let buffer = &mut self.vertex_index;
+        let mut buffer = [0u8; 4];
+        let buffer = &mut buffer[..self.vertex_index];
         self.reader.read_exact(buffer)?;
-        match buffer.len() {
-            1 => {
-                let v = u8::from_le_bytes([buffer[0]]);
-                Ok(Some(v as usize))
-            }
-            2 => {
-                let v = u16::from_le_bytes([buffer[0], buffer[1]]);
-                Ok(Some(v as usize))
-            }
-            4 => {
-                let v = i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
-                Ok((v >= 0).then(|| v as usize))
-            }
-            _ => unreachable!(),
-        }
+        Ok(decode_vertex_index(buffer))
     }
 
     fn read_texture_index(&mut self) -> Result<Option<usize>, Error> {
-        self.read_signed_index(|this| {
-            this.reader.read_exact(&mut this.tex_index)?;
-            Ok(&this.tex_index)
-        })
+        self.read_signed_index(self.tex_index)
     }
 
     fn read_material_index(&mut self) -> Result<Option<usize>, Error> {
-        self.read_signed_index(|this| {
-            this.reader.read_exact(&mut this.mat_index)?;
-            Ok(&this.mat_index)
-        })
+        self.read_signed_index(self.mat_index)
     }
 
     fn read_bone_index(&mut self) -> Result<Option<usize>, Error> {
-        self.read_signed_index(|this| {
-            this.reader.read_exact(&mut this.bone_index)?;
-            Ok(&this.bone_index)
-        })
+        self.read_signed_index(self.bone_index)
     }
 
     fn read_morph_index(&mut self) -> Result<Option<usize>, Error> {
-        self.read_signed_index(|this| {
-            this.reader.read_exact(&mut this.morph_index)?;
-            Ok(&this.morph_index)
-        })
+        self.read_signed_index(self.morph_index)
     }
 
     fn read_rigid_index(&mut self) -> Result<Option<usize>, Error> {
-        self.read_signed_index(|this| {
-            this.reader.read_exact(&mut this.rig_index)?;
-            Ok(&this.rig_index)
-        })
+        self.read_signed_index(self.rig_index)
     }
 
     fn read_index_size(&mut self) -> Result<u8, Error> {
@@ -221,7 +202,7 @@ where
         }
     }
 
-    fn header(&mut self) -> Result<Header, Error> {
+    pub fn header(&mut self) -> Result<Header, Error> {
         let magic = self.read_bin::<4>()?;
         if magic != [b'P', b'M', b'X', b' '] {
             return Err(Error::InvalidData("magic number".into()));
@@ -249,7 +230,7 @@ where
         })
     }
 
-    fn model_info(&mut self) -> Result<ModelInfo, Error> {
+    pub fn model_info(&mut self) -> Result<ModelInfo, Error> {
         Ok(ModelInfo {
             name: self.read_string()?,
             name_en: self.read_string()?,
@@ -258,45 +239,45 @@ where
         })
     }
 
-    fn vertex(&mut self) -> Result<Vertex, Error> {
-        let position = self.read_vec3()?;
-        let normal = self.read_vec3()?;
-        let uv = self.read_vec2()?;
-        let extended_uv = (0..self.extended_uv)
-            .map(|_| self.read_vec4())
-            .collect::<Result<Vec<_>, Error>>()?;
-        let weight = match self.read_u8()? {
+    pub fn vertex(&mut self) -> Result<Vertex, Error> {
+        // the part up to the weight type has a fixed size, and the type gives the size of the rest
+        let bone = self.bone_index;
+        let extended_uv = self.extended_uv;
+        let mut data = Decoder(self.read_buffer(33 + 16 * extended_uv)?);
+        let position = data.vec();
+        let normal = data.vec();
+        let uv = data.vec();
+        let extended_uv = (0..extended_uv).map(|_| data.vec()).collect();
+        let kind = data.u8();
+        let size = weight_size(kind, bone).ok_or(Error::InvalidData("vertex::weight".into()))?;
+        let mut data = Decoder(self.read_buffer(size + 4)?);
+        let weight = match kind {
             0 => Weight::Bdef1(Bdef1 {
-                bone: self.read_bone_index()?,
+                bone: data.index(bone),
             }),
             1 => Weight::Bdef2(Bdef2 {
-                bones: [self.read_bone_index()?, self.read_bone_index()?],
-                weight: self.read_f32()?,
+                bones: [data.index(bone), data.index(bone)],
+                weight: data.f32(),
             }),
             2 => Weight::Bdef4(Bdef4 {
                 bones: [
-                    self.read_bone_index()?,
-                    self.read_bone_index()?,
-                    self.read_bone_index()?,
-                    self.read_bone_index()?,
-                ],
-                weights: [
-                    self.read_f32()?,
-                    self.read_f32()?,
-                    self.read_f32()?,
-                    self.read_f32()?,
+                    data.index(bone),
+                    data.index(bone),
+                    data.index(bone),
+                    data.index(bone),
                 ],
+                weights: data.vec(),
             }),
             3 => Weight::Sdef(Sdef {
-                bones: [self.read_bone_index()?, self.read_bone_index()?],
-                weight: self.read_f32()?,
-                c: self.read_vec3()?,
-                r0: self.read_vec3()?,
-                r1: self.read_vec3()?,
+                bones: [data.index(bone), data.index(bone)],
+                weight: data.f32(),
+                c: data.vec(),
+                r0: data.vec(),
+                r1: data.vec(),
             }),
-            _ => return Err(Error::InvalidData("vertex::weight".into())),
+            _ => unreachable!(),
         };
-        let edge_ratio = self.read_f32()?;
+        let edge_ratio = data.f32();
         Ok(Vertex {
             position,
             normal,
@@ -307,28 +288,67 @@ where
         })
     }
 
-    fn vertices(&mut self) -> Result<Vec<Vertex>, Error> {
-        let len = self.read_u32()?;
-        (0..len).map(|_| self.vertex()).collect()
+    pub fn vertices(&mut self) -> Result<Vec<Vertex>, Error> {
+        let len = self.read_u32()? as usize;
+        // the count is untrusted, so only a bounded amount is reserved up front and
+        // the rest grows as vertices are actually decoded
+        let mut vertices = Vec::with_capacity(len.min(VERTEX_PREALLOC));
+        for _ in 0..len {
+            vertices.push(self.vertex()?);
+        }
+        Ok(vertices)
     }
 
-    fn faces(&mut self) -> Result<Vec<u32>, Error> {
+    pub fn faces(&mut self) -> Result<Vec<u32>, Error> {
         let len = self.read_u32()?;
-        (0..len)
-            .map(|_| {
-                Ok(self
-                    .read_vertex_index()?
-                    .ok_or(Error::InvalidData("faces".into()))? as u32)
-            })
-            .collect()
+        let mut faces = vec![];
+        self.read_faces_into(len as usize, &mut faces)?;
+        Ok(faces)
+    }
+
+    /// Appends `len` vertex indices of the faces section to `faces`.
+    pub fn read_faces_into(&mut self, len: usize, faces: &mut Vec<u32>) -> Result<(), Error> {
+        let size = self.vertex_index;
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(FACE_BUFFER_SIZE / size);
+            let bytes = self.read_buffer(n * size)?;
+            faces.reserve(n);
+            match size {
+                1 => faces.extend(bytes.iter().map(|&v| v as u32)),
+                2 => faces.extend(
+                    bytes
+                        .chunks_exact(2)
+                        .map(|c| u16::from_le_bytes([c[0], c[1]]) as u32),
+                ),
+                4 => {
+                    // 4-byte indices are signed, and negative ones are invalid
+                    if bytes.chunks_exact(4).any(|c| c[3] & 0x80 != 0) {
+                        return Err(Error::InvalidData("faces".into()));
+                    }
+                    faces.extend(
+                        bytes
+                            .chunks_exact(4)
+                            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])),
+                    );
+                }
+                _ => unreachable!(),
+            }
+            remaining -= n;
+        }
+        Ok(())
     }
 
-    fn textures(&mut self) -> Result<Vec<PathBuf>, Error> {
+    pub fn texture(&mut self) -> Result<PathBuf, Error> {
+        Ok(self.read_string()?.into())
+    }
+
+    pub fn textures(&mut self) -> Result<Vec<PathBuf>, Error> {
         let len = self.read_u32()?;
-        (0..len).map(|_| Ok(self.read_string()?.into())).collect()
+        (0..len).map(|_| self.texture()).collect()
     }
 
-    fn material(&mut self) -> Result<Material, Error> {
+    pub fn material(&mut self) -> Result<Material, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let diffuse = self.read_vec4()?;
@@ -385,12 +405,12 @@ where
         })
     }
 
-    fn materials(&mut self) -> Result<Vec<Material>, Error> {
+    pub fn materials(&mut self) -> Result<Vec<Material>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.material()).collect()
     }
 
-    fn bone(&mut self) -> Result<Bone, Error> {
+    pub fn bone(&mut self) -> Result<Bone, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let position = self.read_vec3()?;
@@ -439,7 +459,7 @@ where
         let external_parent = (flags & 0x2000 == 0x2000)
             .then(|| {
                 let v = self.read_i32()?;
-                Ok::<_, Error>((v >= 0).then(|| v as usize))
+                Ok::<_, Error>((v >= 0).then_some(v as usize))
             })
             .transpose()?
             .flatten();
@@ -491,12 +511,12 @@ where
         })
     }
 
-    fn bones(&mut self) -> Result<Vec<Bone>, Error> {
+    pub fn bones(&mut self) -> Result<Vec<Bone>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.bone()).collect()
     }
 
-    fn morph(&mut self) -> Result<Morph, Error> {
+    pub fn morph(&mut self) -> Result<Morph, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let panel = match self.read_u8()? {
@@ -595,12 +615,12 @@ where
         })
     }
 
-    fn morphs(&mut self) -> Result<Vec<Morph>, Error> {
+    pub fn morphs(&mut self) -> Result<Vec<Morph>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.morph()).collect()
     }
 
-    fn display_group(&mut self) -> Result<DisplayGroup, Error> {
+    pub fn display_group(&mut self) -> Result<DisplayGroup, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let special = self.read_u8()? == 1;
@@ -623,12 +643,12 @@ where
         })
     }
 
-    fn display_groups(&mut self) -> Result<Vec<DisplayGroup>, Error> {
+    pub fn display_groups(&mut self) -> Result<Vec<DisplayGroup>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.display_group()).collect()
     }
 
-    fn rigid(&mut self) -> Result<Rigid, Error> {
+    pub fn rigid(&mut self) -> Result<Rigid, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let bone = self.read_bone_index()?;
@@ -673,12 +693,12 @@ where
         })
     }
 
-    fn rigids(&mut self) -> Result<Vec<Rigid>, Error> {
+    pub fn rigids(&mut self) -> Result<Vec<Rigid>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.rigid()).collect()
     }
 
-    fn joint(&mut self) -> Result<Joint, Error> {
+    pub fn joint(&mut self) -> Result<Joint, Error> {
         let name = self.read_string()?;
         let name_en = self.read_string()?;
         let t = self.read_u8()?;
@@ -711,8 +731,279 @@ where
         })
     }
 
-    fn joints(&mut self) -> Result<Vec<Joint>, Error> {
+    pub fn joints(&mut self) -> Result<Vec<Joint>, Error> {
         let len = self.read_u32()?;
         (0..len).map(|_| self.joint()).collect()
     }
 }
+
+/// Returns the size of a vertex weight following its type byte, or `None` for an unknown type.
+fn weight_size(kind: u8, bone_index: usize) -> Option<usize> {
+    match kind {
+        0 => Some(bone_index),
+        1 => Some(bone_index * 2 + 4),
+        2 => Some(bone_index * 4 + 16),
+        3 => Some(bone_index * 2 + 40),
+        _ => None,
+    }
+}
+
+fn decode_signed_index(bytes: &[u8]) -> Option<usize> {
+    let v = match *bytes {
+        [a] => i8::from_le_bytes([a]) as i32,
+        [a, b] => i16::from_le_bytes([a, b]) as i32,
+        [a, b, c, d] => i32::from_le_bytes([a, b, c, d]),
+        _ => unreachable!(),
+    };
+    (v >= 0).then_some(v as usize)
+}
+
+fn decode_vertex_index(bytes: &[u8]) -> Option<usize> {
+    match *bytes {
+        [a] => Some(a as usize),
+        [a, b] => Some(u16::from_le_bytes([a, b]) as usize),
+        _ => decode_signed_index(bytes),
+    }
+}
+
+/// Decodes values from bytes that have already been read, panicking if they run out.
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+        bytes.try_into().unwrap()
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes::<1>()[0]
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.bytes())
+    }
+
+    fn vec<const N: usize>(&mut self) -> [f32; N] {
+        std::array::from_fn(|_| self.f32())
+    }
+
+    fn index(&mut self, size: usize) -> Option<usize> {
+        let (bytes, rest) = self.0.split_at(size);
+        self.0 = rest;
+        decode_signed_index(bytes)
+    }
+}
+
+/// Sources that can move past bytes without decoding them.
+pub(crate) trait Skip: Read {
+    fn skip(&mut self, n: u64) -> std::io::Result<()>;
+}
+
+impl Skip for &[u8] {
+    fn skip(&mut self, n: u64) -> std::io::Result<()> {
+        let n = usize::try_from(n).unwrap_or(usize::MAX);
+        if self.len() < n {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        *self = &self[n..];
+        Ok(())
+    }
+}
+
+/// Adapts a seekable source to [`Skip`].
+pub(crate) struct Seeker<R>(pub R);
+
+impl<R: Read> Read for Seeker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.0.read_exact(buf)
+    }
+}
+
+impl<R: Read + std::io::Seek> Skip for Seeker<R> {
+    fn skip(&mut self, n: u64) -> std::io::Result<()> {
+        // seeking discards the buffer of a `BufReader`, so short distances are read instead
+        if n <= 4096 {
+            let copied = std::io::copy(&mut (&mut self.0).take(n), &mut std::io::sink())?;
+            if copied < n {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        } else {
+            self.0.seek(std::io::SeekFrom::Current(n as i64))?;
+        }
+        Ok(())
+    }
+}
+
+/// Adapts any source to [`Skip`] by reading and discarding bytes.
+pub(crate) struct Discard<R>(pub R);
+
+impl<R: Read> Read for Discard<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.0.read_exact(buf)
+    }
+}
+
+impl<R: Read> Skip for Discard<R> {
+    fn skip(&mut self, n: u64) -> std::io::Result<()> {
+        let copied = std::io::copy(&mut (&mut self.0).take(n), &mut std::io::sink())?;
+        if copied < n {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+impl<T> Reader<T>
+where
+    T: Skip,
+{
+    fn skip(&mut self, n: usize) -> Result<(), Error> {
+        Ok(self.reader.skip(n as u64)?)
+    }
+
+    fn skip_string(&mut self) -> Result<(), Error> {
+        let len = self.read_u32()?;
+        self.skip(len as usize)
+    }
+
+    fn skip_strings(&mut self, n: usize) -> Result<(), Error> {
+        (0..n).try_for_each(|_| self.skip_string())
+    }
+
+    pub fn skip_model_info(&mut self) -> Result<(), Error> {
+        self.skip_strings(4)
+    }
+
+    /// Skips a section of `len` elements with `f`.
+    pub fn skip_elements(
+        &mut self,
+        len: u32,
+        f: fn(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        (0..len).try_for_each(|_| f(self))
+    }
+
+    pub fn skip_vertex(&mut self) -> Result<(), Error> {
+        self.skip(32 + 16 * self.extended_uv)?;
+        let weight = weight_size(self.read_u8()?, self.bone_index)
+            .ok_or(Error::InvalidData("vertex::weight".into()))?;
+        self.skip(weight + 4)
+    }
+
+    pub fn skip_faces(&mut self, len: u32) -> Result<(), Error> {
+        self.skip(len as usize * self.vertex_index)
+    }
+
+    pub fn skip_texture(&mut self) -> Result<(), Error> {
+        self.skip_string()
+    }
+
+    pub fn skip_material(&mut self) -> Result<(), Error> {
+        self.skip_strings(2)?;
+        self.skip(65 + self.tex_index * 2 + 1)?;
+        match self.read_u8()? {
+            0 => self.skip(self.tex_index)?,
+            1 => self.skip(1)?,
+            _ => return Err(Error::InvalidData("material::toon".into())),
+        }
+        self.skip_string()?;
+        self.skip(4)
+    }
+
+    pub fn skip_bone(&mut self) -> Result<(), Error> {
+        let bone = self.bone_index;
+        self.skip_strings(2)?;
+        self.skip(12 + bone + 4)?;
+        let flags = self.read_u16()?;
+        let has = |flag: u16| flags & flag == flag;
+        let mut len = if has(0x0001) { bone } else { 12 };
+        if has(0x0100) || has(0x0200) {
+            len += bone + 4;
+        }
+        if has(0x0400) {
+            len += 12;
+        }
+        if has(0x0800) {
+            len += 24;
+        }
+        if has(0x2000) {
+            len += 4;
+        }
+        self.skip(len)?;
+        if has(0x0020) {
+            self.skip(bone + 8)?;
+            let links = self.read_u32()?;
+            for _ in 0..links {
+                self.skip(bone)?;
+                if self.read_u8()? == 0x01 {
+                    self.skip(24)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn skip_morph(&mut self) -> Result<(), Error> {
+        self.skip_strings(2)?;
+        self.skip(1)?;
+        let kind = self.read_u8()?;
+        let len = self.read_u32()? as usize;
+        let offset = match kind {
+            0 => self.morph_index + 4,
+            1 => self.vertex_index + 12,
+            2 => self.bone_index + 28,
+            3..=7 => self.vertex_index + 16,
+            8 => self.mat_index + 113,
+            _ => return Err(Error::InvalidData("morph::kind".into())),
+        };
+        self.skip(len * offset)
+    }
+
+    pub fn skip_display_group(&mut self) -> Result<(), Error> {
+        self.skip_strings(2)?;
+        self.skip(1)?;
+        let len = self.read_u32()?;
+        for _ in 0..len {
+            match self.read_u8()? {
+                0 => self.skip(self.bone_index)?,
+                1 => self.skip(self.morph_index)?,
+                _ => return Err(Error::InvalidData("display_group::elements".into())),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn skip_rigid(&mut self) -> Result<(), Error> {
+        self.skip_strings(2)?;
+        self.skip(self.bone_index + 61)
+    }
+
+    pub fn skip_joint(&mut self) -> Result<(), Error> {
+        self.skip_strings(2)?;
+        self.skip(1 + self.rig_index * 2 + 96)
+    }
+}
+
+impl<'a> Reader<&'a [u8]> {
+    /// Returns the bytes that have not been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.reader
+    }
+
+    /// Reads a string without decoding it.
+    pub fn read_raw_string(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u32()? as usize;
+        let data = self.reader;
+        self.skip(len)?;
+        Ok(&data[..len])
+    }
+}
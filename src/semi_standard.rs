@@ -0,0 +1,618 @@
+//! Insertion of semi-standard bones, following the recipe of PMX Editor's plugin.
+
+use crate::math::*;
+use crate::standard::{normalize_name, Side, StandardBone};
+use crate::weight::{from_influences, influences};
+use crate::*;
+
+/// Positions of the twist bones along their segment and the addition ratio of the sub bones.
+const TWIST: f32 = 0.6;
+const TWIST_STAGES: [f32; 3] = [0.25, 0.5, 0.75];
+/// Position of 足先EX between 足首 and つま先.
+const TOE_EX: f32 = 2.0 / 3.0;
+
+impl Pmx {
+    /// Inserts the semi-standard bones the model lacks and returns the ones inserted.
+    ///
+    /// Bones are only inserted when the standard bones they hang from exist.
+    /// Vertices are re-weighted to 上半身2, the twist sub bones, the D bones and 足先EX;
+    /// the other bones only change the hierarchy. Twist sub bones are inserted
+    /// with their twist bone, so models that already have the twist bone keep
+    /// their weights. The sub bones are not standard bones and are not returned.
+    pub fn insert_semi_standard_bones(&mut self) -> Vec<StandardBone> {
+        let mut inserted = vec![];
+        self.insert_root(&mut inserted);
+        self.insert_upper_body2(&mut inserted);
+        self.insert_waist(&mut inserted);
+        for side in [Side::Left, Side::Right] {
+            let sided = |bone: StandardBone| match side {
+                Side::Left => bone,
+                Side::Right => bone.mirror(),
+            };
+            self.insert_waist_cancel(sided(StandardBone::LeftWaistCancel), &mut inserted);
+            self.insert_leg_ik_parent(sided(StandardBone::LeftLegIkParent), &mut inserted);
+            self.insert_shoulder(sided(StandardBone::LeftShoulderP), &mut inserted);
+            self.insert_twist(sided(StandardBone::LeftArmTwist), &mut inserted);
+            self.insert_twist(sided(StandardBone::LeftWristTwist), &mut inserted);
+            self.insert_leg_d(sided(StandardBone::LeftLegD), &mut inserted);
+            self.insert_toe_ex(sided(StandardBone::LeftToeEx), &mut inserted);
+        }
+        inserted
+    }
+
+    fn insert_root(&mut self, inserted: &mut Vec<StandardBone>) {
+        if self.standard_bone(StandardBone::Root).is_none() {
+            let root = self.add_bone(0, semi_bone(StandardBone::Root, [0.0; 3]), None);
+            self.bones[root].translatable = true;
+            for (i, bone) in self.bones.iter_mut().enumerate() {
+                let view_center =
+                    StandardBone::from_name(&bone.name) == Some(StandardBone::ViewCenter);
+                if i != root && bone.parent.is_none() && !view_center {
+                    bone.parent = Some(root);
+                }
+            }
+            if let Some(group) = self
+                .display_groups
+                .iter_mut()
+                .find(|g| g.special && g.name == "Root")
+            {
+                group.elements.insert(0, DisplayElement::Bone(Some(root)));
+            }
+            inserted.push(StandardBone::Root);
+        }
+        if self.standard_bone(StandardBone::ViewCenter).is_none() {
+            let center = self.add_bone(0, semi_bone(StandardBone::ViewCenter, [0.0; 3]), None);
+            self.bones[center].translatable = true;
+            inserted.push(StandardBone::ViewCenter);
+        }
+    }
+
+    /// Splits 上半身 halfway to 首 and moves the weights above the split to 上半身2.
+    fn insert_upper_body2(&mut self, inserted: &mut Vec<StandardBone>) {
+        if self.standard_bone(StandardBone::UpperBody2).is_some() {
+            return;
+        }
+        let Some(upper) = self.standard_bone(StandardBone::UpperBody) else {
+            return;
+        };
+        let Some(neck) = self.standard_bone(StandardBone::Neck) else {
+            return;
+        };
+        let position = lerp(self.bones[upper].position, self.bones[neck].position, 0.5);
+        let bone = semi_bone(StandardBone::UpperBody2, position);
+        let upper2 = self.add_bone(upper + 1, bone, Some(upper));
+        let upper = self.standard_bone(StandardBone::UpperBody).unwrap();
+        let neck = self.standard_bone(StandardBone::Neck).unwrap();
+        for (i, bone) in self.bones.iter_mut().enumerate() {
+            if i != upper2 && bone.parent == Some(upper) {
+                bone.parent = Some(upper2);
+            }
+        }
+        self.bones[upper2].parent = Some(upper);
+        self.bones[upper2].connected_to = ConnectedTo::Bone(Some(neck));
+        if let ConnectedTo::Bone(_) = self.bones[upper].connected_to {
+            self.bones[upper].connected_to = ConnectedTo::Bone(Some(upper2));
+        }
+        self.split_weights(upper, |p| {
+            if p[1] >= position[1] {
+                vec![(upper2, 1.0)]
+            } else {
+                vec![(upper, 1.0)]
+            }
+        });
+        inserted.push(StandardBone::UpperBody2);
+    }
+
+    /// Inserts 腰 between the feet and 下半身 as the parent of 上半身 and 下半身.
+    fn insert_waist(&mut self, inserted: &mut Vec<StandardBone>) {
+        if self.standard_bone(StandardBone::Waist).is_some() {
+            return;
+        }
+        let Some(lower) = self.standard_bone(StandardBone::LowerBody) else {
+            return;
+        };
+        let Some(upper) = self.standard_bone(StandardBone::UpperBody) else {
+            return;
+        };
+        let legs = [StandardBone::LeftLeg, StandardBone::RightLeg]
+            .iter()
+            .filter_map(|b| self.standard_bone(*b))
+            .map(|b| self.bones[b].position)
+            .collect::<Vec<_>>();
+        let mut position = self.bones[lower].position;
+        if !legs.is_empty() {
+            let legs = scale(
+                legs.iter().copied().fold([0.0; 3], add),
+                1.0 / legs.len() as f32,
+            );
+            position = lerp(position, legs, 0.5);
+        }
+        let parent = self.bones[lower].parent;
+        let waist = self.add_bone(
+            lower.min(upper),
+            semi_bone(StandardBone::Waist, position),
+            Some(lower),
+        );
+        let parent = parent.map(|p| if p >= waist { p + 1 } else { p });
+        self.bones[waist].parent = parent;
+        for b in [StandardBone::LowerBody, StandardBone::UpperBody] {
+            let b = self.standard_bone(b).unwrap();
+            self.bones[b].parent = Some(waist);
+        }
+        inserted.push(StandardBone::Waist);
+    }
+
+    /// Inserts 腰キャンセル, which cancels the rotation of 腰 for the leg.
+    fn insert_waist_cancel(&mut self, cancel: StandardBone, inserted: &mut Vec<StandardBone>) {
+        let leg = self.sided(cancel, StandardBone::LeftLeg);
+        if self.standard_bone(cancel).is_some() {
+            return;
+        }
+        let (Some(waist), Some(leg)) = (
+            self.standard_bone(StandardBone::Waist),
+            self.standard_bone(leg),
+        ) else {
+            return;
+        };
+        let mut bone = semi_bone(cancel, self.bones[leg].position);
+        bone.visibility = false;
+        bone.operable = false;
+        let parent = self.bones[leg].parent;
+        let waist = if waist >= leg { waist + 1 } else { waist };
+        bone.addition = Some(Addition {
+            rotation: true,
+            translation: false,
+            local: false,
+            bone: Some(waist),
+            ratio: -1.0,
+        });
+        let c = self.add_bone(leg, bone, None);
+        self.bones[c].parent = parent.map(|p| if p >= c { p + 1 } else { p });
+        self.bones[c + 1].parent = Some(c);
+        inserted.push(cancel);
+    }
+
+    fn insert_leg_ik_parent(&mut self, parent: StandardBone, inserted: &mut Vec<StandardBone>) {
+        let ik = self.sided(parent, StandardBone::LeftLegIk);
+        if self.standard_bone(parent).is_some() {
+            return;
+        }
+        let Some(ik) = self.standard_bone(ik) else {
+            return;
+        };
+        let mut position = self.bones[ik].position;
+        position[1] = 0.0;
+        let ik_parent = self.bones[ik].parent;
+        let mut bone = semi_bone(parent, position);
+        bone.translatable = true;
+        let p = self.add_bone(ik, bone, Some(ik));
+        self.bones[p].parent = ik_parent.map(|i| if i >= p { i + 1 } else { i });
+        self.bones[p + 1].parent = Some(p);
+        inserted.push(parent);
+    }
+
+    /// Inserts 肩P above 肩 and 肩C below it, which cancels the rotation of 肩P for 腕.
+    fn insert_shoulder(&mut self, shoulder_p: StandardBone, inserted: &mut Vec<StandardBone>) {
+        let shoulder = self.sided(shoulder_p, StandardBone::LeftShoulder);
+        let shoulder_c = self.sided(shoulder_p, StandardBone::LeftShoulderC);
+        let arm = self.sided(shoulder_p, StandardBone::LeftArm);
+        if self.standard_bone(shoulder_p).is_none() {
+            let Some(s) = self.standard_bone(shoulder) else {
+                return;
+            };
+            let parent = self.bones[s].parent;
+            let p = self.add_bone(s, semi_bone(shoulder_p, self.bones[s].position), Some(s));
+            self.bones[p].parent = parent.map(|i| if i >= p { i + 1 } else { i });
+            self.bones[p + 1].parent = Some(p);
+            inserted.push(shoulder_p);
+        }
+        if self.standard_bone(shoulder_c).is_none() {
+            let (Some(p), Some(s), Some(a)) = (
+                self.standard_bone(shoulder_p),
+                self.standard_bone(shoulder),
+                self.standard_bone(arm),
+            ) else {
+                return;
+            };
+            let mut bone = semi_bone(shoulder_c, self.bones[a].position);
+            bone.visibility = false;
+            bone.operable = false;
+            bone.parent = Some(s);
+            bone.addition = Some(Addition {
+                rotation: true,
+                translation: false,
+                local: false,
+                bone: Some(p),
+                ratio: -1.0,
+            });
+            let c = self.add_bone(s + 1, bone, None);
+            let a = self.standard_bone(arm).unwrap();
+            self.bones[a].parent = Some(c);
+            inserted.push(shoulder_c);
+        }
+    }
+
+    /// Inserts a twist bone and three sub bones taking part of its rotation,
+    /// and spreads the weights of the segment over them.
+    fn insert_twist(&mut self, twist: StandardBone, inserted: &mut Vec<StandardBone>) {
+        let (from, to) = match twist {
+            StandardBone::LeftArmTwist => (StandardBone::LeftArm, StandardBone::LeftElbow),
+            StandardBone::RightArmTwist => (StandardBone::RightArm, StandardBone::RightElbow),
+            StandardBone::LeftWristTwist => (StandardBone::LeftElbow, StandardBone::LeftWrist),
+            StandardBone::RightWristTwist => (StandardBone::RightElbow, StandardBone::RightWrist),
+            _ => return,
+        };
+        let (Some(a), Some(b)) = (self.standard_bone(from), self.standard_bone(to)) else {
+            return;
+        };
+        let start = self.bones[a].position;
+        let end = self.bones[b].position;
+        let axis = sub(end, start);
+        if self.standard_bone(twist).is_some() {
+            return;
+        }
+        let mut bone = semi_bone(twist, lerp(start, end, TWIST));
+        bone.parent = Some(a);
+        bone.fixed_pole = Some(normalize(axis));
+        let t = self.add_bone(b, bone, Some(a));
+        self.bones[t + 1].parent = Some(t);
+        inserted.push(twist);
+        let sub_name = |n: usize| format!("{}{}", twist.name(), n);
+        let exists = |pmx: &Pmx, name: &str| {
+            let name = normalize_name(name);
+            pmx.bones.iter().any(|b| normalize_name(&b.name) == name)
+        };
+        if (1..=3).any(|n| exists(self, &sub_name(n))) {
+            return;
+        }
+        let mut stages = vec![(0.0, self.standard_bone(from).unwrap())];
+        for (n, ratio) in TWIST_STAGES.into_iter().enumerate() {
+            let t = self.standard_bone(twist).unwrap();
+            let mut bone = semi_bone(twist, lerp(start, end, ratio));
+            bone.name = sub_name(n + 1);
+            bone.name_en = twist.name_en().replace('_', &format!("{}_", n + 1));
+            bone.parent = self.standard_bone(from);
+            bone.visibility = false;
+            bone.operable = false;
+            bone.addition = Some(Addition {
+                rotation: true,
+                translation: false,
+                local: false,
+                bone: Some(t),
+                ratio,
+            });
+            let s = self.add_bone(t + 1 + n, bone, None);
+            for stage in stages.iter_mut() {
+                if stage.1 >= s {
+                    stage.1 += 1;
+                }
+            }
+            stages.push((ratio, s));
+        }
+        stages.push((1.0, self.standard_bone(twist).unwrap()));
+        let a = self.standard_bone(from).unwrap();
+        let len2 = dot(axis, axis);
+        if len2 <= f32::EPSILON {
+            return;
+        }
+        self.split_weights(a, |p| {
+            let t = (dot(sub(p, start), axis) / len2).clamp(0.0, 1.0);
+            let k = stages.windows(2).position(|w| t <= w[1].0).unwrap();
+            let (t0, b0) = stages[k];
+            let (t1, b1) = stages[k + 1];
+            let s = (t - t0) / (t1 - t0);
+            vec![(b0, 1.0 - s), (b1, s)]
+        });
+    }
+
+    /// Inserts 足D, ひざD and 足首D, which follow the leg after IK, and moves the leg weights to them.
+    fn insert_leg_d(&mut self, leg_d: StandardBone, inserted: &mut Vec<StandardBone>) {
+        if self.standard_bone(leg_d).is_some() {
+            return;
+        }
+        let chain = [
+            (StandardBone::LeftLeg, StandardBone::LeftLegD),
+            (StandardBone::LeftKnee, StandardBone::LeftKneeD),
+            (StandardBone::LeftAnkle, StandardBone::LeftAnkleD),
+        ]
+        .map(|(b, d)| (self.sided(leg_d, b), self.sided(leg_d, d)));
+        let Some(bones) = chain
+            .iter()
+            .map(|(b, _)| self.standard_bone(*b))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+        let mut parent = self.bones[bones[0]].parent;
+        let mut ds: Vec<usize> = vec![];
+        for (&(_, d), &b) in chain.iter().zip(&bones) {
+            if self.standard_bone(d).is_some() {
+                return;
+            }
+            let mut bone = semi_bone(d, self.bones[b].position);
+            bone.parent = parent;
+            bone.deform_hierarchy = 1;
+            bone.addition = Some(Addition {
+                rotation: true,
+                translation: false,
+                local: false,
+                bone: Some(b),
+                ratio: 1.0,
+            });
+            let i = self.bones.len();
+            // each D bone is shown after the previous one so that the display follows the chain
+            self.add_bone(i, bone, Some(ds.last().copied().unwrap_or(bones[0])));
+            if let Some(&prev) = ds.last() {
+                self.bones[prev].connected_to = ConnectedTo::Bone(Some(i));
+            }
+            parent = Some(i);
+            ds.push(i);
+            inserted.push(d);
+        }
+        for (&b, &d) in bones.iter().zip(&ds) {
+            self.split_weights(b, |_| vec![(d, 1.0)]);
+        }
+    }
+
+    /// Inserts 足先EX under 足首D and moves the weights past it along the foot.
+    fn insert_toe_ex(&mut self, toe_ex: StandardBone, inserted: &mut Vec<StandardBone>) {
+        if self.standard_bone(toe_ex).is_some() {
+            return;
+        }
+        let ankle = self.sided(toe_ex, StandardBone::LeftAnkle);
+        let ankle_d = self.sided(toe_ex, StandardBone::LeftAnkleD);
+        let toe = self.sided(toe_ex, StandardBone::LeftToe);
+        let (Some(ankle), Some(ankle_d), Some(toe)) = (
+            self.standard_bone(ankle),
+            self.standard_bone(ankle_d),
+            self.standard_bone(toe),
+        ) else {
+            return;
+        };
+        let start = self.bones[ankle].position;
+        let axis = sub(self.bones[toe].position, start);
+        let position = lerp(start, self.bones[toe].position, TOE_EX);
+        let mut bone = semi_bone(toe_ex, position);
+        bone.parent = Some(ankle_d);
+        bone.deform_hierarchy = 1;
+        let i = self.bones.len();
+        let ex = self.add_bone(i, bone, Some(ankle_d));
+        self.split_weights(ankle_d, |p| {
+            if dot(sub(p, position), axis) > 0.0 {
+                vec![(ex, 1.0)]
+            } else {
+                vec![(ankle_d, 1.0)]
+            }
+        });
+        inserted.push(toe_ex);
+    }
+
+    /// Returns the bone of the same side as `side` that corresponds to the left bone `left`.
+    fn sided(&self, side: StandardBone, left: StandardBone) -> StandardBone {
+        match side.side() {
+            Some(Side::Right) => left.mirror(),
+            _ => left,
+        }
+    }
+
+    /// Inserts `bone` at `index` and shows it next to `display_with`, returning its index.
+    fn add_bone(&mut self, index: usize, bone: Bone, display_with: Option<usize>) -> usize {
+        self.insert_bone(index, bone).unwrap();
+        let Some(anchor) = display_with.map(|b| if b >= index { b + 1 } else { b }) else {
+            return index;
+        };
+        for group in self.display_groups.iter_mut() {
+            let position = group
+                .elements
+                .iter()
+                .position(|e| matches!(e, DisplayElement::Bone(Some(b)) if *b == anchor));
+            if let Some(position) = position {
+                group
+                    .elements
+                    .insert(position + 1, DisplayElement::Bone(Some(index)));
+                break;
+            }
+        }
+        index
+    }
+
+    /// Replaces the influence of `bone` on every vertex by the bones and shares `f` returns.
+    fn split_weights(&mut self, bone: usize, f: impl Fn([f32; 3]) -> Vec<(usize, f32)>) {
+        for vertex in self.vertices.iter_mut() {
+            let influences = influences(&vertex.weight);
+            let Some(&(_, w)) = influences.iter().find(|(b, _)| *b == bone) else {
+                continue;
+            };
+            let shares = f(vertex.position);
+            if let [(single, _)] = shares.as_slice() {
+                // keep the kind of weight, e.g. Sdef, when only the bone changes
+                replace_bone(&mut vertex.weight, bone, *single);
+                if influences.iter().all(|(b, _)| b != single || *b == bone) {
+                    continue;
+                }
+            }
+            let mut influences = influences
+                .into_iter()
+                .filter(|(b, _)| *b != bone)
+                .collect::<Vec<_>>();
+            for (b, share) in shares {
+                match influences.iter_mut().find(|(x, _)| *x == b) {
+                    Some(x) => x.1 += w * share,
+                    None => influences.push((b, w * share)),
+                }
+            }
+            influences.retain(|(_, w)| *w > 0.0);
+            vertex.weight = from_influences(influences);
+        }
+    }
+}
+
+fn replace_bone(weight: &mut Weight, from: usize, to: usize) {
+    let bones: &mut [Option<usize>] = match weight {
+        Weight::Bdef1(w) => std::slice::from_mut(&mut w.bone),
+        Weight::Bdef2(w) => &mut w.bones,
+        Weight::Bdef4(w) => &mut w.bones,
+        Weight::Sdef(w) => &mut w.bones,
+    };
+    for b in bones.iter_mut().filter(|b| **b == Some(from)) {
+        *b = Some(to);
+    }
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    add(a, scale(sub(b, a), t))
+}
+
+fn semi_bone(bone: StandardBone, position: [f32; 3]) -> Bone {
+    Bone {
+        name: bone.name().into(),
+        name_en: bone.name_en().into(),
+        position,
+        parent: None,
+        deform_hierarchy: 0,
+        connected_to: ConnectedTo::Offset([0.0; 3]),
+        rotatable: true,
+        translatable: false,
+        visibility: true,
+        operable: true,
+        ik: None,
+        addition: None,
+        after_physics: false,
+        fixed_pole: None,
+        local_pole: None,
+        external_parent: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn bone(pmx: &Pmx, name: &str) -> usize {
+        pmx.bones.iter().position(|b| b.name == name).unwrap()
+    }
+
+    #[test]
+    fn alicia() {
+        use StandardBone::*;
+        let original = read_pmx();
+        let mut pmx = original.clone();
+        let inserted = pmx.insert_semi_standard_bones();
+        for b in [
+            ViewCenter,
+            LeftWaistCancel,
+            RightLegIkParent,
+            LeftShoulderP,
+            LeftShoulderC,
+            RightLegD,
+            RightAnkleD,
+            LeftToeEx,
+        ] {
+            assert!(inserted.contains(&b));
+            assert!(pmx.standard_bone(b).is_some());
+        }
+        assert!(!inserted.contains(&Root));
+        assert!(!inserted.contains(&Waist));
+        assert!(!inserted.contains(&LeftArmTwist));
+        assert!(pmx.bones.len() == original.bones.len() + inserted.len());
+        assert!(pmx.bones.iter().all(|b| !b.name.starts_with("左腕捩1")));
+
+        for (i, b) in pmx.bones.iter().enumerate() {
+            if let Some(p) = b.parent {
+                assert!(p < i);
+            }
+        }
+        assert!(pmx.bones[bone(&pmx, "左足")].parent == Some(bone(&pmx, "腰キャンセル左")));
+        let cancel = pmx.bones[bone(&pmx, "腰キャンセル左")]
+            .addition
+            .clone()
+            .unwrap();
+        assert!(cancel.bone == Some(bone(&pmx, "腰")) && cancel.ratio == -1.0);
+        assert!(pmx.bones[bone(&pmx, "左腕")].parent == Some(bone(&pmx, "左肩C")));
+        assert!(pmx.bones[bone(&pmx, "右足ＩＫ")].parent == Some(bone(&pmx, "右足IK親")));
+        let leg_d = pmx.bones[bone(&pmx, "左足D")].addition.clone().unwrap();
+        assert!(leg_d.bone == Some(bone(&pmx, "左足")));
+        let names = ["左足", "左足D", "左ひざD", "左足首D", "左足先EX"];
+        let expected = names.map(|name| Some(bone(&pmx, name)));
+        assert!(pmx.display_groups.iter().any(|g| {
+            let shown = g
+                .elements
+                .iter()
+                .map(|e| match e {
+                    DisplayElement::Bone(b) => *b,
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            shown.windows(names.len()).any(|w| w == expected)
+        }));
+
+        let leg = bone(&pmx, "左足");
+        for v in &pmx.vertices {
+            let influences = influences(&v.weight);
+            assert!(influences.iter().all(|(b, _)| *b != leg));
+            let sum = influences.iter().map(|(_, w)| w).sum::<f32>();
+            assert!((sum - 1.0).abs() < 1e-4);
+        }
+        assert!(pmx
+            .display_groups
+            .iter()
+            .flat_map(|g| &g.elements)
+            .any(|e| matches!(e, DisplayElement::Bone(Some(b)) if *b == bone(&pmx, "腰"))));
+
+        assert!(pmx.insert_semi_standard_bones().is_empty());
+    }
+
+    #[test]
+    fn twist() {
+        let mut pmx = read_pmx();
+        let arm = bone(&pmx, "左腕");
+        let twist = bone(&pmx, "左腕捩");
+        pmx.split_weights(twist, |_| vec![(arm, 1.0)]);
+        for b in pmx.bones.iter_mut().filter(|b| b.parent == Some(twist)) {
+            b.parent = Some(arm);
+        }
+        pmx.remove_bones(&[twist]).unwrap();
+        let bones = pmx.bones.len();
+        let inserted = pmx.insert_semi_standard_bones();
+        assert!(inserted.contains(&StandardBone::LeftArmTwist));
+        assert!(!inserted.contains(&StandardBone::RightArmTwist));
+        assert!(pmx.bones.len() == bones + inserted.len() + 3);
+        let addition = pmx.bones[bone(&pmx, "左腕捩2")].addition.clone().unwrap();
+        assert!(addition.bone == Some(bone(&pmx, "左腕捩")) && addition.ratio == 0.5);
+        let twist1 = bone(&pmx, "左腕捩1");
+        assert!(pmx
+            .vertices
+            .iter()
+            .any(|v| influences(&v.weight).iter().any(|(b, _)| *b == twist1)));
+    }
+
+    #[test]
+    fn waist_and_upper_body2() {
+        let mut pmx = read_pmx();
+        let remove = [bone(&pmx, "腰"), bone(&pmx, "上半身2")];
+        for r in remove {
+            let parent = pmx.bones[r].parent;
+            for b in pmx.bones.iter_mut().filter(|b| b.parent == Some(r)) {
+                b.parent = parent;
+            }
+        }
+        pmx.remove_bones(&remove).unwrap();
+        let inserted = pmx.insert_semi_standard_bones();
+        assert!(inserted.contains(&StandardBone::Waist));
+        assert!(inserted.contains(&StandardBone::UpperBody2));
+        let waist = bone(&pmx, "腰");
+        assert!(pmx.bones[bone(&pmx, "上半身")].parent == Some(waist));
+        assert!(pmx.bones[bone(&pmx, "下半身")].parent == Some(waist));
+        let upper2 = bone(&pmx, "上半身2");
+        assert!(pmx.bones[bone(&pmx, "首")].parent == Some(upper2));
+        assert!(pmx
+            .vertices
+            .iter()
+            .any(|v| influences(&v.weight).iter().any(|(b, _)| *b == upper2)));
+    }
+}
@@ -0,0 +1,372 @@
+//! Standard and semi-standard MMD bones.
+
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    #[inline]
+    pub fn mirror(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum StandardBone {
+    ViewCenter,
+    Root,
+    Center,
+    Groove,
+    Waist,
+    LowerBody,
+    UpperBody,
+    UpperBody2,
+    Neck,
+    Head,
+    Eyes,
+    LeftEye,
+    RightEye,
+    LeftShoulderP,
+    LeftShoulder,
+    LeftShoulderC,
+    LeftArm,
+    LeftArmTwist,
+    LeftElbow,
+    LeftWristTwist,
+    LeftWrist,
+    LeftThumb0,
+    LeftThumb1,
+    LeftThumb2,
+    LeftIndex1,
+    LeftIndex2,
+    LeftIndex3,
+    LeftMiddle1,
+    LeftMiddle2,
+    LeftMiddle3,
+    LeftRing1,
+    LeftRing2,
+    LeftRing3,
+    LeftLittle1,
+    LeftLittle2,
+    LeftLittle3,
+    RightShoulderP,
+    RightShoulder,
+    RightShoulderC,
+    RightArm,
+    RightArmTwist,
+    RightElbow,
+    RightWristTwist,
+    RightWrist,
+    RightThumb0,
+    RightThumb1,
+    RightThumb2,
+    RightIndex1,
+    RightIndex2,
+    RightIndex3,
+    RightMiddle1,
+    RightMiddle2,
+    RightMiddle3,
+    RightRing1,
+    RightRing2,
+    RightRing3,
+    RightLittle1,
+    RightLittle2,
+    RightLittle3,
+    LeftWaistCancel,
+    LeftLeg,
+    LeftKnee,
+    LeftAnkle,
+    LeftToe,
+    LeftLegIkParent,
+    LeftLegIk,
+    LeftToeIk,
+    LeftLegD,
+    LeftKneeD,
+    LeftAnkleD,
+    LeftToeEx,
+    RightWaistCancel,
+    RightLeg,
+    RightKnee,
+    RightAnkle,
+    RightToe,
+    RightLegIkParent,
+    RightLegIk,
+    RightToeIk,
+    RightLegD,
+    RightKneeD,
+    RightAnkleD,
+    RightToeEx,
+}
+
+/// Japanese and English names of every standard bone, in the order of [`StandardBone`].
+const BONES: [(StandardBone, &str, &str); 83] = {
+    use StandardBone::*;
+    [
+        (ViewCenter, "操作中心", "view cnt"),
+        (Root, "全ての親", "master"),
+        (Center, "センター", "center"),
+        (Groove, "グルーブ", "groove"),
+        (Waist, "腰", "waist"),
+        (LowerBody, "下半身", "lower body"),
+        (UpperBody, "上半身", "upper body"),
+        (UpperBody2, "上半身2", "upper body2"),
+        (Neck, "首", "neck"),
+        (Head, "頭", "head"),
+        (Eyes, "両目", "eyes"),
+        (LeftEye, "左目", "eye_L"),
+        (RightEye, "右目", "eye_R"),
+        (LeftShoulderP, "左肩P", "shoulderP_L"),
+        (LeftShoulder, "左肩", "shoulder_L"),
+        (LeftShoulderC, "左肩C", "shoulderC_L"),
+        (LeftArm, "左腕", "arm_L"),
+        (LeftArmTwist, "左腕捩", "arm twist_L"),
+        (LeftElbow, "左ひじ", "elbow_L"),
+        (LeftWristTwist, "左手捩", "wrist twist_L"),
+        (LeftWrist, "左手首", "wrist_L"),
+        (LeftThumb0, "左親指０", "thumb0_L"),
+        (LeftThumb1, "左親指１", "thumb1_L"),
+        (LeftThumb2, "左親指２", "thumb2_L"),
+        (LeftIndex1, "左人指１", "fore1_L"),
+        (LeftIndex2, "左人指２", "fore2_L"),
+        (LeftIndex3, "左人指３", "fore3_L"),
+        (LeftMiddle1, "左中指１", "middle1_L"),
+        (LeftMiddle2, "左中指２", "middle2_L"),
+        (LeftMiddle3, "左中指３", "middle3_L"),
+        (LeftRing1, "左薬指１", "third1_L"),
+        (LeftRing2, "左薬指２", "third2_L"),
+        (LeftRing3, "左薬指３", "third3_L"),
+        (LeftLittle1, "左小指１", "little1_L"),
+        (LeftLittle2, "左小指２", "little2_L"),
+        (LeftLittle3, "左小指３", "little3_L"),
+        (RightShoulderP, "右肩P", "shoulderP_R"),
+        (RightShoulder, "右肩", "shoulder_R"),
+        (RightShoulderC, "右肩C", "shoulderC_R"),
+        (RightArm, "右腕", "arm_R"),
+        (RightArmTwist, "右腕捩", "arm twist_R"),
+        (RightElbow, "右ひじ", "elbow_R"),
+        (RightWristTwist, "右手捩", "wrist twist_R"),
+        (RightWrist, "右手首", "wrist_R"),
+        (RightThumb0, "右親指０", "thumb0_R"),
+        (RightThumb1, "右親指１", "thumb1_R"),
+        (RightThumb2, "右親指２", "thumb2_R"),
+        (RightIndex1, "右人指１", "fore1_R"),
+        (RightIndex2, "右人指２", "fore2_R"),
+        (RightIndex3, "右人指３", "fore3_R"),
+        (RightMiddle1, "右中指１", "middle1_R"),
+        (RightMiddle2, "右中指２", "middle2_R"),
+        (RightMiddle3, "右中指３", "middle3_R"),
+        (RightRing1, "右薬指１", "third1_R"),
+        (RightRing2, "右薬指２", "third2_R"),
+        (RightRing3, "右薬指３", "third3_R"),
+        (RightLittle1, "右小指１", "little1_R"),
+        (RightLittle2, "右小指２", "little2_R"),
+        (RightLittle3, "右小指３", "little3_R"),
+        (LeftWaistCancel, "腰キャンセル左", "waist cancel_L"),
+        (LeftLeg, "左足", "leg_L"),
+        (LeftKnee, "左ひざ", "knee_L"),
+        (LeftAnkle, "左足首", "ankle_L"),
+        (LeftToe, "左つま先", "toe_L"),
+        (LeftLegIkParent, "左足IK親", "leg IKP_L"),
+        (LeftLegIk, "左足ＩＫ", "leg IK_L"),
+        (LeftToeIk, "左つま先ＩＫ", "toe IK_L"),
+        (LeftLegD, "左足D", "leg D_L"),
+        (LeftKneeD, "左ひざD", "knee D_L"),
+        (LeftAnkleD, "左足首D", "ankle D_L"),
+        (LeftToeEx, "左足先EX", "toe EX_L"),
+        (RightWaistCancel, "腰キャンセル右", "waist cancel_R"),
+        (RightLeg, "右足", "leg_R"),
+        (RightKnee, "右ひざ", "knee_R"),
+        (RightAnkle, "右足首", "ankle_R"),
+        (RightToe, "右つま先", "toe_R"),
+        (RightLegIkParent, "右足IK親", "leg IKP_R"),
+        (RightLegIk, "右足ＩＫ", "leg IK_R"),
+        (RightToeIk, "右つま先ＩＫ", "toe IK_R"),
+        (RightLegD, "右足D", "leg D_R"),
+        (RightKneeD, "右ひざD", "knee D_R"),
+        (RightAnkleD, "右足首D", "ankle D_R"),
+        (RightToeEx, "右足先EX", "toe EX_R"),
+    ]
+};
+
+impl StandardBone {
+    /// Returns every standard bone, parents before children within each side.
+    pub fn all() -> impl Iterator<Item = StandardBone> {
+        BONES.iter().map(|(bone, _, _)| *bone)
+    }
+
+    /// Returns the usual Japanese name, e.g. `"右足ＩＫ"`.
+    #[inline]
+    pub fn name(self) -> &'static str {
+        BONES[self as usize].1
+    }
+
+    /// Returns the English name used by PMX Editor, e.g. `"leg IK_R"`.
+    #[inline]
+    pub fn name_en(self) -> &'static str {
+        BONES[self as usize].2
+    }
+
+    /// Looks up a bone by its Japanese name.
+    ///
+    /// Full-width and half-width forms are treated alike, and the side may also be
+    /// written as a `左`/`右` suffix or as `.L`/`_L` and `.R`/`_R`, e.g. `腕.L`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = normalize_name(name);
+        let mut candidates = vec![name.clone()];
+        if let Some((side, base)) = split_side(&name) {
+            let side = match side {
+                Side::Left => "左",
+                Side::Right => "右",
+            };
+            candidates.push(format!("{}{}", side, base));
+            candidates.push(format!("{}{}", base, side));
+        }
+        BONES
+            .iter()
+            .find(|(_, jp, _)| candidates.contains(&normalize_name(jp)))
+            .map(|(bone, _, _)| *bone)
+    }
+
+    pub fn side(self) -> Option<Side> {
+        split_side(self.name()).map(|(side, _)| side)
+    }
+
+    /// Returns the bone of the other side, or `self` for bones on the center line.
+    pub fn mirror(self) -> Self {
+        let name = self.name();
+        let mirrored = if name.contains('左') {
+            name.replace('左', "右")
+        } else {
+            name.replace('右', "左")
+        };
+        Self::from_name(&mirrored).unwrap_or(self)
+    }
+}
+
+/// Splits the side off a name, trying a `左`/`右` prefix or suffix and `.L`/`_L` style suffixes.
+pub(crate) fn split_side(name: &str) -> Option<(Side, &str)> {
+    let side = |c: char| match c {
+        '左' | 'L' => Some(Side::Left),
+        '右' | 'R' => Some(Side::Right),
+        _ => None,
+    };
+    if let Some(c) = name.chars().next().filter(|c| *c == '左' || *c == '右') {
+        return Some((side(c)?, &name[c.len_utf8()..]));
+    }
+    let mut rev = name.chars().rev();
+    let last = rev.next()?;
+    if last == '左' || last == '右' {
+        return Some((side(last)?, &name[..name.len() - last.len_utf8()]));
+    }
+    if matches!(rev.next(), Some('.' | '_')) {
+        return Some((side(last)?, &name[..name.len() - 2]));
+    }
+    None
+}
+
+/// Converts full-width ASCII to half-width and half-width katakana to full-width.
+pub(crate) fn normalize_name(name: &str) -> String {
+    const KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+    let mut s = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        match c {
+            '\u{ff01}'..='\u{ff5e}' => {
+                s.push(char::from_u32(c as u32 - 0xff01 + 0x21).unwrap());
+            }
+            '\u{3000}' => s.push(' '),
+            '\u{ff66}'..='\u{ff9d}' => {
+                s.push(KATAKANA.chars().nth(c as usize - 0xff66).unwrap());
+            }
+            '\u{ff9e}' | '\u{ff9f}' => {
+                // voiced kana directly follow the unvoiced ones, semi-voiced ones come next
+                let prev = s.pop();
+                let combined = match (prev, c) {
+                    (Some('ウ'), '\u{ff9e}') => Some('ヴ'),
+                    (Some(p), '\u{ff9e}')
+                        if "カキクケコサシスセソタチツテトハヒフヘホ".contains(p) =>
+                    {
+                        char::from_u32(p as u32 + 1)
+                    }
+                    (Some(p), '\u{ff9f}') if "ハヒフヘホ".contains(p) => {
+                        char::from_u32(p as u32 + 2)
+                    }
+                    _ => None,
+                };
+                match combined {
+                    Some(combined) => s.push(combined),
+                    None => {
+                        s.extend(prev);
+                        s.push(if c == '\u{ff9e}' { '゛' } else { '゜' });
+                    }
+                }
+            }
+            c => s.push(c),
+        }
+    }
+    s
+}
+
+impl Pmx {
+    /// Returns the index of the first bone whose name identifies `bone`.
+    pub fn standard_bone(&self, bone: StandardBone) -> Option<usize> {
+        self.bones
+            .iter()
+            .position(|b| StandardBone::from_name(&b.name) == Some(bone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn table() {
+        for (i, bone) in StandardBone::all().enumerate() {
+            assert!(bone as usize == i);
+            assert!(StandardBone::from_name(bone.name()) == Some(bone));
+            assert!(bone.mirror().mirror() == bone);
+        }
+        assert!(StandardBone::LeftArm.mirror() == StandardBone::RightArm);
+        assert!(StandardBone::Head.mirror() == StandardBone::Head);
+        assert!(StandardBone::LeftWaistCancel.side() == Some(Side::Left));
+        assert!(StandardBone::Center.side().is_none());
+    }
+
+    #[test]
+    fn variants() {
+        use StandardBone::*;
+        assert!(StandardBone::from_name("右足IK") == Some(RightLegIk));
+        assert!(StandardBone::from_name("右足ＩＫ親") == Some(RightLegIkParent));
+        assert!(StandardBone::from_name("ｾﾝﾀｰ") == Some(Center));
+        assert!(StandardBone::from_name("ｸﾞﾙｰﾌﾞ") == Some(Groove));
+        assert!(StandardBone::from_name("上半身２") == Some(UpperBody2));
+        assert!(StandardBone::from_name("左親指0") == Some(LeftThumb0));
+        assert!(StandardBone::from_name("腕.L") == Some(LeftArm));
+        assert!(StandardBone::from_name("ひじ_R") == Some(RightElbow));
+        assert!(StandardBone::from_name("左腰キャンセル") == Some(LeftWaistCancel));
+        assert!(StandardBone::from_name("腕").is_none());
+        assert!(normalize_name("ﾊﾟﾝﾂ") == "パンツ");
+    }
+
+    #[test]
+    fn alicia() {
+        let pmx = read_pmx();
+        let arm = pmx.standard_bone(StandardBone::LeftArm).unwrap();
+        assert!(pmx.bones[arm].name == "左腕");
+        let ik = pmx.standard_bone(StandardBone::RightLegIk).unwrap();
+        assert!(pmx.bones[ik].ik.is_some());
+        assert!(pmx.standard_bone(StandardBone::Center).is_some());
+    }
+}
@@ -0,0 +1,216 @@
+//! Event-driven reading for models too large to hold in memory.
+//!
+//! [`visit`] decodes the file front to back and hands each element to a
+//! [`Visitor`] as soon as it is read, so only one element is alive at a time.
+//! Faces are delivered in chunks of at most [`FACE_CHUNK`] indices.
+
+use crate::lazy::{skipper, SectionKind};
+use crate::reader::{Discard, Error, Reader};
+use crate::*;
+use std::io::Read;
+
+/// Largest number of vertex indices passed to [`Visitor::faces`] at once, a multiple of 3.
+pub const FACE_CHUNK: usize = 3 * 4096;
+
+/// What to do with a section, returned by [`Visitor::section`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Decodes every element of the section.
+    Visit,
+    /// Moves past the section without decoding it.
+    Skip,
+    /// Stops reading, leaving the rest of the file unread.
+    Stop,
+}
+
+/// Callbacks invoked by [`visit`] in file order. Every method does nothing by default.
+///
+/// `index` is the position of the element in its section.
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn header(&mut self, header: &Header) {}
+
+    /// Called before each section with its number of elements.
+    ///
+    /// `len` is 1 for `ModelInfo` and the number of vertex indices for `Faces`.
+    fn section(&mut self, kind: SectionKind, len: usize) -> Action {
+        Action::Visit
+    }
+
+    fn model_info(&mut self, model_info: ModelInfo) {}
+
+    fn vertex(&mut self, index: usize, vertex: Vertex) {}
+
+    /// Receives vertex indices starting at `offset` in the faces section.
+    fn faces(&mut self, offset: usize, indices: &[u32]) {}
+
+    fn texture(&mut self, index: usize, path: PathBuf) {}
+
+    fn material(&mut self, index: usize, material: Material) {}
+
+    fn bone(&mut self, index: usize, bone: Bone) {}
+
+    fn morph(&mut self, index: usize, morph: Morph) {}
+
+    fn display_group(&mut self, index: usize, display_group: DisplayGroup) {}
+
+    fn rigid(&mut self, index: usize, rigid: Rigid) {}
+
+    fn joint(&mut self, index: usize, joint: Joint) {}
+
+    /// Called after the last section unless a section returned [`Action::Stop`].
+    fn end(&mut self) {}
+}
+
+/// Reads a PMX file from `reader`, passing its contents to `visitor`.
+pub fn visit<R, V>(reader: R, visitor: &mut V) -> Result<(), Error>
+where
+    R: Read,
+    V: Visitor + ?Sized,
+{
+    let mut reader = Reader::new(Discard(reader));
+    let header = reader.header()?;
+    reader.set_header(&header);
+    visitor.header(&header);
+    for kind in SectionKind::all() {
+        let len = match kind {
+            SectionKind::ModelInfo => 1,
+            _ => reader.read_u32()? as usize,
+        };
+        let action = visitor.section(kind, len);
+        match (action, kind) {
+            (Action::Stop, _) => return Ok(()),
+            (Action::Skip, SectionKind::ModelInfo) => reader.skip_model_info()?,
+            (Action::Skip, SectionKind::Faces) => reader.skip_faces(len as u32)?,
+            (Action::Skip, _) => reader.skip_elements(len as u32, skipper(kind))?,
+            (Action::Visit, _) => visit_section(&mut reader, kind, len, visitor)?,
+        }
+    }
+    visitor.end();
+    Ok(())
+}
+
+fn visit_section<R, V>(
+    reader: &mut Reader<Discard<R>>,
+    kind: SectionKind,
+    len: usize,
+    visitor: &mut V,
+) -> Result<(), Error>
+where
+    R: Read,
+    V: Visitor + ?Sized,
+{
+    match kind {
+        SectionKind::ModelInfo => visitor.model_info(reader.model_info()?),
+        SectionKind::Faces => {
+            let mut chunk = Vec::with_capacity(FACE_CHUNK.min(len));
+            let mut offset = 0;
+            while offset < len {
+                let n = FACE_CHUNK.min(len - offset);
+                chunk.clear();
+                reader.read_faces_into(n, &mut chunk)?;
+                visitor.faces(offset, &chunk);
+                offset += n;
+            }
+        }
+        _ => {
+            for i in 0..len {
+                match kind {
+                    SectionKind::Vertices => visitor.vertex(i, reader.vertex()?),
+                    SectionKind::Textures => visitor.texture(i, reader.texture()?),
+                    SectionKind::Materials => visitor.material(i, reader.material()?),
+                    SectionKind::Bones => visitor.bone(i, reader.bone()?),
+                    SectionKind::Morphs => visitor.morph(i, reader.morph()?),
+                    SectionKind::DisplayGroups => visitor.display_group(i, reader.display_group()?),
+                    SectionKind::Rigids => visitor.rigid(i, reader.rigid()?),
+                    SectionKind::Joints => visitor.joint(i, reader.joint()?),
+                    SectionKind::ModelInfo | SectionKind::Faces => unreachable!(),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_data() -> Vec<u8> {
+        std::fs::read("resource/Alicia/Alicia_solid.pmx").unwrap()
+    }
+
+    #[derive(Default)]
+    struct Collect {
+        sections: Vec<(SectionKind, usize)>,
+        positions: Vec<[f32; 3]>,
+        faces: Vec<u32>,
+        chunks: usize,
+        bones: Vec<String>,
+        joints: usize,
+        ended: bool,
+    }
+
+    impl Visitor for Collect {
+        fn section(&mut self, kind: SectionKind, len: usize) -> Action {
+            self.sections.push((kind, len));
+            match kind {
+                SectionKind::Morphs | SectionKind::Materials => Action::Skip,
+                SectionKind::Rigids => Action::Stop,
+                _ => Action::Visit,
+            }
+        }
+
+        fn vertex(&mut self, index: usize, vertex: Vertex) {
+            assert!(index == self.positions.len());
+            self.positions.push(vertex.position);
+        }
+
+        fn faces(&mut self, offset: usize, indices: &[u32]) {
+            assert!(offset == self.faces.len());
+            assert!(indices.len() <= FACE_CHUNK);
+            self.faces.extend_from_slice(indices);
+            self.chunks += 1;
+        }
+
+        fn bone(&mut self, _: usize, bone: Bone) {
+            self.bones.push(bone.name);
+        }
+
+        fn joint(&mut self, _: usize, _: Joint) {
+            self.joints += 1;
+        }
+
+        fn end(&mut self) {
+            self.ended = true;
+        }
+    }
+
+    #[test]
+    fn alicia() {
+        let data = read_data();
+        let pmx = crate::read(data.as_slice()).unwrap();
+        let mut collect = Collect::default();
+        visit(data.as_slice(), &mut collect).unwrap();
+
+        assert!(collect.positions.len() == pmx.vertices.len());
+        assert!(collect.positions[100] == pmx.vertices[100].position);
+        assert!(collect.faces == pmx.faces);
+        assert!(collect.chunks == pmx.faces.len().div_ceil(FACE_CHUNK));
+        assert!(collect.bones.len() == pmx.bones.len());
+        assert!(collect.sections.len() == 9);
+        assert!(collect.sections[2] == (SectionKind::Faces, pmx.faces.len()));
+        assert!(collect.joints == 0);
+        assert!(!collect.ended);
+    }
+
+    #[test]
+    fn errors() {
+        struct Nothing;
+        impl Visitor for Nothing {}
+
+        let data = read_data();
+        assert!(visit(data.as_slice(), &mut Nothing).is_ok());
+        assert!(visit(&data[..data.len() - 1], &mut Nothing).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Resolution of texture paths against the file system.
+//!
+//! Texture paths in PMX files are Windows-style relative paths. They use
+//! backslashes, rely on a case-insensitive file system, and point at files
+//! whose names were often extracted from archives as raw Shift-JIS bytes.
+
+use crate::*;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(feature = "textures")]
+mod decode;
+#[cfg(feature = "textures")]
+mod toon;
+
+#[cfg(feature = "textures")]
+pub use decode::*;
+#[cfg(feature = "textures")]
+pub use toon::shared_toon;
+
+/// Returns the file name of a shared toon texture, e.g. `toon01.bmp` for `Toon::Shared(0)`.
+pub fn shared_toon_name(index: u32) -> String {
+    format!("toon{:02}.bmp", index + 1)
+}
+
+#[derive(Clone, Debug)]
+pub enum Unresolved {
+    Texture(usize),
+    SharedToon { material: usize, index: u32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Resolution {
+    /// Concrete paths, indexed like `Pmx::textures`.
+    pub textures: Vec<Option<PathBuf>>,
+    /// Concrete toon paths, indexed like `Pmx::materials`.
+    pub toons: Vec<Option<PathBuf>>,
+    pub unresolved: Vec<Unresolved>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    model_dir: PathBuf,
+    toon_dir: Option<PathBuf>,
+}
+
+impl Resolver {
+    pub fn new(model_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            model_dir: model_dir.into(),
+            toon_dir: None,
+        }
+    }
+
+    /// Sets the directory searched for shared toon textures missing from the model directory.
+    pub fn toon_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.toon_dir = Some(dir.into());
+        self
+    }
+
+    /// Resolves a raw texture path relative to the model directory.
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        resolve_in(&self.model_dir, path)
+    }
+
+    pub fn resolve_toon(&self, pmx: &Pmx, toon: &Toon) -> Option<PathBuf> {
+        match toon {
+            Toon::Texture(index) => self.resolve(pmx.textures.get((*index)?)?),
+            Toon::Shared(index) => {
+                let name = PathBuf::from(shared_toon_name(*index));
+                self.resolve(&name)
+                    .or_else(|| resolve_in(self.toon_dir.as_ref()?, &name))
+            }
+        }
+    }
+
+    pub fn resolve_all(&self, pmx: &Pmx) -> Resolution {
+        let mut unresolved = vec![];
+        let textures = pmx
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let resolved = self.resolve(path);
+                if resolved.is_none() {
+                    unresolved.push(Unresolved::Texture(i));
+                }
+                resolved
+            })
+            .collect::<Vec<_>>();
+        let toons = pmx
+            .materials
+            .iter()
+            .enumerate()
+            .map(|(material, m)| match m.toon {
+                Toon::Texture(index) => index.and_then(|i| textures.get(i).cloned().flatten()),
+                Toon::Shared(index) => {
+                    let resolved = self.resolve_toon(pmx, &m.toon);
+                    if resolved.is_none() {
+                        unresolved.push(Unresolved::SharedToon { material, index });
+                    }
+                    resolved
+                }
+            })
+            .collect();
+        Resolution {
+            textures,
+            toons,
+            unresolved,
+        }
+    }
+}
+
+impl Pmx {
+    /// Resolves every texture and toon against `model_dir`, the directory containing the PMX file.
+    pub fn resolve_textures(&self, model_dir: impl Into<PathBuf>) -> Resolution {
+        Resolver::new(model_dir).resolve_all(self)
+    }
+}
+
+fn resolve_in(dir: &Path, path: &Path) -> Option<PathBuf> {
+    let raw = path.to_string_lossy().replace('\\', "/");
+    let relative = Path::new(&raw);
+    let exact = dir.join(relative);
+    if exact.is_file() {
+        return Some(exact);
+    }
+    let mut current = dir.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) => current = find_entry(&current, name)?,
+            Component::ParentDir => current.push(".."),
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    current.is_file().then_some(current)
+}
+
+/// Finds a directory entry by exact name, then case-insensitively, then as a Shift-JIS name.
+fn find_entry(dir: &Path, name: &OsStr) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+    let wanted = name.to_string_lossy().to_lowercase();
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            decode_file_name(&entry.file_name())
+                .iter()
+                .any(|candidate| candidate.to_lowercase() == wanted)
+        })
+        .map(|entry| entry.path())
+}
+
+fn decode_file_name(name: &OsStr) -> Vec<String> {
+    let mut names = vec![name.to_string_lossy().into_owned()];
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let bytes = name.as_bytes();
+        if std::str::from_utf8(bytes).is_err() {
+            let (decoded, _, had_errors) = encoding_rs::SHIFT_JIS.decode(bytes);
+            if !had_errors {
+                names.push(decoded.into_owned());
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pmx_rs_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn alicia() {
+        let pmx = read_pmx();
+        let resolution = pmx.resolve_textures("resource/Alicia");
+        assert!(resolution.textures[2] == Some(PathBuf::from("resource/Alicia/Alicia_body.tga")));
+        assert!(resolution.textures[0].is_none());
+        assert!(resolution
+            .unresolved
+            .iter()
+            .any(|u| matches!(u, Unresolved::Texture(0))));
+        assert!(resolution
+            .unresolved
+            .iter()
+            .any(|u| matches!(u, Unresolved::SharedToon { index: 3, .. })));
+    }
+
+    #[test]
+    fn separators_and_case() {
+        let dir = temp_dir("case");
+        std::fs::create_dir_all(dir.join("Tex")).unwrap();
+        std::fs::write(dir.join("Tex/Body.PNG"), b"").unwrap();
+        let resolver = Resolver::new(&dir);
+        assert!(resolver.resolve(Path::new("tex\\body.png")) == Some(dir.join("Tex/Body.PNG")));
+        assert!(resolver.resolve(Path::new("tex\\face.png")).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn shared_toon() {
+        let dir = temp_dir("toon");
+        std::fs::write(dir.join("toon04.bmp"), b"").unwrap();
+        let pmx = read_pmx();
+        let resolver = Resolver::new("resource/Alicia").toon_dir(&dir);
+        assert!(shared_toon_name(0) == "toon01.bmp");
+        assert!(resolver.resolve_toon(&pmx, &Toon::Shared(3)) == Some(dir.join("toon04.bmp")));
+        assert!(resolver.resolve_toon(&pmx, &Toon::Shared(4)).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shift_jis() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = temp_dir("sjis");
+        let (name, _, _) = encoding_rs::SHIFT_JIS.encode("髪.bmp");
+        std::fs::write(dir.join(OsStr::from_bytes(&name)), b"").unwrap();
+        let resolved = Resolver::new(&dir).resolve(Path::new("髪.bmp"));
+        assert!(resolved == Some(dir.join(OsStr::from_bytes(&name))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
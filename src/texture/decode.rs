@@ -0,0 +1,151 @@
+use super::Resolver;
+use crate::*;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unresolved texture path")]
+    Unresolved,
+    #[error("unknown image format")]
+    UnknownFormat,
+    #[error("image error: {}", .0)]
+    Image(image::ImageError),
+    #[error("io error: {}", .0)]
+    Io(std::io::Error),
+}
+
+impl From<image::ImageError> for Error {
+    fn from(src: image::ImageError) -> Self {
+        Self::Image(src)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(src: std::io::Error) -> Self {
+        Self::Io(src)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Format {
+    Bmp,
+    Tga,
+    Png,
+    Jpeg,
+    Dds,
+}
+
+impl Format {
+    /// Detects the format from magic bytes. `.sph` and `.spa` files are BMPs and detect as such.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"DDS ") {
+            Some(Self::Dds)
+        } else if is_tga(data) {
+            Some(Self::Tga)
+        } else {
+            None
+        }
+    }
+}
+
+/// TGA has no magic number, so the header fields are checked for plausible values instead.
+fn is_tga(data: &[u8]) -> bool {
+    if data.len() < 18 {
+        return false;
+    }
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let depth = data[16];
+    matches!(color_map_type, 0 | 1)
+        && matches!(image_type, 1 | 2 | 3 | 9 | 10 | 11)
+        && matches!(depth, 8 | 15 | 16 | 24 | 32)
+}
+
+/// Decodes an image into RGBA8 regardless of its extension.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Error> {
+    let format = match Format::detect(data).ok_or(Error::UnknownFormat)? {
+        Format::Bmp => image::ImageFormat::Bmp,
+        Format::Tga => image::ImageFormat::Tga,
+        Format::Png => image::ImageFormat::Png,
+        Format::Jpeg => image::ImageFormat::Jpeg,
+        Format::Dds => {
+            let decoder = image::codecs::dds::DdsDecoder::new(std::io::Cursor::new(data))?;
+            return Ok(image::DynamicImage::from_decoder(decoder)?.into_rgba8());
+        }
+    };
+    Ok(image::load_from_memory_with_format(data, format)?.into_rgba8())
+}
+
+#[derive(Debug, Default)]
+pub struct Images {
+    /// Decoded images, keyed by index into `Pmx::textures`.
+    pub images: HashMap<usize, RgbaImage>,
+    pub errors: Vec<(usize, Error)>,
+}
+
+impl Resolver {
+    pub fn load(&self, path: &std::path::Path) -> Result<RgbaImage, Error> {
+        let path = self.resolve(path).ok_or(Error::Unresolved)?;
+        decode(&std::fs::read(path)?)
+    }
+
+    /// Loads every texture referenced by `Pmx::textures`.
+    pub fn load_all(&self, pmx: &Pmx) -> Images {
+        let mut images = Images::default();
+        for (i, path) in pmx.textures.iter().enumerate() {
+            match self.load(path) {
+                Ok(image) => {
+                    images.images.insert(i, image);
+                }
+                Err(e) => images.errors.push((i, e)),
+            }
+        }
+        images
+    }
+}
+
+impl Pmx {
+    /// Loads every texture relative to `model_dir`, the directory containing the PMX file.
+    pub fn load_textures(&self, model_dir: impl Into<PathBuf>) -> Images {
+        Resolver::new(model_dir).load_all(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn detect() {
+        let tga = std::fs::read("resource/Alicia/Alicia_body.tga").unwrap();
+        let sphere = std::fs::read("resource/Alicia/hair_s.bmp").unwrap();
+        assert!(Format::detect(&tga) == Some(Format::Tga));
+        assert!(Format::detect(&sphere) == Some(Format::Bmp));
+        assert!(Format::detect(b"DDS \0\0\0\0") == Some(Format::Dds));
+        assert!(Format::detect(b"text").is_none());
+    }
+
+    #[test]
+    fn load_alicia() {
+        let pmx = read_pmx();
+        let images = pmx.load_textures("resource/Alicia");
+        assert!(images.images.len() + images.errors.len() == pmx.textures.len());
+        let body = &images.images[&2];
+        assert!(body.width() == 1024 && body.height() == 1024);
+        assert!(images.images.contains_key(&1));
+        assert!(matches!(images.errors[0], (0, Error::Unresolved)));
+    }
+}
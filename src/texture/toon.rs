@@ -0,0 +1,68 @@
+use super::Images;
+use crate::*;
+use image::{Rgba, RgbaImage};
+use std::borrow::Cow;
+
+const SIZE: u32 = 32;
+
+/// Shade color, first shaded row and blend height of each built-in ramp.
+///
+/// These are procedural approximations of MMD's `toon01.bmp` to `toon10.bmp`:
+/// white in the lit upper rows, blending into the shade color further down.
+const RAMPS: [([u8; 3], u32, u32); 10] = [
+    ([205, 205, 205], 16, 1),
+    ([245, 222, 204], 16, 1),
+    ([153, 153, 153], 16, 1),
+    ([250, 214, 199], 16, 1),
+    ([240, 204, 219], 16, 1),
+    ([209, 209, 230], 16, 1),
+    ([186, 186, 186], 8, 16),
+    ([199, 219, 245], 8, 16),
+    ([222, 204, 237], 8, 16),
+    ([230, 230, 230], 8, 16),
+];
+
+/// Returns the built-in ramp for `Toon::Shared(index)`, or `None` if `index` is not in `0..10`.
+pub fn shared_toon(index: u32) -> Option<RgbaImage> {
+    let &(shade, start, blend) = RAMPS.get(index as usize)?;
+    Some(RgbaImage::from_fn(SIZE, SIZE, |_, y| {
+        let t = (y.saturating_sub(start) as f32 / blend as f32).min(1.0);
+        let [r, g, b] = shade.map(|c| (255.0 + (c as f32 - 255.0) * t).round() as u8);
+        Rgba([r, g, b, 255])
+    }))
+}
+
+impl Images {
+    /// Returns the image a material samples for `toon`, falling back to the built-in shared ramps.
+    pub fn toon(&self, toon: &Toon) -> Option<Cow<'_, RgbaImage>> {
+        match toon {
+            Toon::Texture(index) => self.images.get(&(*index)?).map(Cow::Borrowed),
+            Toon::Shared(index) => shared_toon(*index).map(Cow::Owned),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps() {
+        for i in 0..10 {
+            let toon = shared_toon(i).unwrap();
+            assert!(toon.dimensions() == (SIZE, SIZE));
+            assert!(toon.get_pixel(0, 0).0 == [255, 255, 255, 255]);
+            assert!(toon.get_pixel(0, SIZE - 1).0[..3] == RAMPS[i as usize].0);
+        }
+        assert!(shared_toon(10).is_none());
+    }
+
+    #[test]
+    fn toon_value() {
+        let mut images = Images::default();
+        images.images.insert(1, RgbaImage::new(2, 2));
+        assert!(images.toon(&Toon::Texture(Some(1))).unwrap().dimensions() == (2, 2));
+        assert!(images.toon(&Toon::Texture(None)).is_none());
+        assert!(images.toon(&Toon::Shared(3)).unwrap().dimensions() == (SIZE, SIZE));
+    }
+}
@@ -0,0 +1,352 @@
+//! Translation of Japanese names into English.
+//!
+//! Names are first looked up whole, then as a side (`左`/`右`) and a trailing number
+//! around words that are translated one by one, so `左髪３` becomes `hair3_L`.
+
+use crate::standard::{normalize_name, split_side, Side, StandardBone};
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    Bone,
+    Morph,
+    Material,
+    DisplayGroup,
+}
+
+const BONES: &[(&str, &str)] = &[
+    ("おっぱい", "bust"),
+    ("胸", "chest"),
+    ("舌", "tongue"),
+    ("口", "mouth"),
+    ("顎", "jaw"),
+    ("目光", "eye light"),
+    ("前髪", "front hair"),
+    ("後髪", "back hair"),
+    ("横髪", "side hair"),
+    ("もみあげ", "sideburns"),
+    ("アホ毛", "ahoge"),
+    ("三つ編み", "braid"),
+    ("ポニテ", "ponytail"),
+    ("ツインテ", "twintail"),
+];
+
+const MORPHS: &[(&str, &str)] = &[
+    ("あ", "a"),
+    ("い", "i"),
+    ("う", "u"),
+    ("え", "e"),
+    ("お", "o"),
+    ("ん", "n"),
+    ("▲", "mouth triangle"),
+    ("∧", "mouth hat"),
+    ("Λ", "mouth hat"),
+    ("■", "mouth square"),
+    ("ω", "omega"),
+    ("ワ", "wa"),
+    ("つ", "tsu"),
+    ("べ", "tongue out"),
+    ("ぺろっ", "tongue out"),
+    ("呆", "dumb"),
+    ("怒", "angry mouth"),
+    ("にっこり", "grin"),
+    ("にやり", "smirk"),
+    ("口角上げ", "mouth corner up"),
+    ("口角下げ", "mouth corner down"),
+    ("口角広げ", "mouth wide"),
+    ("口横広げ", "mouth wide"),
+    ("歯無し上", "no upper teeth"),
+    ("歯無し下", "no lower teeth"),
+    ("まばたき", "blink"),
+    ("笑い", "smile"),
+    ("ウィンク", "wink"),
+    ("ウィンク右", "wink R"),
+    ("ウィンク２", "wink 2"),
+    ("ウィンク２右", "wink 2 R"),
+    ("なごみ", "calm"),
+    ("はぅ", "><"),
+    ("びっくり", "surprised"),
+    ("じと目", "half-closed eyes"),
+    ("キリッ", "sharp eyes"),
+    ("はちゅ目", "round eyes"),
+    ("はちゅ目縦潰れ", "round eyes flat vertical"),
+    ("はちゅ目横潰れ", "round eyes flat horizontal"),
+    ("星目", "star eyes"),
+    ("はぁと", "heart eyes"),
+    ("瞳小", "small pupils"),
+    ("瞳大", "big pupils"),
+    ("瞳縦潰れ", "pupils flat vertical"),
+    ("ハイライト消", "no highlight"),
+    ("まぶた下上げ", "lower eyelid up"),
+    ("真面目", "serious"),
+    ("困る", "troubled"),
+    ("にこり", "cheerful"),
+    ("怒り", "anger"),
+    ("上", "brow up"),
+    ("下", "brow down"),
+    ("前", "brow front"),
+    ("寄せ", "brow together"),
+    ("照れ", "blush"),
+    ("照れ２", "blush 2"),
+    ("照れ消し", "no blush"),
+    ("照れ２消し", "no blush 2"),
+    ("がーん", "shock"),
+    ("涙", "tears"),
+    ("青ざめ", "pale"),
+    ("悔", "frustrated"),
+    ("俯瞰煽り", "look up down"),
+    ("輪郭右", "contour R"),
+    ("輪郭左", "contour L"),
+    ("髪影消し", "no hair shadow"),
+    ("まつげ線太く", "thick eyelashes"),
+    ("舌光沢", "tongue gloss"),
+];
+
+const MATERIALS: &[(&str, &str)] = &[
+    ("肌", "skin"),
+    ("顔", "face"),
+    ("体", "body"),
+    ("髪", "hair"),
+    ("目", "eye"),
+    ("白目", "eye white"),
+    ("瞳", "pupil"),
+    ("ハイライト", "highlight"),
+    ("眉", "eyebrow"),
+    ("まつげ", "eyelash"),
+    ("口", "mouth"),
+    ("歯", "teeth"),
+    ("舌", "tongue"),
+    ("服", "clothes"),
+    ("靴", "shoes"),
+    ("靴下", "socks"),
+    ("頬", "cheek"),
+    ("影", "shadow"),
+    ("下着", "underwear"),
+];
+
+const DISPLAY_GROUPS: &[(&str, &str)] = &[
+    ("表情", "Exp"),
+    ("センター", "center"),
+    ("ＩＫ", "IK"),
+    ("体(上)", "upper body"),
+    ("体(下)", "lower body"),
+    ("髪", "hair"),
+    ("腕", "arms"),
+    ("指", "fingers"),
+    ("足", "legs"),
+    ("目", "eyes"),
+    ("その他", "other"),
+    ("物理", "physics"),
+    ("服", "clothes"),
+];
+
+/// Words used to translate names that are not in a table as a whole.
+const WORDS: &[(&str, &str)] = &[
+    ("髪", "hair"),
+    ("前髪", "front hair"),
+    ("後髪", "back hair"),
+    ("横髪", "side hair"),
+    ("三つ編み", "braid"),
+    ("リボン", "ribbon"),
+    ("スカート", "skirt"),
+    ("袖", "sleeve"),
+    ("襟", "collar"),
+    ("ネクタイ", "necktie"),
+    ("マフラー", "scarf"),
+    ("帽子", "hat"),
+    ("尻尾", "tail"),
+    ("耳", "ear"),
+    ("武器", "weapon"),
+    ("剣", "sword"),
+    ("おっぱい", "bust"),
+    ("胸", "chest"),
+    ("目", "eye"),
+    ("光", "light"),
+    ("舌", "tongue"),
+    ("腕", "arm"),
+    ("ひじ", "elbow"),
+    ("手首", "wrist"),
+    ("手", "hand"),
+    ("指", "finger"),
+    ("足", "leg"),
+    ("ひざ", "knee"),
+    ("足首", "ankle"),
+    ("つま先", "toe"),
+    ("回転", "rotation"),
+    ("捩", "twist"),
+    ("先", "tip"),
+    ("親", "parent"),
+    ("IK", "IK"),
+    ("消し", "off"),
+    ("消", "off"),
+];
+
+/// Returns the English name of `name`, or `None` if part of it is unknown.
+///
+/// Names already written in ASCII are returned as they are.
+pub fn translate(category: Category, name: &str) -> Option<String> {
+    let name = normalize_name(name);
+    if name.is_empty() {
+        return None;
+    }
+    if name.is_ascii() {
+        return Some(name);
+    }
+    if category == Category::Bone {
+        if let Some(bone) = StandardBone::from_name(&name) {
+            return Some(bone.name_en().into());
+        }
+    }
+    let table = match category {
+        Category::Bone => BONES,
+        Category::Morph => MORPHS,
+        Category::Material => MATERIALS,
+        Category::DisplayGroup => DISPLAY_GROUPS,
+    };
+    let lookup = |table: &[(&str, &'static str)], name: &str| {
+        table
+            .iter()
+            .find(|(jp, _)| normalize_name(jp) == name)
+            .map(|(_, en)| *en)
+    };
+    if let Some(en) = lookup(table, &name) {
+        return Some(en.into());
+    }
+
+    let (side, base) = match split_side(&name) {
+        Some((side, base)) => (Some(side), base),
+        None => (None, name.as_str()),
+    };
+    let words = base.trim_end_matches(|c: char| c.is_ascii_digit() || "_.-".contains(c));
+    let number = &base[words.len()..];
+    let mut english = vec![];
+    let mut rest = words;
+    while !rest.is_empty() {
+        // prefer the longest word at each position
+        let (len, en) = table
+            .iter()
+            .chain(WORDS)
+            .map(|(jp, en)| (normalize_name(jp), *en))
+            .filter(|(jp, _)| rest.starts_with(jp.as_str()))
+            .map(|(jp, en)| (jp.len(), en))
+            .max_by_key(|(len, _)| *len)?;
+        english.push(en);
+        rest = &rest[len..];
+    }
+    if english.is_empty() {
+        return None;
+    }
+    let side = match side {
+        Some(Side::Left) => "_L",
+        Some(Side::Right) => "_R",
+        None => "",
+    };
+    Some(format!("{}{}{}", english.join(" "), number, side))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TranslateOptions {
+    /// Replaces existing English names instead of only filling empty ones.
+    pub overwrite: bool,
+}
+
+/// Indices of the elements left without an English name.
+#[derive(Clone, Debug, Default)]
+pub struct Untranslated {
+    pub bones: Vec<usize>,
+    pub morphs: Vec<usize>,
+    pub materials: Vec<usize>,
+    pub display_groups: Vec<usize>,
+}
+
+impl Untranslated {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+            && self.morphs.is_empty()
+            && self.materials.is_empty()
+            && self.display_groups.is_empty()
+    }
+}
+
+impl Pmx {
+    /// Fills `name_en` of bones, morphs, materials and display groups from the built-in tables.
+    pub fn translate_names(&mut self, options: &TranslateOptions) -> Untranslated {
+        let bones = self.bones.iter_mut().map(|b| (&b.name, &mut b.name_en));
+        let morphs = self.morphs.iter_mut().map(|m| (&m.name, &mut m.name_en));
+        let materials = self.materials.iter_mut().map(|m| (&m.name, &mut m.name_en));
+        let groups = self
+            .display_groups
+            .iter_mut()
+            .map(|g| (&g.name, &mut g.name_en));
+        Untranslated {
+            bones: translate_all(Category::Bone, bones, options),
+            morphs: translate_all(Category::Morph, morphs, options),
+            materials: translate_all(Category::Material, materials, options),
+            display_groups: translate_all(Category::DisplayGroup, groups, options),
+        }
+    }
+}
+
+fn translate_all<'a>(
+    category: Category,
+    names: impl Iterator<Item = (&'a String, &'a mut String)>,
+    options: &TranslateOptions,
+) -> Vec<usize> {
+    let mut untranslated = vec![];
+    for (i, (name, name_en)) in names.enumerate() {
+        if !options.overwrite && !name_en.is_empty() {
+            continue;
+        }
+        match translate(category, name) {
+            Some(en) => *name_en = en,
+            None if name_en.is_empty() => untranslated.push(i),
+            None => {}
+        }
+    }
+    untranslated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn names() {
+        assert!(translate(Category::Bone, "右足ＩＫ").unwrap() == "leg IK_R");
+        assert!(translate(Category::Bone, "左髪３").unwrap() == "hair3_L");
+        assert!(translate(Category::Bone, "前髪１＿２").unwrap() == "front hair1_2");
+        assert!(translate(Category::Bone, "右手首回転").unwrap() == "wrist rotation_R");
+        assert!(translate(Category::Morph, "まばたき").unwrap() == "blink");
+        assert!(translate(Category::Morph, "ウィンク２右").unwrap() == "wink 2 R");
+        assert!(translate(Category::Material, "hair").unwrap() == "hair");
+        assert!(translate(Category::DisplayGroup, "体(上)").unwrap() == "upper body");
+        assert!(translate(Category::Morph, "ほげ").is_none());
+    }
+
+    #[test]
+    fn alicia() {
+        let mut pmx = read_pmx();
+        let untranslated = pmx.translate_names(&TranslateOptions::default());
+        let arm = pmx.standard_bone(StandardBone::LeftArm).unwrap();
+        assert!(pmx.bones[arm].name_en == "arm_L");
+        assert!(pmx.morphs[0].name_en == "a");
+        assert!(pmx.display_groups[0].name_en == "Root");
+        assert!(untranslated.materials.is_empty());
+        assert!(untranslated.bones.len() < 10);
+        for &i in untranslated.bones.iter() {
+            assert!(pmx.bones[i].name_en.is_empty());
+        }
+
+        pmx.morphs[0].name_en = "custom".into();
+        pmx.translate_names(&TranslateOptions::default());
+        assert!(pmx.morphs[0].name_en == "custom");
+        pmx.translate_names(&TranslateOptions { overwrite: true });
+        assert!(pmx.morphs[0].name_en == "a");
+    }
+}
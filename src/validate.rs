@@ -0,0 +1,149 @@
+//! Checks that a model can be written back as a valid PMX file.
+
+use crate::edit::{Dropped, Kind, Report};
+use crate::*;
+
+#[derive(Clone, PartialEq, Debug, thiserror::Error)]
+pub enum Issue {
+    #[error("unsupported version: {}", .0)]
+    UnsupportedVersion(f32),
+    #[error("extended uv count out of range: {}", .0)]
+    ExtendedUvCount(u8),
+    #[error("{:?} index size {} is too small, {} required", .kind, .size, .required)]
+    IndexSize { kind: Kind, size: u8, required: u8 },
+    #[error("face index count {} is not a multiple of 3", .0)]
+    FaceCount(usize),
+    #[error("materials use {} face indices but there are {}", .materials, .faces)]
+    MaterialIndexCount { materials: usize, faces: usize },
+    #[error("{:?} reference out of range: {:?}", .0, .1)]
+    OutOfRange(Kind, Dropped),
+    #[error("vertex {} has non-finite attributes", .0)]
+    NonFinite(usize),
+}
+
+impl Pmx {
+    /// Returns every problem that would make this model an invalid PMX file.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        if self.header.version != 2.0 && self.header.version != 2.1 {
+            issues.push(Issue::UnsupportedVersion(self.header.version));
+        }
+        if self.header.extended_uv > 4 {
+            issues.push(Issue::ExtendedUvCount(self.header.extended_uv));
+        }
+        let sizes = self.header.index_sizes();
+        let required = self.minimal_index_sizes();
+        let pairs = [
+            (Kind::Vertex, sizes.vertex, required.vertex),
+            (Kind::Texture, sizes.texture, required.texture),
+            (Kind::Material, sizes.material, required.material),
+            (Kind::Bone, sizes.bone, required.bone),
+            (Kind::Morph, sizes.morph, required.morph),
+            (Kind::Rigid, sizes.rigid, required.rigid),
+        ];
+        for (kind, size, required) in pairs {
+            if size < required {
+                issues.push(Issue::IndexSize {
+                    kind,
+                    size,
+                    required,
+                });
+            }
+        }
+        if !self.faces.len().is_multiple_of(3) {
+            issues.push(Issue::FaceCount(self.faces.len()));
+        }
+        let materials = self
+            .materials
+            .iter()
+            .map(|m| m.index_count as usize)
+            .sum::<usize>();
+        if materials != self.faces.len() {
+            issues.push(Issue::MaterialIndexCount {
+                materials,
+                faces: self.faces.len(),
+            });
+        }
+        for (i, v) in self.vertices.iter().enumerate() {
+            let finite = v
+                .position
+                .iter()
+                .chain(&v.normal)
+                .chain(&v.uv)
+                .chain(v.extended_uv.iter().flatten())
+                .all(|x| x.is_finite());
+            if !finite {
+                issues.push(Issue::NonFinite(i));
+            }
+        }
+        let vertices = self.vertices.len();
+        for (triangle, face) in self.faces.chunks(3).enumerate() {
+            if face.iter().any(|&i| i as usize >= vertices) {
+                issues.push(Issue::OutOfRange(Kind::Vertex, Dropped::Face { triangle }));
+            }
+        }
+        // rewriting with an identity map drops exactly the references that are out of range,
+        // faces are checked above so that a wrong index_count is not reported twice
+        let mut pmx = self.clone();
+        pmx.faces.clear();
+        for material in pmx.materials.iter_mut() {
+            material.index_count = 0;
+        }
+        for kind in [
+            Kind::Vertex,
+            Kind::Texture,
+            Kind::Material,
+            Kind::Bone,
+            Kind::Morph,
+            Kind::Rigid,
+        ] {
+            let map = (0..pmx.len_of(kind)).map(Some).collect::<Vec<_>>();
+            let mut report = Report::default();
+            pmx.rewrite_references(kind, &map, &mut report);
+            issues.extend(
+                report
+                    .dropped
+                    .into_iter()
+                    .map(|d| Issue::OutOfRange(kind, d)),
+            );
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    #[test]
+    fn alicia() {
+        let mut pmx = read_pmx();
+        assert!(pmx.validate().is_empty());
+
+        let len = pmx.bones.len();
+        pmx.bones[3].parent = Some(len);
+        pmx.materials[0].index_count += 3;
+        pmx.header.vertex_index_size = 1;
+        let issues = pmx.validate();
+        assert!(issues.len() == 3);
+        assert!(issues.contains(&Issue::OutOfRange(
+            Kind::Bone,
+            Dropped::BoneParent { bone: 3 }
+        )));
+        assert!(issues
+            .iter()
+            .any(|i| matches!(i, Issue::MaterialIndexCount { .. })));
+        assert!(issues.iter().any(|i| matches!(
+            i,
+            Issue::IndexSize {
+                kind: Kind::Vertex,
+                ..
+            }
+        )));
+    }
+}
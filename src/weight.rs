@@ -0,0 +1,275 @@
+//! Cleanup of vertex weights.
+
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct CleanupOptions {
+    /// Influences with a smaller weight are removed before normalization.
+    pub threshold: f32,
+    /// Maximum number of influences per vertex, clamped to `1..=4`.
+    pub max_influences: usize,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.001,
+            max_influences: 4,
+        }
+    }
+}
+
+/// Number of vertices affected by each step of the cleanup.
+#[derive(Clone, Debug, Default)]
+pub struct CleanupReport {
+    /// Vertices that referenced the same bone more than once.
+    pub merged: usize,
+    /// Vertices that lost zero-weight, boneless or below-threshold influences.
+    pub pruned: usize,
+    /// Vertices that had more than `max_influences` influences.
+    pub limited: usize,
+    /// Vertices whose weights did not sum to 1.
+    pub normalized: usize,
+    /// Vertices whose weight was changed to a simpler variant.
+    pub downgraded: usize,
+    /// Vertices without any bone to bind to, which are left untouched.
+    pub unweighted: Vec<usize>,
+}
+
+impl Pmx {
+    /// Normalizes the weights of all vertices and stores each in its simplest form.
+    ///
+    /// `Sdef` weights stay `Sdef` unless a single bone remains.
+    pub fn clean_weights(&mut self, options: &CleanupOptions) -> CleanupReport {
+        let mut report = CleanupReport::default();
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            match clean(&vertex.weight, options, &mut report) {
+                Some(weight) => vertex.weight = weight,
+                None => report.unweighted.push(i),
+            }
+        }
+        report
+    }
+}
+
+fn clean(weight: &Weight, options: &CleanupOptions, report: &mut CleanupReport) -> Option<Weight> {
+    let mut merged: Vec<(usize, f32)> = vec![];
+    let mut duplicate = false;
+    let mut pruned = false;
+    for (bone, w) in slots(weight) {
+        let Some(bone) = bone else {
+            pruned |= w != 0.0;
+            continue;
+        };
+        match merged.iter_mut().find(|(b, _)| *b == bone) {
+            Some(m) => {
+                duplicate |= w > 0.0 && m.1 > 0.0;
+                m.1 += w;
+            }
+            None => merged.push((bone, w)),
+        }
+    }
+    let mut influences = merged;
+    let max = influences.iter().map(|(_, w)| *w).fold(0.0, f32::max);
+    if max <= 0.0 {
+        return None;
+    }
+    let threshold = options.threshold.min(max).max(f32::MIN_POSITIVE);
+    let len = influences.len();
+    influences.retain(|(_, w)| *w >= threshold);
+    pruned |= influences.len() < len;
+
+    influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let max_influences = options.max_influences.clamp(1, 4);
+    let limited = influences.len() > max_influences;
+    influences.truncate(max_influences);
+
+    let sum = influences.iter().map(|(_, w)| *w).sum::<f32>();
+    let normalized = (sum - 1.0).abs() > 1e-5;
+    for (_, w) in influences.iter_mut() {
+        *w /= sum;
+    }
+
+    let cleaned = match (influences.as_slice(), weight) {
+        ([(bone, _)], _) => Weight::Bdef1(Bdef1 { bone: Some(*bone) }),
+        ([a, b], Weight::Sdef(w)) => {
+            // keep the authored order so that r0 and r1 stay with their bones
+            let (a, b) = if w.bones[0] == Some(b.0) {
+                (b, a)
+            } else {
+                (a, b)
+            };
+            Weight::Sdef(Sdef {
+                bones: [Some(a.0), Some(b.0)],
+                weight: a.1,
+                ..w.clone()
+            })
+        }
+        ([a, b], _) => Weight::Bdef2(Bdef2 {
+            bones: [Some(a.0), Some(b.0)],
+            weight: a.1,
+        }),
+        (influences, _) => {
+            let mut w = Bdef4 {
+                bones: [None; 4],
+                weights: [0.0; 4],
+            };
+            for (i, (bone, weight)) in influences.iter().enumerate() {
+                w.bones[i] = Some(*bone);
+                w.weights[i] = *weight;
+            }
+            Weight::Bdef4(w)
+        }
+    };
+    let rank = |w: &Weight| match w {
+        Weight::Bdef1(_) => 0,
+        Weight::Bdef2(_) | Weight::Sdef(_) => 1,
+        Weight::Bdef4(_) => 2,
+    };
+    let downgraded = rank(&cleaned) < rank(weight);
+
+    report.merged += usize::from(duplicate);
+    report.pruned += usize::from(pruned);
+    report.limited += usize::from(limited);
+    report.normalized += usize::from(normalized);
+    report.downgraded += usize::from(downgraded);
+    Some(cleaned)
+}
+
+/// Returns every bone slot of `weight` with its weight, as stored.
+fn slots(weight: &Weight) -> Vec<(Option<usize>, f32)> {
+    match weight {
+        Weight::Bdef1(w) => vec![(w.bone, 1.0)],
+        Weight::Bdef2(w) => vec![(w.bones[0], w.weight), (w.bones[1], 1.0 - w.weight)],
+        Weight::Bdef4(w) => w.bones.iter().copied().zip(w.weights).collect(),
+        Weight::Sdef(w) => vec![(w.bones[0], w.weight), (w.bones[1], 1.0 - w.weight)],
+    }
+}
+
+/// Returns the bones and weights of `weight`, merging duplicate bones and dropping empty influences.
+pub(crate) fn influences(weight: &Weight) -> Vec<(usize, f32)> {
+    let mut merged: Vec<(usize, f32)> = vec![];
+    for (bone, w) in slots(weight) {
+        let Some(bone) = bone.filter(|_| w > 0.0) else {
+            continue;
+        };
+        match merged.iter_mut().find(|(b, _)| *b == bone) {
+            Some(m) => m.1 += w,
+            None => merged.push((bone, w)),
+        }
+    }
+    merged
+}
+
+/// Builds the simplest weight for `influences`, keeping the four largest and normalizing them.
+pub(crate) fn from_influences(mut influences: Vec<(usize, f32)>) -> Weight {
+    influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut w = Bdef4 {
+        bones: [None; 4],
+        weights: [0.0; 4],
+    };
+    for (i, (bone, weight)) in influences.into_iter().take(4).enumerate() {
+        w.bones[i] = Some(bone);
+        w.weights[i] = weight;
+    }
+    let weight = Weight::Bdef4(w);
+    let options = CleanupOptions {
+        threshold: 0.0,
+        max_influences: 4,
+    };
+    clean(&weight, &options, &mut CleanupReport::default())
+        .unwrap_or(Weight::Bdef1(Bdef1 { bone: None }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_pmx() -> Pmx {
+        let file = std::fs::File::open("resource/Alicia/Alicia_solid.pmx").unwrap();
+        crate::read(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn cleaned(weight: Weight) -> (Weight, CleanupReport) {
+        let mut report = CleanupReport::default();
+        let options = CleanupOptions {
+            threshold: 0.05,
+            max_influences: 3,
+        };
+        (clean(&weight, &options, &mut report).unwrap(), report)
+    }
+
+    #[test]
+    fn bdef4() {
+        let (weight, report) = cleaned(Weight::Bdef4(Bdef4 {
+            bones: [Some(1), Some(2), Some(1), Some(3)],
+            weights: [0.2, 0.2, 0.2, 0.01],
+        }));
+        let Weight::Bdef2(w) = weight else { panic!() };
+        assert!(w.bones == [Some(1), Some(2)]);
+        assert!((w.weight - 2.0 / 3.0).abs() < 1e-6);
+        assert!(report.merged == 1);
+        assert!(report.pruned == 1);
+        assert!(report.normalized == 1);
+        assert!(report.downgraded == 1);
+
+        let (weight, report) = cleaned(Weight::Bdef4(Bdef4 {
+            bones: [Some(0), Some(1), Some(2), Some(3)],
+            weights: [0.4, 0.3, 0.2, 0.1],
+        }));
+        let Weight::Bdef4(w) = weight else { panic!() };
+        assert!(w.bones == [Some(0), Some(1), Some(2), None]);
+        assert!(report.limited == 1);
+        assert!(report.downgraded == 0);
+    }
+
+    #[test]
+    fn simplest() {
+        let (weight, report) = cleaned(Weight::Bdef2(Bdef2 {
+            bones: [Some(4), Some(5)],
+            weight: 1.0,
+        }));
+        assert!(matches!(weight, Weight::Bdef1(Bdef1 { bone: Some(4) })));
+        assert!(report.pruned == 1 && report.downgraded == 1);
+
+        let sdef = Sdef {
+            bones: [Some(4), Some(5)],
+            weight: 0.25,
+            c: [1.0; 3],
+            r0: [2.0; 3],
+            r1: [3.0; 3],
+        };
+        let (weight, report) = cleaned(Weight::Sdef(sdef));
+        let Weight::Sdef(w) = weight else { panic!() };
+        assert!(w.bones == [Some(4), Some(5)] && w.weight == 0.25 && w.r0 == [2.0; 3]);
+        assert!(report.downgraded == 0 && report.normalized == 0);
+
+        let mut report = CleanupReport::default();
+        let weight = Weight::Bdef1(Bdef1 { bone: None });
+        assert!(clean(&weight, &CleanupOptions::default(), &mut report).is_none());
+    }
+
+    #[test]
+    fn alicia() {
+        let mut pmx = read_pmx();
+        let report = pmx.clean_weights(&CleanupOptions::default());
+        assert!(report.unweighted.is_empty());
+        for v in &pmx.vertices {
+            let sum = match &v.weight {
+                Weight::Bdef1(_) => 1.0,
+                Weight::Bdef2(w) => {
+                    assert!(w.bones[0] != w.bones[1]);
+                    1.0
+                }
+                Weight::Bdef4(w) => {
+                    assert!(w.weights.iter().filter(|w| **w > 0.0).count() > 2);
+                    w.weights.iter().sum()
+                }
+                Weight::Sdef(_) => 1.0,
+            };
+            assert!((sum - 1.0f32).abs() < 1e-5);
+        }
+        let again = pmx.clean_weights(&CleanupOptions::default());
+        assert!(again.pruned == 0 && again.normalized == 0 && again.downgraded == 0);
+    }
+}
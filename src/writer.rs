@@ -0,0 +1,511 @@
+use super::*;
+use crate::reader::Error;
+use std::io::Write;
+
+pub(crate) struct Writer<T> {
+    writer: T,
+    encoding: Encoding,
+    extended_uv: usize,
+    vertex_index: usize,
+    tex_index: usize,
+    mat_index: usize,
+    bone_index: usize,
+    morph_index: usize,
+    rig_index: usize,
+}
+
+impl<T> Writer<T>
+where
+    T: Write,
+{
+    pub fn new(writer: T) -> Self {
+        Self {
+            writer,
+            encoding: Encoding::Utf16,
+            extended_uv: 0,
+            vertex_index: 0,
+            tex_index: 0,
+            mat_index: 0,
+            bone_index: 0,
+            morph_index: 0,
+            rig_index: 0,
+        }
+    }
+
+    pub fn write(&mut self, pmx: &Pmx) -> Result<(), Error> {
+        self.header(&pmx.header)?;
+        self.model_info(&pmx.model_info)?;
+        self.write_len(pmx.vertices.len())?;
+        for vertex in pmx.vertices.iter() {
+            self.vertex(vertex)?;
+        }
+        self.faces(&pmx.faces)?;
+        self.write_len(pmx.textures.len())?;
+        for texture in pmx.textures.iter() {
+            self.write_string(&texture.to_string_lossy())?;
+        }
+        self.write_len(pmx.materials.len())?;
+        for material in pmx.materials.iter() {
+            self.material(material)?;
+        }
+        self.write_len(pmx.bones.len())?;
+        for bone in pmx.bones.iter() {
+            self.bone(bone)?;
+        }
+        self.write_len(pmx.morphs.len())?;
+        for morph in pmx.morphs.iter() {
+            self.morph(morph)?;
+        }
+        self.write_len(pmx.display_groups.len())?;
+        for display_group in pmx.display_groups.iter() {
+            self.display_group(display_group)?;
+        }
+        self.write_len(pmx.rigids.len())?;
+        for rigid in pmx.rigids.iter() {
+            self.rigid(rigid)?;
+        }
+        self.write_len(pmx.joints.len())?;
+        for joint in pmx.joints.iter() {
+            self.joint(joint)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn write_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.write_bin(&[v])
+    }
+
+    fn write_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.write_bin(&v.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.write_bin(&v.to_le_bytes())
+    }
+
+    fn write_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.write_bin(&v.to_le_bytes())
+    }
+
+    fn write_f32(&mut self, v: f32) -> Result<(), Error> {
+        self.write_bin(&v.to_le_bytes())
+    }
+
+    fn write_vec<const N: usize>(&mut self, v: &[f32; N]) -> Result<(), Error> {
+        let mut buffer = [0u8; 16];
+        for (dst, v) in buffer.chunks_exact_mut(4).zip(v) {
+            dst.copy_from_slice(&v.to_le_bytes());
+        }
+        self.write_bin(&buffer[..N * 4])
+    }
+
+    fn write_len(&mut self, len: usize) -> Result<(), Error> {
+        let len = u32::try_from(len).map_err(|_| Error::InvalidData("length".into()))?;
+        self.write_u32(len)
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), Error> {
+        let buffer = match self.encoding {
+            Encoding::Utf16 => s.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Encoding::Utf8 => s.as_bytes().to_vec(),
+        };
+        self.write_len(buffer.len())?;
+        self.write_bin(&buffer)
+    }
+
+    fn write_signed_index(&mut self, index: Option<usize>, size: usize) -> Result<(), Error> {
+        let max = match size {
+            1 => i8::MAX as usize,
+            2 => i16::MAX as usize,
+            _ => i32::MAX as usize,
+        };
+        let v = match index {
+            Some(i) if i > max => {
+                return Err(Error::InvalidData(format!(
+                    "index {} with size {}",
+                    i, size
+                )))
+            }
+            Some(i) => i as i32,
+            None => -1,
+        };
+        self.write_bin(&v.to_le_bytes()[..size])
+    }
+
+    fn write_vertex_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        match (index, self.vertex_index) {
+            (Some(i), 1) if i <= u8::MAX as usize => self.write_u8(i as u8),
+            (Some(i), 2) if i <= u16::MAX as usize => self.write_u16(i as u16),
+            (index, 4) => self.write_signed_index(index, 4),
+            (index, size) => Err(Error::InvalidData(format!(
+                "vertex index {:?} with size {}",
+                index, size
+            ))),
+        }
+    }
+
+    fn write_texture_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.write_signed_index(index, self.tex_index)
+    }
+
+    fn write_material_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.write_signed_index(index, self.mat_index)
+    }
+
+    fn write_bone_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.write_signed_index(index, self.bone_index)
+    }
+
+    fn write_morph_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.write_signed_index(index, self.morph_index)
+    }
+
+    fn write_rigid_index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.write_signed_index(index, self.rig_index)
+    }
+
+    pub fn header(&mut self, header: &Header) -> Result<(), Error> {
+        let sizes = header.index_sizes();
+        let sizes = [
+            sizes.vertex,
+            sizes.texture,
+            sizes.material,
+            sizes.bone,
+            sizes.morph,
+            sizes.rigid,
+        ];
+        if sizes.iter().any(|s| !matches!(s, 1 | 2 | 4)) {
+            return Err(Error::InvalidData("header::index_size".into()));
+        }
+        self.encoding = header.encoding;
+        self.extended_uv = header.extended_uv as _;
+        [
+            self.vertex_index,
+            self.tex_index,
+            self.mat_index,
+            self.bone_index,
+            self.morph_index,
+            self.rig_index,
+        ] = sizes.map(|s| s as usize);
+        self.write_bin(b"PMX ")?;
+        self.write_f32(header.version)?;
+        self.write_u8(8)?;
+        self.write_u8(header.encoding as u8)?;
+        self.write_u8(header.extended_uv)?;
+        self.write_bin(&sizes)
+    }
+
+    pub fn model_info(&mut self, model_info: &ModelInfo) -> Result<(), Error> {
+        self.write_string(&model_info.name)?;
+        self.write_string(&model_info.name_en)?;
+        self.write_string(&model_info.comment)?;
+        self.write_string(&model_info.comment_en)
+    }
+
+    pub fn vertex(&mut self, vertex: &Vertex) -> Result<(), Error> {
+        if vertex.extended_uv.len() != self.extended_uv {
+            return Err(Error::InvalidData("vertex::extended_uv".into()));
+        }
+        self.write_vec(&vertex.position)?;
+        self.write_vec(&vertex.normal)?;
+        self.write_vec(&vertex.uv)?;
+        for uv in vertex.extended_uv.iter() {
+            self.write_vec(uv)?;
+        }
+        match &vertex.weight {
+            Weight::Bdef1(w) => {
+                self.write_u8(0)?;
+                self.write_bone_index(w.bone)?;
+            }
+            Weight::Bdef2(w) => {
+                self.write_u8(1)?;
+                self.write_bone_index(w.bones[0])?;
+                self.write_bone_index(w.bones[1])?;
+                self.write_f32(w.weight)?;
+            }
+            Weight::Bdef4(w) => {
+                self.write_u8(2)?;
+                for bone in w.bones {
+                    self.write_bone_index(bone)?;
+                }
+                self.write_vec(&w.weights)?;
+            }
+            Weight::Sdef(w) => {
+                self.write_u8(3)?;
+                self.write_bone_index(w.bones[0])?;
+                self.write_bone_index(w.bones[1])?;
+                self.write_f32(w.weight)?;
+                self.write_vec(&w.c)?;
+                self.write_vec(&w.r0)?;
+                self.write_vec(&w.r1)?;
+            }
+        }
+        self.write_f32(vertex.edge_ratio)
+    }
+
+    pub fn faces(&mut self, faces: &[u32]) -> Result<(), Error> {
+        self.write_len(faces.len())?;
+        for &f in faces {
+            self.write_vertex_index(Some(f as usize))?;
+        }
+        Ok(())
+    }
+
+    pub fn material(&mut self, material: &Material) -> Result<(), Error> {
+        self.write_string(&material.name)?;
+        self.write_string(&material.name_en)?;
+        self.write_vec(&material.diffuse)?;
+        self.write_vec(&material.specular)?;
+        self.write_f32(material.specular_power)?;
+        self.write_vec(&material.ambient)?;
+        let flags = [
+            material.both,
+            material.ground_shadow,
+            material.self_shadow_map,
+            material.self_shadow,
+            material.edge,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0u8, |flags, (i, &on)| flags | (u8::from(on) << i));
+        self.write_u8(flags)?;
+        self.write_vec(&material.edge_color)?;
+        self.write_f32(material.edge_size)?;
+        self.write_texture_index(material.texture)?;
+        self.write_texture_index(material.sphere)?;
+        self.write_u8(match material.sphere_mode {
+            SphereMode::None => 0,
+            SphereMode::Mul => 1,
+            SphereMode::Add => 2,
+            SphereMode::SubTexture => 3,
+        })?;
+        match material.toon {
+            Toon::Texture(texture) => {
+                self.write_u8(0)?;
+                self.write_texture_index(texture)?;
+            }
+            Toon::Shared(index) => {
+                let index =
+                    u8::try_from(index).map_err(|_| Error::InvalidData("material::toon".into()))?;
+                self.write_u8(1)?;
+                self.write_u8(index)?;
+            }
+        }
+        self.write_string(&material.memo)?;
+        self.write_u32(material.index_count)
+    }
+
+    pub fn bone(&mut self, bone: &Bone) -> Result<(), Error> {
+        self.write_string(&bone.name)?;
+        self.write_string(&bone.name_en)?;
+        self.write_vec(&bone.position)?;
+        self.write_bone_index(bone.parent)?;
+        self.write_i32(bone.deform_hierarchy)?;
+        let addition = bone
+            .addition
+            .as_ref()
+            .filter(|a| a.rotation || a.translation);
+        let mut flags = 0u16;
+        for (bit, on) in [
+            (0x0001, matches!(bone.connected_to, ConnectedTo::Bone(_))),
+            (0x0002, bone.rotatable),
+            (0x0004, bone.translatable),
+            (0x0008, bone.visibility),
+            (0x0010, bone.operable),
+            (0x0020, bone.ik.is_some()),
+            (0x0080, addition.is_some_and(|a| a.local)),
+            (0x0100, addition.is_some_and(|a| a.rotation)),
+            (0x0200, addition.is_some_and(|a| a.translation)),
+            (0x0400, bone.fixed_pole.is_some()),
+            (0x0800, bone.local_pole.is_some()),
+            (0x1000, bone.after_physics),
+            (0x2000, bone.external_parent.is_some()),
+        ] {
+            if on {
+                flags |= bit;
+            }
+        }
+        self.write_u16(flags)?;
+        match bone.connected_to {
+            ConnectedTo::Offset(offset) => self.write_vec(&offset)?,
+            ConnectedTo::Bone(b) => self.write_bone_index(b)?,
+        }
+        if let Some(addition) = addition {
+            self.write_bone_index(addition.bone)?;
+            self.write_f32(addition.ratio)?;
+        }
+        if let Some(axis) = &bone.fixed_pole {
+            self.write_vec(axis)?;
+        }
+        if let Some(pole) = &bone.local_pole {
+            self.write_vec(&pole.x)?;
+            self.write_vec(&pole.z)?;
+        }
+        if let Some(parent) = bone.external_parent {
+            let parent = i32::try_from(parent)
+                .map_err(|_| Error::InvalidData("bone::external_parent".into()))?;
+            self.write_i32(parent)?;
+        }
+        if let Some(ik) = &bone.ik {
+            self.write_bone_index(ik.bone)?;
+            self.write_u32(ik.loop_count)?;
+            self.write_f32(ik.angle)?;
+            self.write_len(ik.links.len())?;
+            for link in ik.links.iter() {
+                self.write_bone_index(link.bone)?;
+                match &link.limits {
+                    Some(limits) => {
+                        self.write_u8(1)?;
+                        self.write_vec(&limits.lower)?;
+                        self.write_vec(&limits.upper)?;
+                    }
+                    None => self.write_u8(0)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn morph(&mut self, morph: &Morph) -> Result<(), Error> {
+        self.write_string(&morph.name)?;
+        self.write_string(&morph.name_en)?;
+        self.write_u8(match morph.panel {
+            Panel::Reserved => 0,
+            Panel::Eyebrow => 1,
+            Panel::Eye => 2,
+            Panel::Mouth => 3,
+            Panel::Other => 4,
+        })?;
+        match &morph.kind {
+            morph::Kind::Group(offsets) => {
+                self.write_u8(0)?;
+                self.write_len(offsets.len())?;
+                for offset in offsets.iter() {
+                    self.write_morph_index(offset.morph)?;
+                    self.write_f32(offset.ratio)?;
+                }
+            }
+            morph::Kind::Vertex(offsets) => {
+                self.write_u8(1)?;
+                self.write_len(offsets.len())?;
+                for offset in offsets.iter() {
+                    self.write_vertex_index(offset.vertex)?;
+                    self.write_vec(&offset.offset)?;
+                }
+            }
+            morph::Kind::Bone(offsets) => {
+                self.write_u8(2)?;
+                self.write_len(offsets.len())?;
+                for offset in offsets.iter() {
+                    self.write_bone_index(offset.bone)?;
+                    self.write_vec(&offset.offset)?;
+                    self.write_vec(&offset.rotation)?;
+                }
+            }
+            morph::Kind::Uv(offsets) | morph::Kind::ExtendedUv(_, offsets) => {
+                let kind = match morph.kind {
+                    morph::Kind::ExtendedUv(i, _) if i < 4 => 4 + i as u8,
+                    morph::Kind::ExtendedUv(..) => {
+                        return Err(Error::InvalidData("morph::kind".into()))
+                    }
+                    _ => 3,
+                };
+                self.write_u8(kind)?;
+                self.write_len(offsets.len())?;
+                for offset in offsets.iter() {
+                    self.write_vertex_index(offset.vertex)?;
+                    self.write_vec(&offset.offset)?;
+                }
+            }
+            morph::Kind::Maerial(offsets) => {
+                self.write_u8(8)?;
+                self.write_len(offsets.len())?;
+                for offset in offsets.iter() {
+                    self.write_material_index(offset.material)?;
+                    self.write_u8(match offset.op {
+                        morph::MaterialOp::Mul => 0,
+                        morph::MaterialOp::Add => 1,
+                    })?;
+                    self.write_vec(&offset.diffuse)?;
+                    self.write_vec(&offset.specular)?;
+                    self.write_f32(offset.specular_power)?;
+                    self.write_vec(&offset.ambient)?;
+                    self.write_vec(&offset.edge_color)?;
+                    self.write_f32(offset.edge_size)?;
+                    self.write_vec(&offset.texture)?;
+                    self.write_vec(&offset.sphere)?;
+                    self.write_vec(&offset.toon)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn display_group(&mut self, display_group: &DisplayGroup) -> Result<(), Error> {
+        self.write_string(&display_group.name)?;
+        self.write_string(&display_group.name_en)?;
+        self.write_u8(u8::from(display_group.special))?;
+        self.write_len(display_group.elements.len())?;
+        for element in display_group.elements.iter() {
+            match *element {
+                DisplayElement::Bone(b) => {
+                    self.write_u8(0)?;
+                    self.write_bone_index(b)?;
+                }
+                DisplayElement::Morph(m) => {
+                    self.write_u8(1)?;
+                    self.write_morph_index(m)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rigid(&mut self, rigid: &Rigid) -> Result<(), Error> {
+        self.write_string(&rigid.name)?;
+        self.write_string(&rigid.name_en)?;
+        self.write_bone_index(rigid.bone)?;
+        self.write_u8(rigid.group)?;
+        self.write_u16(rigid.non_collision_groups)?;
+        self.write_u8(match rigid.shape {
+            rigid::Shape::Sphere => 0,
+            rigid::Shape::Box => 1,
+            rigid::Shape::Capsule => 2,
+        })?;
+        self.write_vec(&rigid.size)?;
+        self.write_vec(&rigid.position)?;
+        self.write_vec(&rigid.rotation)?;
+        self.write_f32(rigid.mass)?;
+        self.write_f32(rigid.dump_translation)?;
+        self.write_f32(rigid.dump_rotation)?;
+        self.write_f32(rigid.repulsive)?;
+        self.write_f32(rigid.friction)?;
+        self.write_u8(match rigid.method {
+            rigid::Method::Static => 0,
+            rigid::Method::Dynamic => 1,
+            rigid::Method::DynamicWithBone => 2,
+        })
+    }
+
+    pub fn joint(&mut self, joint: &Joint) -> Result<(), Error> {
+        self.write_string(&joint.name)?;
+        self.write_string(&joint.name_en)?;
+        self.write_u8(0)?;
+        self.write_rigid_index(joint.rigids[0])?;
+        self.write_rigid_index(joint.rigids[1])?;
+        self.write_vec(&joint.position)?;
+        self.write_vec(&joint.rotation)?;
+        self.write_vec(&joint.limit_translation.lower)?;
+        self.write_vec(&joint.limit_translation.upper)?;
+        self.write_vec(&joint.limit_rotation.lower)?;
+        self.write_vec(&joint.limit_rotation.upper)?;
+        self.write_vec(&joint.spring_translation)?;
+        self.write_vec(&joint.spring_rotation)
+    }
+}